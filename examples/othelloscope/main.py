@@ -5,7 +5,7 @@ import numpy as np
 import torch
 import transformer_lens
 from transformer_lens import HookedTransformer, HookedTransformerConfig
-from transformer_scope import Payload, PayloadBuilder, Scope
+from transformer_scope import Payload, PayloadBuilder, Quantization, Scope
 
 from examples.othelloscope import calculations
 
@@ -112,15 +112,14 @@ print("Creating payload...")
 # Load html template for neuron sites.
 neuron_template = open(os.path.join(path, "mlp_neuron.html"), "r").read()
 
-# Convert the focus game moves to string labels.
-focus_game_moves_str = np.vectorize(calculations.int_to_label)(
-    focus_game_moves.detach().cpu().numpy()
-).astype("object")
-
 # Create a payload builder with 8 layers and 2048 neurons per layer.
 payload_builder = PayloadBuilder(8, 2048)
 # Add the template for the neuron sites to the payload builder.
 payload_builder.mlp_neuron_template(neuron_template)
+# Add the labels of all moves as the vocabulary.
+payload_builder.vocabulary(
+    [calculations.int_to_label(i) for i in range(model_config.d_vocab)]
+)
 # Add values for the ownership heatmap.
 payload_builder.add_f32_value(
     "ownership_heatmap", ownership_heatmap.detach().cpu().numpy(), Scope.Neuron
@@ -144,16 +143,24 @@ payload_builder.add_f32_value(
 payload_builder.set_rank_values("ownership_heatmap_stds")
 
 # Add activation values in focus games for each neuron.
+# These dominate the payload size, so they are stored with 8 bits per activation.
 payload_builder.add_f32_value(
     "focus_game_neuron_activations",
     focus_game_neuron_activations.detach().cpu().numpy(),
     Scope.Neuron,
+    Quantization.U8,
+)
+# Add the focus game moves as token ids, which are shown using the vocabulary.
+payload_builder.add_u32_value(
+    "focus_game_moves",
+    focus_game_moves.detach().cpu().numpy().astype(np.uint32),
+    Scope.Global,
 )
-# Add the focus game moves as string values.
-payload_builder.add_str_value("focus_game_moves", focus_game_moves_str, Scope.Global)
 
 # Build the payload.
 payload = payload_builder.build()
+for key, (max_abs_error, rms_error) in payload.quantization_errors().items():
+    print(f"Quantization error of {key}: max {max_abs_error:.2e}, RMS {rms_error:.2e}")
 
 print("Writing payload to file...")
 payload.to_file(os.path.join(output_path, "payload"))
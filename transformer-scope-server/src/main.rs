@@ -3,16 +3,77 @@ use std::{
     io::{self, Write},
 };
 
-use actix_web::{get, web, App, HttpServer, Responder};
-use transformer_scope::html;
+use actix_web::{
+    error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound},
+    get,
+    http::header,
+    post, web, App, HttpResponse, HttpServer, Responder,
+};
+use maud::Markup;
+use serde::{Deserialize, Serialize};
+use transformer_scope::{
+    html::{self, PageImages},
+    Annotation, SearchQuery, SearchQueryError,
+};
 
 mod state;
-use state::ApplicationState;
+use state::{ApplicationState, Images, Page};
+
+/// Generates a page and keeps its images, which are requested right after the page.
+fn generate_page(data: &ApplicationState, page: Page) -> (Markup, Images) {
+    let payload = data.payload();
+    let annotations = data.annotations().read().unwrap();
+    let images = PageImages::default();
+    let markup = match page {
+        Page::Index => html::generate_index_page(payload, &annotations, false, &images),
+        Page::Layer(layer_index) => {
+            html::generate_layer_page(layer_index, payload, &annotations, false, &images)
+        }
+        Page::Neuron(layer_index, neuron_index) => html::generate_neuron_page(
+            layer_index,
+            neuron_index,
+            payload,
+            &annotations,
+            false,
+            &images,
+        ),
+    };
+    (markup, data.cache_images(page, images))
+}
+
+/// Responds with a PNG image of a page. The page is only generated again if its images are no longer cached.
+fn image_response(data: &ApplicationState, page: Page, image_index: usize) -> Option<HttpResponse> {
+    let images = data
+        .cached_images(page)
+        .unwrap_or_else(|| generate_page(data, page).1);
+    let image = images.get(image_index)?.clone();
+    Some(HttpResponse::Ok().content_type("image/png").body(image))
+}
+
+/// The layer page with the given original index, if the layer is in the payload.
+fn layer_page(data: &ApplicationState, layer_index: usize) -> Option<Page> {
+    let layer_index = data.payload().layer_index_from_original(layer_index)?;
+    Some(Page::Layer(layer_index))
+}
+
+/// The neuron page with the given original indices, if the neuron is in the payload.
+fn neuron_page(data: &ApplicationState, layer_index: usize, neuron_index: usize) -> Option<Page> {
+    // Paths use the indices of the original model, which differ from the payload indices for subsets.
+    let payload = data.payload();
+    let layer_index = payload.layer_index_from_original(layer_index)?;
+    let neuron_index = payload.neuron_index_from_original(neuron_index)?;
+    Some(Page::Neuron(layer_index, neuron_index))
+}
 
 #[get("/")]
 async fn index(data: web::Data<ApplicationState>) -> impl Responder {
-    let data = data.as_ref();
-    html::generate_index_page(data.payload(), false)
+    generate_page(&data, Page::Index).0
+}
+
+#[get("/L{layer_index}")]
+async fn layer(data: web::Data<ApplicationState>, path: web::Path<usize>) -> impl Responder {
+    let page = layer_page(&data, path.into_inner())?;
+    Some(generate_page(&data, page).0)
 }
 
 #[get("/L{layer_index}/N{neuron_index}")]
@@ -21,8 +82,217 @@ async fn neuron(
     path: web::Path<(usize, usize)>,
 ) -> impl Responder {
     let (layer_index, neuron_index) = path.into_inner();
+    let page = neuron_page(&data, layer_index, neuron_index)?;
+    Some(generate_page(&data, page).0)
+}
+
+#[get("/images/{image_index}.png")]
+async fn index_image(data: web::Data<ApplicationState>, path: web::Path<usize>) -> impl Responder {
+    image_response(&data, Page::Index, path.into_inner())
+}
+
+#[get("/L{layer_index}/images/{image_index}.png")]
+async fn layer_image(
+    data: web::Data<ApplicationState>,
+    path: web::Path<(usize, usize)>,
+) -> impl Responder {
+    let (layer_index, image_index) = path.into_inner();
+    let page = layer_page(&data, layer_index)?;
+    image_response(&data, page, image_index)
+}
+
+#[get("/L{layer_index}/N{neuron_index}/images/{image_index}.png")]
+async fn neuron_image(
+    data: web::Data<ApplicationState>,
+    path: web::Path<(usize, usize, usize)>,
+) -> impl Responder {
+    let (layer_index, neuron_index, image_index) = path.into_inner();
+    let page = neuron_page(&data, layer_index, neuron_index)?;
+    image_response(&data, page, image_index)
+}
+
+#[get("/clusters/{key}/C{cluster_index}")]
+async fn cluster(
+    data: web::Data<ApplicationState>,
+    path: web::Path<(String, usize)>,
+) -> impl Responder {
+    let (key, cluster_index) = path.into_inner();
+    let payload = data.payload();
+    let clustering = payload.clustering(&key)?;
+    if cluster_index >= clustering.num_clusters {
+        return None;
+    }
+    let annotations = data.annotations().read().unwrap();
+    Some(html::generate_cluster_page(
+        payload,
+        &annotations,
+        clustering,
+        cluster_index,
+        false,
+    ))
+}
+
+#[derive(Deserialize)]
+struct SearchParameters {
+    #[serde(default)]
+    q: String,
+}
+
+fn search_neurons(
+    data: &ApplicationState,
+    query: &str,
+) -> Result<Vec<(usize, usize)>, SearchQueryError> {
+    let query = SearchQuery::parse(query)?;
+    data.search_index().read().unwrap().search(&query)
+}
+
+#[get("/search")]
+async fn search(
+    data: web::Data<ApplicationState>,
+    parameters: web::Query<SearchParameters>,
+) -> impl Responder {
+    let query = parameters.into_inner().q;
+    let results = search_neurons(&data, &query);
+    let annotations = data.annotations().read().unwrap();
+    html::generate_search_page(&annotations, &query, results.as_deref())
+}
+
+#[derive(Serialize)]
+struct SearchResult {
+    layer: usize,
+    neuron: usize,
+}
+
+#[get("/search.json")]
+async fn search_json(
+    data: web::Data<ApplicationState>,
+    parameters: web::Query<SearchParameters>,
+) -> actix_web::Result<web::Json<Vec<SearchResult>>> {
+    let results = search_neurons(&data, &parameters.q).map_err(ErrorBadRequest)?;
+    Ok(web::Json(
+        results
+            .into_iter()
+            .map(|(layer_index, neuron_index)| SearchResult {
+                layer: layer_index,
+                neuron: neuron_index,
+            })
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct AnnotationForm {
+    label: String,
+    tags: String,
+    author: String,
+}
+
+/// Responds with an error unless the neuron with the given original indices is in the payload.
+fn check_neuron_exists(
+    data: &ApplicationState,
+    layer_index: usize,
+    neuron_index: usize,
+) -> actix_web::Result<()> {
+    let payload = data.payload();
+    if payload.layer_index_from_original(layer_index).is_some()
+        && payload.neuron_index_from_original(neuron_index).is_some()
+    {
+        Ok(())
+    } else {
+        Err(ErrorNotFound(format!(
+            "Layer {layer_index} neuron {neuron_index} is not in the payload."
+        )))
+    }
+}
+
+fn redirect_to_neuron(layer_index: usize, neuron_index: usize) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, format!("/L{layer_index}/N{neuron_index}")))
+        .finish()
+}
+
+#[get("/L{layer_index}/N{neuron_index}/annotation")]
+async fn get_annotation(
+    data: web::Data<ApplicationState>,
+    path: web::Path<(usize, usize)>,
+) -> actix_web::Result<web::Json<Annotation>> {
+    let (layer_index, neuron_index) = path.into_inner();
+    check_neuron_exists(&data, layer_index, neuron_index)?;
+
+    let annotations = data.annotations().read().unwrap();
+    annotations
+        .get(layer_index, neuron_index)
+        .cloned()
+        .map(web::Json)
+        .ok_or_else(|| ErrorNotFound("No annotation for this neuron."))
+}
+
+#[post("/L{layer_index}/N{neuron_index}/annotation")]
+async fn set_annotation(
+    data: web::Data<ApplicationState>,
+    path: web::Path<(usize, usize)>,
+    form: web::Form<AnnotationForm>,
+) -> actix_web::Result<HttpResponse> {
+    let (layer_index, neuron_index) = path.into_inner();
+    check_neuron_exists(&data, layer_index, neuron_index)?;
+    let AnnotationForm {
+        label,
+        tags,
+        author,
+    } = form.into_inner();
+    let label = label.trim();
+    if label.is_empty() {
+        return Err(ErrorBadRequest("An annotation needs a label."));
+    }
+    let annotation = Annotation {
+        label: label.to_string(),
+        tags: tags
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+        author: author.trim().to_string(),
+    };
+
+    // Only change the served annotations once they are saved, so a failed write changes nothing.
+    let mut annotations = data.annotations().write().unwrap();
+    let mut updated_annotations = annotations.clone();
+    updated_annotations.set(layer_index, neuron_index, annotation.clone());
+    data.save_annotations(&updated_annotations)
+        .map_err(ErrorInternalServerError)?;
+    *annotations = updated_annotations;
+    data.search_index().write().unwrap().set_annotation(
+        layer_index,
+        neuron_index,
+        Some(annotation),
+    );
+    Ok(redirect_to_neuron(layer_index, neuron_index))
+}
+
+#[post("/L{layer_index}/N{neuron_index}/annotation/delete")]
+async fn delete_annotation(
+    data: web::Data<ApplicationState>,
+    path: web::Path<(usize, usize)>,
+) -> actix_web::Result<HttpResponse> {
+    let (layer_index, neuron_index) = path.into_inner();
+    check_neuron_exists(&data, layer_index, neuron_index)?;
 
-    html::generate_neuron_page(layer_index, neuron_index, data.payload(), false)
+    let mut annotations = data.annotations().write().unwrap();
+    let mut updated_annotations = annotations.clone();
+    if updated_annotations
+        .remove(layer_index, neuron_index)
+        .is_some()
+    {
+        data.save_annotations(&updated_annotations)
+            .map_err(ErrorInternalServerError)?;
+        *annotations = updated_annotations;
+        data.search_index()
+            .write()
+            .unwrap()
+            .set_annotation(layer_index, neuron_index, None);
+    }
+    Ok(redirect_to_neuron(layer_index, neuron_index))
 }
 
 #[actix_web::main]
@@ -41,7 +311,17 @@ async fn main() -> std::io::Result<()> {
             .service(actix_files::Files::new("/static", "./static"))
             .app_data(data.clone())
             .service(index)
+            .service(layer)
             .service(neuron)
+            .service(cluster)
+            .service(index_image)
+            .service(layer_image)
+            .service(neuron_image)
+            .service(search)
+            .service(search_json)
+            .service(get_annotation)
+            .service(set_annotation)
+            .service(delete_annotation)
     })
     .bind(("127.0.0.1", 8080))?
     .run()
@@ -1,10 +1,34 @@
-use std::path::Path;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
 
-use transformer_scope::Payload;
+use transformer_scope::{html::PageImages, Annotations, AnnotationsError, Payload, SearchIndex};
+
+/// The number of pages whose images are kept.
+const CACHED_PAGES: usize = 32;
+
+/// The PNG images of a page, in the order the page refers to them.
+pub type Images = Arc<Vec<Vec<u8>>>;
+
+/// A page with images, identified by payload indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Page {
+    Index,
+    Layer(usize),
+    Neuron(usize, usize),
+}
 
 #[derive(Clone)]
 pub struct ApplicationState {
     payload: Payload,
+    annotations: Arc<RwLock<Annotations>>,
+    annotations_path: PathBuf,
+    search_index: Arc<RwLock<SearchIndex>>,
+    /// The images of the most recently generated pages, oldest first, so they are drawn once per page view
+    /// rather than once per image.
+    images: Arc<Mutex<VecDeque<(Page, Images)>>>,
 }
 
 impl ApplicationState {
@@ -13,8 +37,19 @@ impl ApplicationState {
         P: AsRef<Path>,
     {
         fn inner(path: &Path) -> ApplicationState {
-            let payload = Payload::from_file(path);
-            ApplicationState { payload }
+            let payload = Payload::from_file(path)
+                .unwrap_or_else(|error| panic!("Failed to load payload: {error}"));
+            let annotations_path = Annotations::sidecar_path(path);
+            let annotations = Annotations::from_file(&annotations_path)
+                .unwrap_or_else(|error| panic!("Failed to load annotations: {error}"));
+            let search_index = SearchIndex::new(&payload, &annotations);
+            ApplicationState {
+                payload,
+                annotations: Arc::new(RwLock::new(annotations)),
+                annotations_path,
+                search_index: Arc::new(RwLock::new(search_index)),
+                images: Arc::new(Mutex::new(VecDeque::new())),
+            }
         }
         inner(path.as_ref())
     }
@@ -22,4 +57,39 @@ impl ApplicationState {
     pub fn payload(&self) -> &Payload {
         &self.payload
     }
+
+    pub fn annotations(&self) -> &RwLock<Annotations> {
+        &self.annotations
+    }
+
+    /// The search index, which has to be updated when annotations change.
+    pub fn search_index(&self) -> &RwLock<SearchIndex> {
+        &self.search_index
+    }
+
+    /// Writes the annotations to the sidecar file next to the payload.
+    pub fn save_annotations(&self, annotations: &Annotations) -> Result<(), AnnotationsError> {
+        annotations.to_file(&self.annotations_path)
+    }
+
+    /// Keeps the images generated for a page, replacing older images of it.
+    pub fn cache_images(&self, page: Page, images: PageImages) -> Images {
+        let images = Arc::new(images.into_vec());
+        let mut cache = self.images.lock().unwrap();
+        cache.retain(|(cached_page, _)| *cached_page != page);
+        if cache.len() == CACHED_PAGES {
+            cache.pop_front();
+        }
+        cache.push_back((page, images.clone()));
+        images
+    }
+
+    pub fn cached_images(&self, page: Page) -> Option<Images> {
+        self.images
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(cached_page, _)| *cached_page == page)
+            .map(|(_, images)| images.clone())
+    }
 }
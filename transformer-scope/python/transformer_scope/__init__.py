@@ -2,6 +2,16 @@
 
 from .logit_attribution import mlp_logit_attributions
 from .payload import Payload, PayloadBuilder
-from .transformer_scope import Scope, setup_keyboard_interrupt
+from .top_k_accumulator import TopKAccumulator
+from .transformer_scope import (
+    ClusteringMethod,
+    PayloadSchemaError,
+    Quantization,
+    Scope,
+    SimilarityMetric,
+    TemplateParseError,
+    ValueRequirement,
+    setup_keyboard_interrupt,
+)
 
 setup_keyboard_interrupt()
@@ -1,3 +1,5 @@
+from typing import Dict, List, Optional, Tuple
+
 import numpy as np
 
 from . import transformer_scope as ts
@@ -7,8 +9,33 @@ class Payload:
     def to_file(self, path: str) -> None:
         self.payload.to_file(path)
 
-    def generate_site_files(self, dir_path: str) -> None:
-        self.payload.generate_site_files(dir_path)
+    def generate_site_files(
+        self, dir_path: str, annotations_path: Optional[str] = None
+    ) -> None:
+        """
+        Generates a static site in the given directory.
+
+        :param annotations_path: Path of an annotations file to show on the pages,
+            usually `<payload path>.annotations.json`.
+        """
+        self.payload.generate_site_files(dir_path, annotations_path)
+
+    def quantization_errors(self) -> Dict[str, Tuple[float, float]]:
+        """
+        Returns the maximum absolute error and the RMS error of the finite elements of every quantized value.
+        """
+        return self.payload.quantization_errors()
+
+    def subset(
+        self, layers: Optional[List[int]] = None, neurons: Optional[List[int]] = None
+    ) -> "Payload":
+        """
+        Returns a payload containing only the given layers and neurons.
+        Indices refer to the original model and the same neurons are kept in every selected layer.
+        """
+        payload = Payload()
+        payload.payload = self.payload.subset(layers, neurons)
+        return payload
 
 
 class PayloadBuilder:
@@ -20,20 +47,180 @@ class PayloadBuilder:
         self.payload_builder = ts.PayloadBuilder(num_layers, num_mlp_neurons)
 
     def mlp_neuron_template(self, template: str) -> None:
+        """
+        Sets the template of the neuron pages.
+
+        :raises TemplateParseError: If the template is invalid. The message contains the line and column of the error.
+        :raises PayloadSchemaError: If the template shows a value added before with a different data type, scope or shape.
+            The template is not set then.
+        """
         self.payload_builder.mlp_neuron_template(template)
 
+    def index_template(self, template: str) -> None:
+        """
+        Sets the template of the index page. It may show global values and the elements `$ranked_neurons()`,
+        `$clusters()`, `$values()` and `$search()`. Without it, the index shows the search form, the clusters and the
+        ranked neurons.
+
+        :raises TemplateParseError: If the template is invalid. The message contains the line and column of the error.
+        :raises PayloadSchemaError: If the template shows a value added before with a different data type, scope or shape.
+            The template is not set then.
+        """
+        self.payload_builder.index_template(template)
+
+    def layer_template(self, template: str) -> None:
+        """
+        Sets the template of the layer pages. It may show global and layer values, numbers of every neuron in the layer
+        with `$layer_heatmap(value)` and `$top_neurons(value)`, and `$statistics(value)` of any value over the layer.
+        Without it, the layer pages list the top neurons by rank.
+
+        :raises TemplateParseError: If the template is invalid. The message contains the line and column of the error.
+        :raises PayloadSchemaError: If the template shows a value added before with a different data type, scope or shape.
+            The template is not set then.
+        """
+        self.payload_builder.layer_template(template)
+
+    def add_partial(self, name: str, partial: str) -> None:
+        """
+        Adds a partial, a part of a template which templates include with `$include(name, parameter=argument)`.
+        Parameters are written as `{{parameter}}` in the partial.
+
+        :raises TemplateParseError: If a parameter is invalid. The rest of the partial is checked when it is included.
+        :raises PayloadSchemaError: If a template including the partial shows a value added before with a different
+            data type, scope or shape. The partial is not added then.
+        """
+        self.payload_builder.add_partial(name, partial)
+
+    def schema(self) -> List[ts.ValueRequirement]:
+        """
+        Returns the values shown by the templates set so far, with the data types, scopes and number of axes
+        after the scope they may have. Values added later are checked against it.
+
+        :raises PayloadBuildError: If a template includes a missing partial or uses an argument incorrectly.
+        """
+        return self.payload_builder.schema()
+
+    def vocabulary(
+        self, tokens: List[str], token_bytes: Optional[List[bytes]] = None
+    ) -> None:
+        """
+        Sets the token vocabulary, so u32 values of token ids can be shown as text.
+
+        :param tokens: The text of every token, indexed by token id.
+        :param token_bytes: Optionally the raw bytes of every token.
+        """
+        self.payload_builder.vocabulary(tokens, token_bytes)
+
     def add_str_value(self, key: str, value: np.ndarray, scope: ts.Scope) -> None:
+        """
+        :raises PayloadSchemaError: If the templates show the value with a different data type, scope or shape.
+        """
         self.payload_builder.add_str_value(key, value, scope)
 
     def add_u32_value(self, key: str, value: np.ndarray, scope: ts.Scope) -> None:
+        """
+        :raises PayloadSchemaError: If the templates show the value with a different data type, scope or shape.
+        """
         self.payload_builder.add_u32_value(key, value, scope)
 
-    def add_f32_value(self, key: str, value: np.ndarray, scope: ts.Scope) -> None:
-        self.payload_builder.add_f32_value(key, value, scope)
+    def add_f32_value(
+        self,
+        key: str,
+        value: np.ndarray,
+        scope: ts.Scope,
+        quantization: Optional[ts.Quantization] = None,
+    ) -> None:
+        """
+        Adds an f32 value.
+
+        :param quantization: If given, the value is stored with 8 or 16 bits per element
+            with a separate scale and offset for every layer or neuron in the scope.
+            NaN and infinite elements are stored as NaN.
+        :raises PayloadSchemaError: If the templates show the value with a different data type, scope or shape.
+        """
+        self.payload_builder.add_f32_value(key, value, scope, quantization)
+
+    def add_token_snippets_value(
+        self, key: str, snippets: List[List[List[Tuple[List[str], List[float], int]]]]
+    ) -> None:
+        """
+        Adds a list of token snippets for every neuron, e.g. max activating examples.
+
+        :param snippets: `snippets[layer][neuron]` is a list of `(tokens, activations, focus)` tuples
+            with one activation per token, where `focus` is the index of the token the snippet was selected for.
+            The number of snippets may differ between neurons.
+        :raises ValueError: If snippets are not given for every neuron, do not have one activation per token
+            or their focus is not one of their tokens.
+        :raises PayloadSchemaError: If the templates show the value with a different data type or scope.
+        """
+        self.payload_builder.add_token_snippets_value(key, snippets)
 
     def set_rank_values(self, key: str) -> None:
         self.payload_builder.set_rank_values(key)
 
+    def add_nearest_neighbours(
+        self,
+        neighbours_key: str,
+        scores_key: str,
+        source_key: str,
+        k: int,
+        metric: ts.SimilarityMetric = ts.SimilarityMetric.Cosine,
+        chunk_size: Optional[int] = None,
+    ) -> None:
+        """
+        Computes the k most similar neurons of every neuron when the payload is built.
+        Similarity is measured between the flattened per neuron arrays of a Neuron scoped f32 value.
+
+        :param neighbours_key: Name of the u32 value with shape (num_layers, num_mlp_neurons, k, 2)
+            holding the layer and neuron index of every neighbour.
+        :param scores_key: Name of the f32 value with the similarity of every neighbour.
+        :param chunk_size: If given, similarities are computed for this many neurons at a time
+            instead of all at once, which saves memory for models with many neurons.
+        :raises ValueError: If the source value is missing, not a Neuron scoped f32 value,
+            a neighbour value is already set, k is not smaller than the number of neurons or chunk_size is 0.
+        """
+        self.payload_builder.add_nearest_neighbours(
+            neighbours_key, scores_key, source_key, k, metric, chunk_size
+        )
+
+    def add_connections(self, key: str, w_in_key: str, w_out_key: str, k: int) -> None:
+        """
+        Computes the k strongest connections to neurons in earlier and later layers for every neuron
+        when the payload is built, using the virtual weights W_out[i] @ W_in[j] between layers i < j.
+
+        :param key: Prefix of the added values {key}_upstream, {key}_upstream_weights,
+            {key}_downstream and {key}_downstream_weights, which can be shown with $connections(key).
+        :param w_in_key: Name of a Global f32 value with shape (num_layers, d_model, num_mlp_neurons).
+        :param w_out_key: Name of a Global f32 value with shape (num_layers, num_mlp_neurons, d_model).
+        :raises ValueError: If a weight value is missing, not a Global f32 value or has the wrong shape,
+            or one of the added values is already set.
+        """
+        self.payload_builder.add_connections(key, w_in_key, w_out_key, k)
+
+    def cluster_neurons(
+        self,
+        key: str,
+        source_key: str,
+        num_clusters: int,
+        method: ts.ClusteringMethod = ts.ClusteringMethod.KMeans,
+        metric: ts.SimilarityMetric = ts.SimilarityMetric.Cosine,
+        max_iterations: int = 100,
+    ) -> None:
+        """
+        Clusters the neurons by the flattened per neuron arrays of a Neuron scoped f32 value
+        when the payload is built. A page is generated for every cluster.
+
+        :param key: Name of the Neuron scoped u32 value holding the cluster of every neuron.
+        :param method: Agglomerative clustering needs memory quadratic in the number of neurons.
+        :param max_iterations: Maximum number of iterations of k-means. With 0, every neuron is assigned to
+            the nearest initial centroid.
+        :raises ValueError: If the source value is missing or not a Neuron scoped f32 value, the key is already set
+            or num_clusters is not between 1 and the number of neurons.
+        """
+        self.payload_builder.cluster_neurons(
+            key, source_key, num_clusters, method, metric, max_iterations
+        )
+
     def build(self) -> Payload:
         payload = Payload()
         payload.payload = self.payload_builder.build()
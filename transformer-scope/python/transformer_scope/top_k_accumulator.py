@@ -0,0 +1,66 @@
+from typing import List, Optional
+
+import numpy as np
+
+from . import transformer_scope as ts
+from .payload import PayloadBuilder
+
+
+class TopKAccumulator:
+    """
+    Accumulates max activating examples for every neuron from batches of activations,
+    so the full activation tensor never has to be kept in memory.
+    """
+
+    def __init__(
+        self,
+        num_layers: int,
+        num_neurons: int,
+        k: int,
+        num_quantiles: int = 0,
+        examples_per_quantile: int = 0,
+        context_before: int = 10,
+        context_after: int = 5,
+    ):
+        """
+        :param k: Number of top activating examples to keep for every neuron.
+        :param num_quantiles: Number of quantiles to sample examples from. 0 disables quantile examples.
+        :param examples_per_quantile: Number of examples to keep for every quantile.
+        :param context_before: Number of tokens to include before the activating token.
+        :param context_after: Number of tokens to include after the activating token.
+        """
+        self.accumulator = ts.TopKAccumulator(
+            num_layers,
+            num_neurons,
+            k,
+            num_quantiles,
+            examples_per_quantile,
+            context_before,
+            context_after,
+        )
+
+    def add_batch(self, activations: np.ndarray, tokens: np.ndarray) -> None:
+        """
+        :param activations: f32 array of shape (num_layers, num_neurons, batch_size, sequence_length).
+        :param tokens: u32 array of token ids with shape (batch_size, sequence_length).
+        :raises ValueError: If the shapes of the activations or tokens do not match.
+        """
+        self.accumulator.add_batch(activations, tokens)
+
+    def add_to_payload_builder(
+        self,
+        payload_builder: PayloadBuilder,
+        top_k_key: str,
+        vocabulary: Optional[List[str]] = None,
+        quantiles_key: Optional[str] = None,
+    ) -> None:
+        """
+        Adds the accumulated examples to the payload builder as token snippet values.
+        If no vocabulary is given, the vocabulary of the payload builder is used.
+
+        :raises ValueError: If there is no vocabulary, a token is not in the vocabulary
+            or `quantiles_key` is given but no quantile examples were collected.
+        """
+        self.accumulator.add_to_payload_builder(
+            payload_builder.payload_builder, top_k_key, vocabulary, quantiles_key
+        )
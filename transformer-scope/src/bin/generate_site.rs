@@ -1,14 +1,17 @@
 use std::env;
 
-use transformer_scope::{html, Payload};
+use transformer_scope::{html, Annotations, Payload};
 
 pub fn main() {
     let payload_path = env::args().nth(1).unwrap();
     let site_path = env::args().nth(2).unwrap();
 
-    let payload = Payload::from_file(payload_path);
+    let payload = Payload::from_file(&payload_path)
+        .unwrap_or_else(|error| panic!("Failed to load payload: {error}"));
+    let annotations = Annotations::from_file(Annotations::sidecar_path(&payload_path))
+        .unwrap_or_else(|error| panic!("Failed to load annotations: {error}"));
 
     let start_time = std::time::Instant::now();
-    html::generate_site_in_dir(site_path, &payload);
+    html::generate_site_in_dir(site_path, &payload, &annotations);
     println!("Site generated in {:?}", start_time.elapsed());
 }
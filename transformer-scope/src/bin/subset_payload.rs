@@ -0,0 +1,49 @@
+use std::env;
+
+use transformer_scope::Payload;
+
+fn parse_indices(indices: &str) -> Vec<usize> {
+    indices
+        .split(',')
+        .map(|index| {
+            index
+                .trim()
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid index: '{index}'"))
+        })
+        .collect()
+}
+
+/// Usage: subset_payload <payload_path> <output_path> [--layers 0,3] [--neurons 12,400,1021]
+pub fn main() {
+    let mut args = env::args().skip(1);
+    let payload_path = args.next().expect("Missing payload path.");
+    let output_path = args.next().expect("Missing output path.");
+
+    let mut layers = None;
+    let mut neurons = None;
+    while let Some(arg) = args.next() {
+        let indices = args
+            .next()
+            .unwrap_or_else(|| panic!("Missing indices after '{arg}'."));
+        match arg.as_str() {
+            "--layers" => layers = Some(parse_indices(&indices)),
+            "--neurons" => neurons = Some(parse_indices(&indices)),
+            _ => panic!("Unknown argument: '{arg}'"),
+        }
+    }
+
+    let payload = Payload::from_file(payload_path)
+        .unwrap_or_else(|error| panic!("Failed to load payload: {error}"));
+    let subset = payload
+        .subset(layers.as_deref(), neurons.as_deref())
+        .unwrap_or_else(|error| panic!("Failed to create subset: {error}"));
+    subset
+        .to_file(output_path)
+        .unwrap_or_else(|error| panic!("Failed to write subset: {error}"));
+    println!(
+        "Subset with {} layers and {} neurons per layer written.",
+        subset.num_layers(),
+        subset.num_mlp_neurons()
+    );
+}
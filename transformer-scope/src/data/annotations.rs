@@ -0,0 +1,191 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AnnotationsError {
+    #[error("Failed to access annotations file: {0}")]
+    Io(#[from] io::Error),
+    #[error("Failed to parse annotations file: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// A note about a single neuron.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub label: String,
+    pub tags: Vec<String>,
+    pub author: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AnnotationEntry {
+    layer: usize,
+    neuron: usize,
+    #[serde(flatten)]
+    annotation: Annotation,
+}
+
+/// Annotations of neurons, stored as a JSON sidecar file next to the payload.
+///
+/// Neurons are identified by their indices in the original model, so annotations stay valid for subsets of a payload.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<AnnotationEntry>", into = "Vec<AnnotationEntry>")]
+pub struct Annotations {
+    annotations: BTreeMap<(usize, usize), Annotation>,
+}
+
+impl From<Vec<AnnotationEntry>> for Annotations {
+    fn from(entries: Vec<AnnotationEntry>) -> Self {
+        let annotations = entries
+            .into_iter()
+            .map(|entry| ((entry.layer, entry.neuron), entry.annotation))
+            .collect();
+        Self { annotations }
+    }
+}
+
+impl From<Annotations> for Vec<AnnotationEntry> {
+    fn from(annotations: Annotations) -> Self {
+        annotations
+            .annotations
+            .into_iter()
+            .map(|((layer, neuron), annotation)| AnnotationEntry {
+                layer,
+                neuron,
+                annotation,
+            })
+            .collect()
+    }
+}
+
+impl Annotations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The path of the annotations file belonging to the payload at the given path.
+    pub fn sidecar_path<P: AsRef<Path>>(payload_path: P) -> PathBuf {
+        let mut path = payload_path.as_ref().as_os_str().to_owned();
+        path.push(".annotations.json");
+        PathBuf::from(path)
+    }
+
+    /// Loads annotations from the given file. If the file does not exist, no annotations are returned.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, AnnotationsError> {
+        match std::fs::read(path.as_ref()) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), AnnotationsError> {
+        let data = serde_json::to_vec_pretty(self)?;
+        std::fs::write(path.as_ref(), data)?;
+        Ok(())
+    }
+
+    /// The annotation of the neuron with the given original layer and neuron indices.
+    pub fn get(&self, layer_index: usize, neuron_index: usize) -> Option<&Annotation> {
+        self.annotations.get(&(layer_index, neuron_index))
+    }
+
+    pub fn set(&mut self, layer_index: usize, neuron_index: usize, annotation: Annotation) {
+        self.annotations
+            .insert((layer_index, neuron_index), annotation);
+    }
+
+    pub fn remove(&mut self, layer_index: usize, neuron_index: usize) -> Option<Annotation> {
+        self.annotations.remove(&(layer_index, neuron_index))
+    }
+
+    /// Iterates over all annotations as `((layer_index, neuron_index), annotation)`.
+    pub fn iter(&self) -> impl Iterator<Item = (&(usize, usize), &Annotation)> {
+        self.annotations.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array2;
+
+    use super::{Annotation, Annotations};
+    use crate::{
+        data::{value::Scope, Value},
+        html::template::NeuronTemplate,
+        PayloadBuilder,
+    };
+
+    fn annotation(label: &str) -> Annotation {
+        Annotation {
+            label: label.to_string(),
+            tags: vec!["vision".to_string(), "early".to_string()],
+            author: "alice".to_string(),
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "transformer-scope-annotations-{}-{name}.json",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn saved_annotations_are_loaded() {
+        let mut annotations = Annotations::new();
+        annotations.set(0, 5, annotation("Edge detector"));
+        annotations.set(2, 1, annotation("Corner detector"));
+        annotations.set(0, 7, annotation("Removed"));
+        assert_eq!(annotations.remove(0, 7), Some(annotation("Removed")));
+
+        let path = temp_path("round-trip");
+        annotations.to_file(&path).unwrap();
+        let loaded = Annotations::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.iter().eq(annotations.iter()));
+        assert_eq!(loaded.get(2, 1), Some(&annotation("Corner detector")));
+        assert_eq!(loaded.get(0, 7), None);
+    }
+
+    #[test]
+    fn a_missing_file_has_no_annotations() {
+        let annotations = Annotations::from_file(temp_path("missing")).unwrap();
+        assert_eq!(annotations.iter().count(), 0);
+    }
+
+    #[test]
+    fn subsets_find_annotations_by_original_indices() {
+        let mut builder = PayloadBuilder::new(3, 4);
+        builder
+            .mlp_neuron_template(NeuronTemplate::parse("$value(scores)").unwrap())
+            .unwrap();
+        builder.add_value(
+            "scores",
+            Value::new(Array2::<f32>::zeros((3, 4)), Scope::Neuron),
+        );
+        let subset = builder
+            .build()
+            .unwrap()
+            .subset(Some(&[2]), Some(&[3, 1]))
+            .unwrap();
+
+        let mut annotations = Annotations::new();
+        annotations.set(2, 1, annotation("Corner detector"));
+        let found: Vec<_> = (0..subset.num_mlp_neurons())
+            .map(|neuron_index| {
+                annotations.get(
+                    subset.original_layer_index(0),
+                    subset.original_neuron_index(neuron_index),
+                )
+            })
+            .collect();
+        assert_eq!(found, [None, Some(&annotation("Corner detector"))]);
+    }
+}
@@ -0,0 +1,236 @@
+use ndarray::{Array2, ArrayView1, ArrayView2, Axis};
+use serde::{Deserialize, Serialize};
+
+use super::similarity::{self, SimilarityMetric};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClusteringMethod {
+    /// Lloyd's algorithm, initialized deterministically with the farthest point heuristic.
+    /// With `max_iterations` 0, every neuron is assigned to the nearest initial centroid.
+    KMeans { max_iterations: usize },
+    /// Average linkage agglomerative clustering.
+    /// Needs memory quadratic in the number of neurons, so it is best suited for smaller models or subsets.
+    Agglomerative,
+}
+
+/// A clustering of neurons stored in the payload, used to generate cluster pages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Clustering {
+    /// Name of the `Neuron` scoped U32 value holding the cluster of every neuron.
+    pub key: String,
+    /// Name of the value the neurons were clustered by.
+    pub source_key: String,
+    pub num_clusters: usize,
+}
+
+fn squared_distance(a: ArrayView1<f32>, b: ArrayView1<f32>) -> f32 {
+    a.iter().zip(b.iter()).map(|(a, b)| (a - b).powi(2)).sum()
+}
+
+fn k_means(vectors: ArrayView2<f32>, num_clusters: usize, max_iterations: usize) -> Vec<usize> {
+    let num_rows = vectors.nrows();
+
+    // Farthest point initialization: start with the first vector and repeatedly add the vector farthest from all centroids.
+    let mut centroid_indices = vec![0];
+    let mut min_distances: Vec<f32> = vectors
+        .rows()
+        .into_iter()
+        .map(|row| squared_distance(row, vectors.row(0)))
+        .collect();
+    while centroid_indices.len() < num_clusters {
+        let (farthest_index, _) = min_distances
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap();
+        centroid_indices.push(farthest_index);
+        for (row, min_distance) in vectors.rows().into_iter().zip(min_distances.iter_mut()) {
+            *min_distance = min_distance.min(squared_distance(row, vectors.row(farthest_index)));
+        }
+    }
+    let mut centroids = vectors.select(Axis(0), &centroid_indices);
+
+    let mut assignments = vec![usize::MAX; num_rows];
+    // Every row is assigned at least once, even without iterations.
+    for iteration in 0..=max_iterations {
+        let mut changed = false;
+        for (row, assignment) in vectors.rows().into_iter().zip(assignments.iter_mut()) {
+            let (nearest, _) = centroids
+                .rows()
+                .into_iter()
+                .map(|centroid| squared_distance(row, centroid))
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .unwrap();
+            if *assignment != nearest {
+                *assignment = nearest;
+                changed = true;
+            }
+        }
+        if !changed || iteration == max_iterations {
+            break;
+        }
+
+        let mut sums = Array2::<f32>::zeros(centroids.dim());
+        let mut counts = vec![0usize; num_clusters];
+        for (row, &assignment) in vectors.rows().into_iter().zip(assignments.iter()) {
+            let mut sum = sums.row_mut(assignment);
+            sum += &row;
+            counts[assignment] += 1;
+        }
+        // Empty clusters keep their previous centroid.
+        for ((mut centroid, sum), &count) in centroids
+            .rows_mut()
+            .into_iter()
+            .zip(sums.rows())
+            .zip(counts.iter())
+        {
+            if count > 0 {
+                centroid.assign(&(&sum / count as f32));
+            }
+        }
+    }
+    assignments
+}
+
+fn agglomerative(vectors: ArrayView2<f32>, num_clusters: usize) -> Vec<usize> {
+    let num_rows = vectors.nrows();
+    let mut distances = Array2::<f32>::zeros((num_rows, num_rows));
+    for i in 0..num_rows {
+        for j in (i + 1)..num_rows {
+            let distance = squared_distance(vectors.row(i), vectors.row(j)).sqrt();
+            distances[(i, j)] = distance;
+            distances[(j, i)] = distance;
+        }
+    }
+
+    let mut active = vec![true; num_rows];
+    let mut sizes = vec![1usize; num_rows];
+    // The cluster each row has been merged into, resolved at the end.
+    let mut merged_into: Vec<usize> = (0..num_rows).collect();
+    let nearest_of = |row: usize, distances: &Array2<f32>, active: &[bool]| {
+        (0..num_rows)
+            .filter(|&other| other != row && active[other])
+            .map(|other| (other, distances[(row, other)]))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap_or((row, f32::INFINITY))
+    };
+    let mut nearest: Vec<(usize, f32)> = (0..num_rows)
+        .map(|row| nearest_of(row, &distances, &active))
+        .collect();
+
+    for _ in num_clusters..num_rows {
+        let (a, (b, _)) = nearest
+            .iter()
+            .enumerate()
+            .filter(|&(row, _)| active[row])
+            .min_by(|(_, (_, x)), (_, (_, y))| x.total_cmp(y))
+            .map(|(row, &nearest)| (row, nearest))
+            .unwrap();
+
+        // Average linkage update of the distances to the merged cluster.
+        for other in 0..num_rows {
+            if active[other] && other != a && other != b {
+                let distance = (sizes[a] as f32 * distances[(a, other)]
+                    + sizes[b] as f32 * distances[(b, other)])
+                    / (sizes[a] + sizes[b]) as f32;
+                distances[(a, other)] = distance;
+                distances[(other, a)] = distance;
+            }
+        }
+        sizes[a] += sizes[b];
+        active[b] = false;
+        merged_into[b] = a;
+
+        for row in 0..num_rows {
+            if active[row] && (row == a || nearest[row].0 == a || nearest[row].0 == b) {
+                nearest[row] = nearest_of(row, &distances, &active);
+            } else if active[row] && distances[(row, a)] < nearest[row].1 {
+                nearest[row] = (a, distances[(row, a)]);
+            }
+        }
+    }
+
+    let roots: Vec<usize> = (0..num_rows).filter(|&row| active[row]).collect();
+    (0..num_rows)
+        .map(|row| {
+            let mut root = row;
+            while merged_into[root] != root {
+                root = merged_into[root];
+            }
+            roots.binary_search(&root).unwrap()
+        })
+        .collect()
+}
+
+/// Clusters the rows of `vectors`, which has one row per neuron, after normalizing them according to `metric`.
+/// Returns the cluster index of every row.
+pub fn cluster(
+    vectors: ArrayView2<f32>,
+    num_clusters: usize,
+    method: ClusteringMethod,
+    metric: SimilarityMetric,
+) -> Vec<usize> {
+    assert!(
+        num_clusters > 0 && num_clusters <= vectors.nrows(),
+        "Cannot split {} neurons into {num_clusters} clusters.",
+        vectors.nrows()
+    );
+    let vectors = similarity::normalize_rows(vectors, metric);
+    match method {
+        ClusteringMethod::KMeans { max_iterations } => {
+            k_means(vectors.view(), num_clusters, max_iterations)
+        }
+        ClusteringMethod::Agglomerative => agglomerative(vectors.view(), num_clusters),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{arr2, Array2};
+
+    use super::{agglomerative, cluster, k_means, ClusteringMethod};
+    use crate::data::SimilarityMetric;
+
+    fn points() -> Array2<f32> {
+        arr2(&[[0.0], [1.0], [5.0], [6.0], [20.0]])
+    }
+
+    #[test]
+    fn k_means_assigns_rows_to_the_nearest_centroid() {
+        // The initial centroids are 0, 20 and 6, the farthest point from both.
+        assert_eq!(k_means(points().view(), 3, 100), [0, 0, 2, 2, 1]);
+        assert_eq!(k_means(points().view(), 2, 100), [0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn k_means_without_iterations_assigns_every_row() {
+        let vectors = arr2(&[[0.0, 0.0], [0.0, 1.0], [10.0, 0.0], [10.0, 1.0]]);
+        assert_eq!(k_means(vectors.view(), 2, 0), [0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn agglomerative_clustering_merges_the_closest_clusters() {
+        assert_eq!(agglomerative(points().view(), 5), [0, 1, 2, 3, 4]);
+        assert_eq!(agglomerative(points().view(), 3), [0, 0, 1, 1, 2]);
+        // The average distance between {0, 1} and {5, 6} is 5, and between {5, 6} and {20} 14.5.
+        assert_eq!(agglomerative(points().view(), 2), [0, 0, 0, 0, 1]);
+        assert_eq!(agglomerative(points().view(), 1), [0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn vectors_are_clustered_by_direction() {
+        let vectors = arr2(&[[1.0, 0.0], [20.0, 1.0], [0.0, 1.0], [0.1, 3.0]]);
+        for method in [
+            ClusteringMethod::KMeans {
+                max_iterations: 100,
+            },
+            ClusteringMethod::Agglomerative,
+        ] {
+            assert_eq!(
+                cluster(vectors.view(), 2, method, SimilarityMetric::Cosine),
+                [0, 0, 1, 1]
+            );
+        }
+    }
+}
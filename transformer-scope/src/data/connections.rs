@@ -0,0 +1,196 @@
+use ndarray::{s, Array3, Array4, ArrayView3, Axis};
+
+/// Marks unused connection slots, e.g. the upstream connections of neurons in the first layer.
+pub const NO_CONNECTION: u32 = u32::MAX;
+
+/// Names of the values holding the connections stored under the given key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionKeys {
+    pub upstream: String,
+    pub upstream_weights: String,
+    pub downstream: String,
+    pub downstream_weights: String,
+}
+
+impl ConnectionKeys {
+    pub fn new(key: &str) -> Self {
+        Self {
+            upstream: format!("{key}_upstream"),
+            upstream_weights: format!("{key}_upstream_weights"),
+            downstream: format!("{key}_downstream"),
+            downstream_weights: format!("{key}_downstream_weights"),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        [
+            &self.upstream,
+            &self.upstream_weights,
+            &self.downstream,
+            &self.downstream_weights,
+        ]
+        .into_iter()
+    }
+}
+
+/// The strongest connections of every neuron.
+///
+/// Connected neurons have shape `[num_layers, num_mlp_neurons, k, 2]` and hold the layer and neuron index of every
+/// connected neuron, or [`NO_CONNECTION`] if the neuron has fewer than `k` connections. Weights have shape
+/// `[num_layers, num_mlp_neurons, k]` and are sorted by decreasing absolute value.
+pub struct TopConnections {
+    pub upstream: Array4<u32>,
+    pub upstream_weights: Array3<f32>,
+    pub downstream: Array4<u32>,
+    pub downstream_weights: Array3<f32>,
+}
+
+/// A connection weight together with the layer and neuron index of the connected neuron.
+type Connection = (f32, u32, u32);
+
+fn compare_strength(a: &Connection, b: &Connection) -> std::cmp::Ordering {
+    b.0.abs().total_cmp(&a.0.abs())
+}
+
+/// Keeps only the `k` strongest connections.
+fn keep_strongest(connections: &mut Vec<Connection>, k: usize) {
+    if connections.len() > k {
+        connections.select_nth_unstable_by(k, compare_strength);
+        connections.truncate(k);
+    }
+}
+
+fn into_arrays(
+    mut connections: Vec<Vec<Connection>>,
+    num_layers: usize,
+    num_neurons: usize,
+    k: usize,
+) -> (Array4<u32>, Array3<f32>) {
+    let mut neurons = Array4::from_elem((num_layers, num_neurons, k, 2), NO_CONNECTION);
+    let mut weights = Array3::zeros((num_layers, num_neurons, k));
+    for (index, connections) in connections.iter_mut().enumerate() {
+        let (layer_index, neuron_index) = (index / num_neurons, index % num_neurons);
+        connections.sort_unstable_by(compare_strength);
+        for (rank, &(weight, connected_layer, connected_neuron)) in connections.iter().enumerate() {
+            neurons[(layer_index, neuron_index, rank, 0)] = connected_layer;
+            neurons[(layer_index, neuron_index, rank, 1)] = connected_neuron;
+            weights[(layer_index, neuron_index, rank)] = weight;
+        }
+    }
+    (neurons, weights)
+}
+
+/// Computes the virtual weights between the MLP neurons of all pairs of layers through the residual stream,
+/// i.e. `W_out[i] · W_in[j]` for layers `i < j`, and keeps the `k` strongest upstream and downstream connections
+/// of every neuron.
+///
+/// `w_in` has shape `[num_layers, d_model, num_mlp_neurons]` and `w_out` has shape `[num_layers, num_mlp_neurons, d_model]`.
+pub fn top_connections(w_in: ArrayView3<f32>, w_out: ArrayView3<f32>, k: usize) -> TopConnections {
+    let (num_layers, d_model, num_neurons) = w_in.dim();
+    assert_eq!(
+        w_out.dim(),
+        (num_layers, num_neurons, d_model),
+        "W_out must have shape [num_layers, num_mlp_neurons, d_model] matching W_in."
+    );
+
+    let mut upstream: Vec<Vec<Connection>> = vec![Vec::new(); num_layers * num_neurons];
+    let mut downstream: Vec<Vec<Connection>> = vec![Vec::new(); num_layers * num_neurons];
+    for source_layer in 0..num_layers {
+        for target_layer in (source_layer + 1)..num_layers {
+            // Entry (a, b) is the weight from neuron a of the source layer to neuron b of the target layer.
+            let virtual_weights =
+                w_out
+                    .slice(s![source_layer, .., ..])
+                    .dot(&w_in.slice(s![target_layer, .., ..]));
+
+            for (target_neuron, weights) in virtual_weights.axis_iter(Axis(1)).enumerate() {
+                let connections = &mut upstream[target_layer * num_neurons + target_neuron];
+                connections.extend(weights.iter().enumerate().map(|(source_neuron, &weight)| {
+                    (weight, source_layer as u32, source_neuron as u32)
+                }));
+                keep_strongest(connections, k);
+            }
+            for (source_neuron, weights) in virtual_weights.axis_iter(Axis(0)).enumerate() {
+                let connections = &mut downstream[source_layer * num_neurons + source_neuron];
+                connections.extend(weights.iter().enumerate().map(|(target_neuron, &weight)| {
+                    (weight, target_layer as u32, target_neuron as u32)
+                }));
+                keep_strongest(connections, k);
+            }
+        }
+    }
+
+    let (upstream, upstream_weights) = into_arrays(upstream, num_layers, num_neurons, k);
+    let (downstream, downstream_weights) = into_arrays(downstream, num_layers, num_neurons, k);
+    TopConnections {
+        upstream,
+        upstream_weights,
+        downstream,
+        downstream_weights,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{arr2, arr3, s, Array3};
+
+    use super::{top_connections, NO_CONNECTION};
+
+    /// Weights of 2 layers with 2 neurons and `d_model` 2. `W_out[0] · W_in[1]` is `[[3, 4], [-1, -8]]`,
+    /// while `W_in[0]` and `W_out[1]` connect to no other layer.
+    fn weights() -> (Array3<f32>, Array3<f32>) {
+        let w_in = arr3(&[[[100.0, 100.0], [100.0, 100.0]], [[1.0, 0.0], [1.0, 2.0]]]);
+        let w_out = arr3(&[[[1.0, 2.0], [3.0, -4.0]], [[100.0, 100.0], [100.0, 100.0]]]);
+        (w_in, w_out)
+    }
+
+    #[test]
+    fn connections_are_the_virtual_weights_between_layers() {
+        let (w_in, w_out) = weights();
+        let connections = top_connections(w_in.view(), w_out.view(), 2);
+
+        let none = [NO_CONNECTION; 2];
+        assert_eq!(
+            connections.upstream,
+            arr3(&[[none; 2], [none; 2], [[0, 0], [0, 1]], [[0, 1], [0, 0]]])
+                .into_shape((2, 2, 2, 2))
+                .unwrap()
+        );
+        assert_eq!(
+            connections.upstream_weights,
+            arr3(&[[[0.0, 0.0], [0.0, 0.0]], [[3.0, -1.0], [-8.0, 4.0]]])
+        );
+        assert_eq!(
+            connections.downstream,
+            arr3(&[[[1, 1], [1, 0]], [[1, 1], [1, 0]], [none; 2], [none; 2]])
+                .into_shape((2, 2, 2, 2))
+                .unwrap()
+        );
+        assert_eq!(
+            connections.downstream_weights,
+            arr3(&[[[4.0, 3.0], [-8.0, -1.0]], [[0.0, 0.0], [0.0, 0.0]]])
+        );
+    }
+
+    #[test]
+    fn only_the_strongest_connections_are_kept() {
+        let (w_in, w_out) = weights();
+        let connections = top_connections(w_in.view(), w_out.view(), 1);
+        assert_eq!(
+            connections.upstream_weights,
+            arr3(&[[[0.0], [0.0]], [[3.0], [-8.0]]])
+        );
+        assert_eq!(
+            connections.downstream_weights,
+            arr3(&[[[4.0], [-8.0]], [[0.0], [0.0]]])
+        );
+        assert_eq!(
+            connections.downstream.into_shape((2, 2, 2)).unwrap(),
+            arr3(&[[[1, 1], [1, 1]], [[NO_CONNECTION; 2], [NO_CONNECTION; 2]]])
+        );
+        assert_eq!(
+            connections.upstream.slice(s![1, .., 0, ..]),
+            arr2(&[[0, 0], [0, 1]])
+        );
+    }
+}
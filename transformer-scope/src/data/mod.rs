@@ -1,8 +1,28 @@
+mod annotations;
+pub use annotations::{Annotation, Annotations, AnnotationsError};
 mod neuron_rankings;
 mod payload;
-pub use payload::Payload;
+pub use payload::{Payload, PayloadFileError, SubsetError, PAYLOAD_FORMAT_VERSION};
 mod payload_builder;
-pub use payload_builder::PayloadBuilder;
+pub use payload_builder::{PayloadBuilder, RequestError};
 pub mod value;
 pub use value::Value;
+mod token_snippet;
 mod values;
+pub use token_snippet::TokenSnippet;
+mod top_k_accumulator;
+pub use top_k_accumulator::{TopKAccumulator, TopKAccumulatorError};
+mod vocabulary;
+pub use vocabulary::Vocabulary;
+mod quantization;
+pub use quantization::{Quantization, QuantizationError, QuantizedArray};
+mod similarity;
+pub use similarity::{SimilarityMetric, SimilarityMode};
+mod clustering;
+pub use clustering::{Clustering, ClusteringMethod};
+mod search_query;
+pub use search_query::{Comparison, SearchQuery, SearchQueryError};
+mod search_index;
+pub use search_index::SearchIndex;
+mod connections;
+pub use connections::{ConnectionKeys, NO_CONNECTION};
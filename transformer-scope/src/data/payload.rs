@@ -1,50 +1,119 @@
-use std::path::Path;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::Path,
+};
 
 use ndarray::{ArrayView2, Ix2};
 
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-use crate::html::template::{ArgumentError, NeuronTemplate};
+use crate::html::template::{
+    ArgumentError, IndexTemplate, LayerTemplate, NeuronTemplate, Partial, Templates,
+};
 
-use super::{values::Values, Value};
+use super::{
+    neuron_rankings, value::Scope, values::Values, Clustering, QuantizationError, Value, Vocabulary,
+};
+
+/// Starts every payload file, followed by [`PAYLOAD_FORMAT_VERSION`] as a little endian `u32`.
+const PAYLOAD_MAGIC: &[u8; 8] = b"TSCOPE\x00\x00";
+
+/// The version of the payload file format, increased whenever payloads of older versions can no longer be read.
+pub const PAYLOAD_FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Error)]
+pub enum PayloadFileError {
+    #[error("Failed to access payload file: {0}")]
+    Io(#[from] io::Error),
+    #[error("The file is not a versioned payload. Payloads written before format versions were introduced cannot be read, please regenerate the payload.")]
+    Unversioned,
+    #[error("The payload has format version {found}, but this version of transformer-scope reads version {PAYLOAD_FORMAT_VERSION}. Please regenerate the payload.")]
+    Version { found: u32 },
+    #[error("Failed to encode or parse payload file: {0}")]
+    Postcard(#[from] postcard::Error),
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum SubsetError {
+    #[error("Layer {0} is not in the payload.")]
+    MissingLayer(usize),
+    #[error("Neuron {0} is not in the payload.")]
+    MissingNeuron(usize),
+    #[error("Layer {0} is selected more than once.")]
+    DuplicateLayer(usize),
+    #[error("Neuron {0} is selected more than once.")]
+    DuplicateNeuron(usize),
+    #[error("A subset must contain at least one layer and one neuron.")]
+    Empty,
+}
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Payload {
     num_layers: usize,
     num_mlp_neurons: usize,
 
-    mlp_neuron_template: NeuronTemplate,
+    /// For each layer in the payload, the index of the layer in the original model.
+    original_layer_indices: Vec<usize>,
+    /// For each neuron in a layer of the payload, the index of the neuron in the original model.
+    original_neuron_indices: Vec<usize>,
+
+    /// The page templates with their partials included.
+    templates: Templates,
 
+    vocabulary: Option<Vocabulary>,
     values: Values,
+
+    clusterings: Vec<Clustering>,
 }
 
 impl Payload {
     pub fn new(
         num_layers: usize,
         num_mlp_neurons: usize,
-        mlp_neuron_template: NeuronTemplate,
+        templates: Templates,
+        vocabulary: Option<Vocabulary>,
         values: Values,
+        clusterings: Vec<Clustering>,
     ) -> Result<Self, ArgumentError> {
+        let templates = templates.resolve_includes()?;
         let result = Self {
             num_layers,
             num_mlp_neurons,
-            mlp_neuron_template,
+            original_layer_indices: (0..num_layers).collect(),
+            original_neuron_indices: (0..num_mlp_neurons).collect(),
+            templates,
+            vocabulary,
             values,
+            clusterings,
         };
-        result
-            .mlp_neuron_template
-            .validate_arguments(&result)
-            .map(|_| result)
+        result.templates.validate_arguments(&result).map(|_| result)
     }
 
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Self {
-        let data = std::fs::read(path.as_ref()).unwrap();
-        postcard::from_bytes(data.as_slice()).unwrap()
+    /// Loads a payload written by [`Payload::to_file`] with the current [`PAYLOAD_FORMAT_VERSION`].
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PayloadFileError> {
+        let data = std::fs::read(path.as_ref())?;
+        let data = data
+            .strip_prefix(PAYLOAD_MAGIC)
+            .ok_or(PayloadFileError::Unversioned)?;
+        let (version, data) = data
+            .split_first_chunk()
+            .ok_or(PayloadFileError::Unversioned)?;
+        let found = u32::from_le_bytes(*version);
+        if found != PAYLOAD_FORMAT_VERSION {
+            return Err(PayloadFileError::Version { found });
+        }
+        Ok(postcard::from_bytes(data)?)
     }
 
-    pub fn to_file<P: AsRef<Path>>(&self, path: P) {
-        let data = postcard::to_allocvec(&self).unwrap();
-        std::fs::write(path.as_ref(), data).unwrap();
+    /// Writes the payload, preceded by the current [`PAYLOAD_FORMAT_VERSION`].
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), PayloadFileError> {
+        let mut data = PAYLOAD_MAGIC.to_vec();
+        data.extend(PAYLOAD_FORMAT_VERSION.to_le_bytes());
+        let data = postcard::to_extend(&self, data)?;
+        std::fs::write(path.as_ref(), data)?;
+        Ok(())
     }
 
     pub fn num_layers(&self) -> usize {
@@ -55,22 +124,421 @@ impl Payload {
         self.num_mlp_neurons
     }
 
-    pub fn ranked_neurons(&self) -> ArrayView2<u32> {
-        self.values
-            .get("ranked_neurons")
-            .expect("Ranked neurons not set.")
-            .as_u32()
-            .unwrap()
+    /// The index in the original model of the layer with the given index in this payload.
+    pub fn original_layer_index(&self, layer_index: usize) -> usize {
+        self.original_layer_indices[layer_index]
+    }
+
+    /// The index in the original model of the neuron with the given index in this payload.
+    pub fn original_neuron_index(&self, neuron_index: usize) -> usize {
+        self.original_neuron_indices[neuron_index]
+    }
+
+    /// The index in this payload of the layer with the given index in the original model, if it is present.
+    pub fn layer_index_from_original(&self, original_layer_index: usize) -> Option<usize> {
+        self.original_layer_indices
+            .iter()
+            .position(|&index| index == original_layer_index)
+    }
+
+    /// The index in this payload of the neuron with the given index in the original model, if it is present.
+    pub fn neuron_index_from_original(&self, original_neuron_index: usize) -> Option<usize> {
+        self.original_neuron_indices
+            .iter()
+            .position(|&index| index == original_neuron_index)
+    }
+
+    /// The neurons of every layer ordered by rank, or `None` if no rank values were set when building the payload.
+    pub fn ranked_neurons(&self) -> Option<ArrayView2<'_, u32>> {
+        self.values.get("ranked_neurons").map(|ranked_neurons| {
+            ranked_neurons
+                .as_u32()
+                .unwrap()
+                .view()
+                .into_dimensionality::<Ix2>()
+                .unwrap()
+        })
+    }
+
+    pub fn neuron_template(&self) -> &NeuronTemplate {
+        &self.templates.mlp_neuron
+    }
+
+    pub fn index_template(&self) -> &IndexTemplate {
+        &self.templates.index
+    }
+
+    pub fn layer_template(&self) -> &LayerTemplate {
+        &self.templates.layer
+    }
+
+    pub fn partials(&self) -> &BTreeMap<String, Partial> {
+        &self.templates.partials
+    }
+
+    pub fn clusterings(&self) -> &[Clustering] {
+        &self.clusterings
+    }
+
+    pub fn clustering(&self, key: &str) -> Option<&Clustering> {
+        self.clusterings
+            .iter()
+            .find(|clustering| clustering.key == key)
+    }
+
+    /// The neurons in the given cluster as `(layer_index, neuron_index)` pairs of payload indices.
+    pub fn cluster_members(&self, clustering: &Clustering, cluster: usize) -> Vec<(usize, usize)> {
+        let assignments = self
+            .value(&clustering.key)
+            .and_then(|value| value.as_u32())
+            .expect("Cluster assignments must be a U32 value.")
             .view()
             .into_dimensionality::<Ix2>()
-            .unwrap()
+            .unwrap();
+        assignments
+            .indexed_iter()
+            .filter(|&(_, &assignment)| assignment as usize == cluster)
+            .map(|(index, _)| index)
+            .collect()
     }
 
-    pub fn neuron_template(&self) -> &NeuronTemplate {
-        &self.mlp_neuron_template
+    pub fn vocabulary(&self) -> Option<&Vocabulary> {
+        self.vocabulary.as_ref()
     }
 
     pub fn value(&self, key: impl AsRef<str>) -> Option<&Value> {
         self.values.get(key.as_ref())
     }
+
+    /// All values, sorted by name.
+    pub fn values(&self) -> Vec<(&str, &Value)> {
+        let mut values: Vec<_> = self
+            .values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value))
+            .collect();
+        values.sort_by_key(|&(key, _)| key);
+        values
+    }
+
+    /// The quantization error of every quantized value, sorted by value name.
+    pub fn quantization_errors(&self) -> Vec<(&str, QuantizationError)> {
+        let mut errors: Vec<_> = self
+            .values
+            .iter()
+            .filter_map(|(key, value)| {
+                value
+                    .quantization_error()
+                    .map(|error| (key.as_str(), error))
+            })
+            .collect();
+        errors.sort_by_key(|&(key, _)| key);
+        errors
+    }
+
+    fn subset_indices(
+        selection: Option<&[usize]>,
+        original_indices: &[usize],
+        missing_error: fn(usize) -> SubsetError,
+        duplicate_error: fn(usize) -> SubsetError,
+    ) -> Result<Vec<usize>, SubsetError> {
+        let Some(selection) = selection else {
+            return Ok((0..original_indices.len()).collect());
+        };
+        let mut indices = Vec::with_capacity(selection.len());
+        for &original_index in selection {
+            let index = original_indices
+                .iter()
+                .position(|&index| index == original_index)
+                .ok_or(missing_error(original_index))?;
+            if indices.contains(&index) {
+                return Err(duplicate_error(original_index));
+            }
+            indices.push(index);
+        }
+        if indices.is_empty() {
+            Err(SubsetError::Empty)
+        } else {
+            Ok(indices)
+        }
+    }
+
+    /// Creates a payload containing only the given layers and neurons.
+    /// Indices are given as indices in the original model, and the same neurons are kept in every selected layer.
+    /// `None` keeps all layers or neurons respectively.
+    ///
+    /// Every `Layer` and `Neuron` scoped value is sliced accordingly, while global values are kept as is.
+    /// Neuron rankings are recalculated so they only contain the selected neurons,
+    /// and clusters are renumbered so they only contain clusters with selected neurons.
+    pub fn subset(
+        &self,
+        layers: Option<&[usize]>,
+        neurons: Option<&[usize]>,
+    ) -> Result<Self, SubsetError> {
+        let layer_indices = Self::subset_indices(
+            layers,
+            &self.original_layer_indices,
+            SubsetError::MissingLayer,
+            SubsetError::DuplicateLayer,
+        )?;
+        let neuron_indices = Self::subset_indices(
+            neurons,
+            &self.original_neuron_indices,
+            SubsetError::MissingNeuron,
+            SubsetError::DuplicateNeuron,
+        )?;
+
+        let mut values: HashMap<String, Value> = self
+            .values
+            .iter()
+            .filter(|&(key, _)| key != "ranked_neurons")
+            .map(|(key, value)| (key.clone(), value.select(&layer_indices, &neuron_indices)))
+            .collect();
+
+        // Clusters without any of the selected neurons are dropped and the remaining ones are numbered consecutively.
+        let clusterings = self
+            .clusterings
+            .iter()
+            .map(|clustering| {
+                let assignments = values
+                    .get(&clustering.key)
+                    .and_then(|value| value.as_u32())
+                    .expect("Cluster assignments must be a U32 value.");
+                let mut clusters: Vec<u32> = assignments.iter().copied().collect();
+                clusters.sort_unstable();
+                clusters.dedup();
+                let assignments = assignments.map(|assignment| {
+                    u32::try_from(clusters.binary_search(assignment).unwrap()).unwrap()
+                });
+                values.insert(
+                    clustering.key.clone(),
+                    Value::new(assignments, Scope::Neuron),
+                );
+                Clustering {
+                    num_clusters: clusters.len(),
+                    ..clustering.clone()
+                }
+            })
+            .collect();
+
+        if let Some(ranks) = values.get("rank") {
+            let ranks = ranks
+                .as_u32()
+                .expect("Value 'rank' is reserved for neuron ranks.")
+                .view()
+                .into_dimensionality::<Ix2>()
+                .unwrap()
+                .map(|&rank| rank as f32);
+            let (neuron_ranks, ranked_neurons) =
+                neuron_rankings::calculate_neuron_rankings(ranks.view());
+            values.insert(
+                "rank".to_string(),
+                Value::new(
+                    neuron_ranks.map(|&x| u32::try_from(x).unwrap()),
+                    Scope::Neuron,
+                ),
+            );
+            values.insert(
+                "ranked_neurons".to_string(),
+                Value::new(
+                    ranked_neurons.map(|&x| u32::try_from(x).unwrap()),
+                    Scope::Global,
+                ),
+            );
+        }
+
+        Ok(Self {
+            num_layers: layer_indices.len(),
+            num_mlp_neurons: neuron_indices.len(),
+            original_layer_indices: layer_indices
+                .iter()
+                .map(|&index| self.original_layer_indices[index])
+                .collect(),
+            original_neuron_indices: neuron_indices
+                .iter()
+                .map(|&index| self.original_neuron_indices[index])
+                .collect(),
+            templates: self.templates.clone(),
+            vocabulary: self.vocabulary.clone(),
+            values: Values::new(values),
+            clusterings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{arr0, arr2, Array1, Array2, Array3};
+
+    use super::{Payload, PayloadFileError, SubsetError, PAYLOAD_FORMAT_VERSION, PAYLOAD_MAGIC};
+    use crate::{
+        data::{value::Scope, ClusteringMethod, SimilarityMetric, Value},
+        html::template::NeuronTemplate,
+        PayloadBuilder,
+    };
+
+    fn load(name: &str, data: &[u8]) -> Result<Payload, PayloadFileError> {
+        let path = std::env::temp_dir().join(format!(
+            "transformer_scope_{name}_{}.payload",
+            std::process::id()
+        ));
+        std::fs::write(&path, data).unwrap();
+        let result = Payload::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn files_without_a_version_are_rejected() {
+        assert!(matches!(
+            load("unversioned", &[2, 3, 0, 1]),
+            Err(PayloadFileError::Unversioned)
+        ));
+        assert!(matches!(
+            load("truncated", PAYLOAD_MAGIC),
+            Err(PayloadFileError::Unversioned)
+        ));
+    }
+
+    #[test]
+    fn files_of_other_versions_are_rejected() {
+        let mut data = PAYLOAD_MAGIC.to_vec();
+        data.extend((PAYLOAD_FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            load("newer", &data),
+            Err(PayloadFileError::Version { found }) if found == PAYLOAD_FORMAT_VERSION + 1
+        ));
+    }
+
+    /// A payload of 3 layers with 4 neurons each, ranked by `scores` and clustered into even and odd neurons.
+    fn payload() -> Payload {
+        let mut builder = PayloadBuilder::new(3, 4);
+        builder
+            .mlp_neuron_template(
+                NeuronTemplate::parse("$value(bias) $value(scores) $value(title)").unwrap(),
+            )
+            .unwrap();
+        builder.add_value(
+            "bias",
+            Value::new(Array1::from_vec(vec![0.0f32, 1.0, 2.0]), Scope::Layer),
+        );
+        builder.add_value(
+            "scores",
+            Value::new(
+                Array2::from_shape_fn((3, 4), |(layer, neuron)| {
+                    (layer * 10 + neuron * 3 % 4) as f32
+                }),
+                Scope::Neuron,
+            ),
+        );
+        builder.add_value(
+            "title",
+            Value::new(arr0("model".to_string()), Scope::Global),
+        );
+        builder.add_value(
+            "directions",
+            Value::new(
+                Array3::from_shape_fn((3, 4, 2), |(_, neuron, axis)| {
+                    (neuron % 2 == axis) as u32 as f32
+                }),
+                Scope::Neuron,
+            ),
+        );
+        builder.set_rank_values("scores");
+        builder
+            .cluster_neurons(
+                "cluster",
+                "directions",
+                2,
+                ClusteringMethod::Agglomerative,
+                SimilarityMetric::Cosine,
+            )
+            .unwrap();
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn subsets_keep_the_selected_layers_and_neurons() {
+        let subset = payload().subset(Some(&[2, 0]), Some(&[1, 3])).unwrap();
+        assert_eq!((subset.num_layers(), subset.num_mlp_neurons()), (2, 2));
+        assert_eq!(
+            subset.value("bias").unwrap().as_f32().unwrap(),
+            Array1::from_vec(vec![2.0f32, 0.0]).into_dyn()
+        );
+        assert_eq!(
+            subset.value("scores").unwrap().as_f32().unwrap(),
+            arr2(&[[23.0f32, 21.0], [3.0, 1.0]]).into_dyn()
+        );
+        assert_eq!(
+            subset.value("title").unwrap().as_string().unwrap(),
+            &arr0("model".to_string()).into_dyn()
+        );
+    }
+
+    #[test]
+    fn subsets_map_between_their_indices_and_the_original_ones() {
+        let subset = payload().subset(Some(&[2, 0]), Some(&[1, 3])).unwrap();
+        assert_eq!(subset.original_layer_index(0), 2);
+        assert_eq!(subset.original_layer_index(1), 0);
+        assert_eq!(subset.original_neuron_index(1), 3);
+        assert_eq!(subset.layer_index_from_original(0), Some(1));
+        assert_eq!(subset.layer_index_from_original(1), None);
+        assert_eq!(subset.neuron_index_from_original(1), Some(0));
+        assert_eq!(subset.neuron_index_from_original(2), None);
+
+        // Subsets of subsets are selected by original indices as well.
+        let subset = subset.subset(Some(&[0]), Some(&[3])).unwrap();
+        assert_eq!(subset.original_layer_index(0), 0);
+        assert_eq!(subset.original_neuron_index(0), 3);
+    }
+
+    #[test]
+    fn subsets_rank_only_their_neurons() {
+        let payload = payload();
+        assert_eq!(
+            payload.ranked_neurons().unwrap(),
+            arr2(&[[0u32, 3, 2, 1], [0, 3, 2, 1], [0, 3, 2, 1]])
+        );
+        let subset = payload.subset(Some(&[2, 0]), Some(&[1, 3])).unwrap();
+        assert_eq!(
+            subset.value("rank").unwrap().as_u32().unwrap(),
+            &arr2(&[[1u32, 0], [1, 0]]).into_dyn()
+        );
+        assert_eq!(subset.ranked_neurons().unwrap(), arr2(&[[1u32, 0], [1, 0]]));
+    }
+
+    #[test]
+    fn subsets_only_keep_clusters_with_selected_neurons() {
+        let payload = payload();
+        assert_eq!(payload.clustering("cluster").unwrap().num_clusters, 2);
+        let subset = payload.subset(Some(&[2, 0]), Some(&[1, 3])).unwrap();
+        let clustering = subset.clustering("cluster").unwrap();
+        assert_eq!(clustering.num_clusters, 1);
+        assert_eq!(
+            subset.cluster_members(clustering, 0),
+            [(0, 0), (0, 1), (1, 0), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn invalid_subsets_are_rejected() {
+        let payload = payload();
+        assert!(matches!(
+            payload.subset(Some(&[1, 3]), None),
+            Err(SubsetError::MissingLayer(3))
+        ));
+        assert!(matches!(
+            payload.subset(None, Some(&[1, 2, 1])),
+            Err(SubsetError::DuplicateNeuron(1))
+        ));
+        assert!(matches!(
+            payload.subset(Some(&[]), None),
+            Err(SubsetError::Empty)
+        ));
+        let subset = payload.subset(None, Some(&[1, 3])).unwrap();
+        assert!(matches!(
+            subset.subset(None, Some(&[0])),
+            Err(SubsetError::MissingNeuron(0))
+        ));
+    }
 }
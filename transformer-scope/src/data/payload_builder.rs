@@ -1,27 +1,113 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use ndarray::Ix2;
+use ndarray::{Array2, Array4, CowArray, Ix2, Ix3, IxDyn};
+use thiserror::Error;
 
 use crate::{
-    html::template::{ArgumentError, NeuronTemplate},
+    html::template::{
+        ArgumentError, IndexTemplate, LayerTemplate, NeuronTemplate, Partial, PayloadSchema,
+        SchemaError, Templates,
+    },
     Payload,
 };
 
 use super::{
+    clustering::{self, Clustering, ClusteringMethod},
+    connections::{self, ConnectionKeys, TopConnections},
     neuron_rankings,
+    similarity::{self, SimilarityMetric, SimilarityMode},
     value::{self, Scope},
     values::Values,
-    Value,
+    TokenSnippet, Value, Vocabulary,
 };
 
+/// Errors of requests for values computed when the payload is built, e.g. nearest neighbours.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum RequestError {
+    #[error(
+        "No value named {0} found. Please add the value before computing further values from it."
+    )]
+    MissingValue(String),
+    #[error("Value {key} has scope `{found_scope}`, but values are only computed from values with scope `{required_scope}`.")]
+    Scope {
+        key: String,
+        required_scope: Scope,
+        found_scope: Scope,
+    },
+    #[error("Value {key} has data type {found_data_type}, but values are only computed from values with data type F32.")]
+    DataType {
+        key: String,
+        found_data_type: value::DataType,
+    },
+    #[error("Value {0} already set.")]
+    ValueSet(String),
+    #[error("Cannot find {k} neighbours among {num_neurons} neurons.")]
+    TooManyNeighbours { k: usize, num_neurons: usize },
+    #[error("Chunk size must be positive.")]
+    EmptyChunks,
+    #[error("Value {key} must have shape {required_shape}, but has shape {found_shape:?}.")]
+    Shape {
+        key: String,
+        required_shape: &'static str,
+        found_shape: Vec<usize>,
+    },
+    #[error("Cannot split {num_neurons} neurons into {num_clusters} clusters.")]
+    NumClusters {
+        num_clusters: usize,
+        num_neurons: usize,
+    },
+}
+
+/// The numbers of an `F32` value used to compute further values. Quantized values are dequantized whole.
+fn f32_numbers(value: &Value) -> Option<CowArray<'_, f32, IxDyn>> {
+    value
+        .as_f32()
+        .map(CowArray::from)
+        .or_else(|| value.dequantize().map(CowArray::from))
+}
+
+/// Nearest neighbours to compute when the payload is built.
+struct NeighboursRequest {
+    neighbours_key: String,
+    scores_key: String,
+    source_key: String,
+    k: usize,
+    metric: SimilarityMetric,
+    mode: SimilarityMode,
+}
+
+/// Clustering to compute when the payload is built.
+struct ClusteringRequest {
+    key: String,
+    source_key: String,
+    num_clusters: usize,
+    method: ClusteringMethod,
+    metric: SimilarityMetric,
+}
+
+/// Connections between neurons to compute when the payload is built.
+struct ConnectionsRequest {
+    keys: ConnectionKeys,
+    w_in_key: String,
+    w_out_key: String,
+    k: usize,
+}
+
 pub struct PayloadBuilder {
     num_layers: usize,
     num_mlp_neurons: usize,
 
     mlp_neuron_template: Option<NeuronTemplate>,
+    index_template: Option<IndexTemplate>,
+    layer_template: Option<LayerTemplate>,
+    partials: BTreeMap<String, Partial>,
+    vocabulary: Option<Vocabulary>,
     values: HashMap<String, Value>,
 
     rank_values_key: Option<String>,
+    neighbours_requests: Vec<NeighboursRequest>,
+    clustering_requests: Vec<ClusteringRequest>,
+    connections_requests: Vec<ConnectionsRequest>,
 }
 
 impl PayloadBuilder {
@@ -30,17 +116,130 @@ impl PayloadBuilder {
             num_layers,
             num_mlp_neurons,
             mlp_neuron_template: None,
+            index_template: None,
+            layer_template: None,
+            partials: BTreeMap::new(),
+            vocabulary: None,
             values: HashMap::new(),
             rank_values_key: None,
+            neighbours_requests: Vec::new(),
+            clustering_requests: Vec::new(),
+            connections_requests: Vec::new(),
         }
     }
 
-    pub fn mlp_neuron_template(&mut self, neuron_template: NeuronTemplate) {
+    pub fn num_layers(&self) -> usize {
+        self.num_layers
+    }
+
+    pub fn num_mlp_neurons(&self) -> usize {
+        self.num_mlp_neurons
+    }
+
+    /// Sets the template of the neuron pages.
+    /// Fails if a value added before does not match the template, in which case the template is not set.
+    pub fn mlp_neuron_template(
+        &mut self,
+        neuron_template: NeuronTemplate,
+    ) -> Result<(), SchemaError> {
         assert!(
             self.mlp_neuron_template.is_none(),
             "MLP neuron template already set."
         );
         self.mlp_neuron_template = Some(neuron_template);
+        let result = self.check_values();
+        if result.is_err() {
+            self.mlp_neuron_template = None;
+        }
+        result
+    }
+
+    /// Sets the template of the index page, which otherwise shows the search form, the clusters and the table of
+    /// ranked neurons. Fails like [`PayloadBuilder::mlp_neuron_template`].
+    pub fn index_template(&mut self, index_template: IndexTemplate) -> Result<(), SchemaError> {
+        assert!(self.index_template.is_none(), "Index template already set.");
+        self.index_template = Some(index_template);
+        let result = self.check_values();
+        if result.is_err() {
+            self.index_template = None;
+        }
+        result
+    }
+
+    /// Sets the template of the layer pages, which otherwise list the top neurons of the layer by rank.
+    /// Fails like [`PayloadBuilder::mlp_neuron_template`].
+    pub fn layer_template(&mut self, layer_template: LayerTemplate) -> Result<(), SchemaError> {
+        assert!(self.layer_template.is_none(), "Layer template already set.");
+        self.layer_template = Some(layer_template);
+        let result = self.check_values();
+        if result.is_err() {
+            self.layer_template = None;
+        }
+        result
+    }
+
+    /// Adds a partial, which templates include with `$include(name, parameter=argument, ...)`.
+    /// Fails if a value added before does not match a template including it, in which case the partial is not added.
+    pub fn add_partial(
+        &mut self,
+        name: impl Into<String>,
+        partial: Partial,
+    ) -> Result<(), SchemaError> {
+        let name: String = name.into();
+        if self.partials.contains_key(&name) {
+            panic!("Partial {name} already set.");
+        }
+        self.partials.insert(name.clone(), partial);
+        let result = self.check_values();
+        if result.is_err() {
+            self.partials.remove(&name);
+        }
+        result
+    }
+
+    /// The values shown by the templates set so far, with the data types, scopes and numbers of axes they may have.
+    /// Values are checked against it as they are added, so mistakes are found before computing the rest.
+    ///
+    /// Fails if a template includes a partial that has not been added yet.
+    pub fn schema(&self) -> Result<PayloadSchema, ArgumentError> {
+        let mut schema = PayloadSchema::default();
+        if let Some(neuron_template) = &self.mlp_neuron_template {
+            schema.extend(neuron_template.schema(&self.partials)?);
+        }
+        let index_template = self.index_template.clone().unwrap_or_default();
+        schema.extend(index_template.schema(&self.partials)?);
+        let layer_template = self.layer_template.clone().unwrap_or_default();
+        schema.extend(layer_template.schema(&self.partials)?);
+        Ok(schema)
+    }
+
+    /// Checks a value against the schema of the templates set so far.
+    /// Templates including partials that have not been added yet are checked when the payload is built.
+    pub fn check_value(&self, key: &str, value: &Value) -> Result<(), SchemaError> {
+        match self.schema() {
+            Ok(schema) => schema.check(key, value),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Checks the values added so far against the schema, after the templates changed.
+    fn check_values(&self) -> Result<(), SchemaError> {
+        let Ok(schema) = self.schema() else {
+            return Ok(());
+        };
+        self.values
+            .iter()
+            .try_for_each(|(key, value)| schema.check(key, value))
+    }
+
+    /// Sets the token vocabulary, which allows elements to show `U32` values as text.
+    pub fn vocabulary(&mut self, vocabulary: Vocabulary) {
+        assert!(self.vocabulary.is_none(), "Vocabulary already set.");
+        self.vocabulary = Some(vocabulary);
+    }
+
+    pub fn get_vocabulary(&self) -> Option<&Vocabulary> {
+        self.vocabulary.as_ref()
     }
 
     pub fn contains_key(&self, key: &str) -> bool {
@@ -60,9 +259,38 @@ impl PayloadBuilder {
         if self.contains_key(&key) {
             panic!("Value {key} already set.");
         }
+        if let Err(error) = self.check_value(&key, &value) {
+            panic!("{error}");
+        }
         self.values.insert(key, value);
     }
 
+    /// Adds a `Neuron` scoped value holding a list of token snippets for every neuron, e.g. max activating examples.
+    /// `snippets[layer_index][neuron_index]` contains the snippets of the given neuron and the number of snippets may differ between neurons.
+    pub fn add_token_snippets(
+        &mut self,
+        key: impl Into<String>,
+        snippets: Vec<Vec<Vec<TokenSnippet>>>,
+    ) {
+        assert_eq!(
+            snippets.len(),
+            self.num_layers,
+            "Token snippets must be given for every layer."
+        );
+        assert!(
+            snippets
+                .iter()
+                .all(|layer| layer.len() == self.num_mlp_neurons),
+            "Token snippets must be given for every neuron in every layer."
+        );
+        let snippets = Array2::from_shape_vec(
+            (self.num_layers, self.num_mlp_neurons),
+            snippets.into_iter().flatten().collect(),
+        )
+        .unwrap();
+        self.add_value(key, Value::new(snippets, Scope::Neuron));
+    }
+
     pub fn set_rank_values(&mut self, rank_values_key: impl Into<String>) {
         let key: String = rank_values_key.into();
         if let Some(rank_values) = self.values.get(&key) {
@@ -84,6 +312,166 @@ impl PayloadBuilder {
         self.rank_values_key = Some(key);
     }
 
+    /// Checks that further values can be computed from the F32 value `key` with the given scope.
+    fn check_source(&self, key: &str, required_scope: Scope) -> Result<(), RequestError> {
+        let source = self
+            .values
+            .get(key)
+            .ok_or_else(|| RequestError::MissingValue(key.to_owned()))?;
+        if source.scope() != required_scope {
+            return Err(RequestError::Scope {
+                key: key.to_owned(),
+                required_scope,
+                found_scope: source.scope(),
+            });
+        }
+        if source.data_type() != value::DataType::F32 {
+            return Err(RequestError::DataType {
+                key: key.to_owned(),
+                found_data_type: source.data_type(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that a computed value does not replace a value added before.
+    fn check_unused(&self, key: &str) -> Result<(), RequestError> {
+        if self.contains_key(key) {
+            Err(RequestError::ValueSet(key.to_owned()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Computes the `k` most similar neurons of every neuron when the payload is built.
+    /// Similarity is measured between the flattened per neuron arrays of the `Neuron` scoped F32 value `source_key`.
+    ///
+    /// The neighbours are added as a U32 value `neighbours_key` with shape `[num_layers, num_mlp_neurons, k, 2]`,
+    /// where the last axis holds the layer and neuron index of each neighbour.
+    /// Their similarities are added as an F32 value `scores_key` with shape `[num_layers, num_mlp_neurons, k]`.
+    /// Both are sorted by decreasing similarity. `k` must be smaller than the number of neurons.
+    pub fn add_nearest_neighbours(
+        &mut self,
+        neighbours_key: impl Into<String>,
+        scores_key: impl Into<String>,
+        source_key: impl Into<String>,
+        k: usize,
+        metric: SimilarityMetric,
+        mode: SimilarityMode,
+    ) -> Result<(), RequestError> {
+        let neighbours_key: String = neighbours_key.into();
+        let scores_key: String = scores_key.into();
+        let source_key: String = source_key.into();
+        self.check_source(&source_key, Scope::Neuron)?;
+        let num_neurons = self.num_layers * self.num_mlp_neurons;
+        if k >= num_neurons {
+            return Err(RequestError::TooManyNeighbours { k, num_neurons });
+        }
+        if mode == (SimilarityMode::Chunked { chunk_size: 0 }) {
+            return Err(RequestError::EmptyChunks);
+        }
+        for key in [&neighbours_key, &scores_key] {
+            self.check_unused(key)?;
+        }
+        self.neighbours_requests.push(NeighboursRequest {
+            neighbours_key,
+            scores_key,
+            source_key,
+            k,
+            metric,
+            mode,
+        });
+        Ok(())
+    }
+
+    /// Clusters the neurons when the payload is built, using the flattened per neuron arrays of the `Neuron` scoped F32 value `source_key`.
+    /// The vectors are normalized according to `metric` before clustering.
+    ///
+    /// The cluster of every neuron is added as a `Neuron` scoped U32 value `key`, and a page is generated for every cluster.
+    /// There must be at least one and at most as many clusters as neurons.
+    pub fn cluster_neurons(
+        &mut self,
+        key: impl Into<String>,
+        source_key: impl Into<String>,
+        num_clusters: usize,
+        method: ClusteringMethod,
+        metric: SimilarityMetric,
+    ) -> Result<(), RequestError> {
+        let key: String = key.into();
+        let source_key: String = source_key.into();
+        self.check_source(&source_key, Scope::Neuron)?;
+        let num_neurons = self.num_layers * self.num_mlp_neurons;
+        if num_clusters == 0 || num_clusters > num_neurons {
+            return Err(RequestError::NumClusters {
+                num_clusters,
+                num_neurons,
+            });
+        }
+        self.check_unused(&key)?;
+        self.clustering_requests.push(ClusteringRequest {
+            key,
+            source_key,
+            num_clusters,
+            method,
+            metric,
+        });
+        Ok(())
+    }
+
+    /// Computes the strongest connections between neurons of different layers when the payload is built.
+    ///
+    /// `w_in_key` and `w_out_key` must be `Global` F32 values with the MLP input weights of shape
+    /// `[num_layers, d_model, num_mlp_neurons]` and output weights of shape `[num_layers, num_mlp_neurons, d_model]`.
+    /// The virtual weight from a neuron in layer `i` to a neuron in a later layer `j` is the dot product of its
+    /// output weights with the input weights of the other neuron.
+    ///
+    /// For every neuron, the `k` upstream and downstream connections with the largest absolute weights are added as the
+    /// `Neuron` scoped values `{key}_upstream` and `{key}_downstream` with shape `[num_layers, num_mlp_neurons, k, 2]`,
+    /// holding the layer and neuron index of every connected neuron, and `{key}_upstream_weights` and
+    /// `{key}_downstream_weights` with the weights. They can be shown with the `connections` element.
+    pub fn add_connections(
+        &mut self,
+        key: impl AsRef<str>,
+        w_in_key: impl Into<String>,
+        w_out_key: impl Into<String>,
+        k: usize,
+    ) -> Result<(), RequestError> {
+        let keys = ConnectionKeys::new(key.as_ref());
+        let w_in_key: String = w_in_key.into();
+        let w_out_key: String = w_out_key.into();
+        self.check_source(&w_in_key, Scope::Global)?;
+        self.check_source(&w_out_key, Scope::Global)?;
+        let w_in_shape = self.values[&w_in_key].shape().to_vec();
+        let w_out_shape = self.values[&w_out_key].shape().to_vec();
+        if !(w_in_shape.len() == 3
+            && w_in_shape[0] == self.num_layers
+            && w_in_shape[2] == self.num_mlp_neurons)
+        {
+            return Err(RequestError::Shape {
+                key: w_in_key,
+                required_shape: "[num_layers, d_model, num_mlp_neurons]",
+                found_shape: w_in_shape,
+            });
+        }
+        if w_out_shape != [w_in_shape[0], w_in_shape[2], w_in_shape[1]] {
+            return Err(RequestError::Shape {
+                key: w_out_key,
+                required_shape: "[num_layers, num_mlp_neurons, d_model] matching the input weights",
+                found_shape: w_out_shape,
+            });
+        }
+        for key in keys.iter() {
+            self.check_unused(key)?;
+        }
+        self.connections_requests.push(ConnectionsRequest {
+            keys,
+            w_in_key,
+            w_out_key,
+            k,
+        });
+        Ok(())
+    }
+
     pub fn build(self) -> Result<Payload, ArgumentError> {
         if self.contains_key("rank") {
             panic!("Value name 'neuron_ranks' is reserved.")
@@ -95,15 +483,22 @@ impl PayloadBuilder {
             num_layers,
             num_mlp_neurons,
             mlp_neuron_template,
+            index_template,
+            layer_template,
+            partials,
+            vocabulary,
             mut values,
             rank_values_key,
+            neighbours_requests,
+            clustering_requests,
+            connections_requests,
         } = self;
 
         if let Some(rank_values_key) = rank_values_key {
             let rank_values = values
                 .get(&rank_values_key).unwrap_or_else(|| panic!("No value found with key '{rank_values_key}'. This should be guaranteed by the `set_rank_values` method."));
             let rank_values_type = rank_values.data_type();
-            let rank_values = rank_values.as_f32().unwrap_or_else(|| panic!("Value with key '{rank_values_key}' has the data type '{rank_values_type}', but only F32 is supported. This should be guaranteed by teh `set_rank_values` method."));
+            let rank_values = f32_numbers(rank_values).unwrap_or_else(|| panic!("Value with key '{rank_values_key}' has the data type '{rank_values_type}', but only F32 is supported. This should be guaranteed by teh `set_rank_values` method."));
             let rank_values = rank_values.view().into_dimensionality::<Ix2>().unwrap();
             assert_eq!(rank_values.shape(), &[num_layers, num_mlp_neurons]);
 
@@ -125,13 +520,330 @@ impl PayloadBuilder {
             );
         }
 
+        for request in neighbours_requests {
+            let NeighboursRequest {
+                neighbours_key,
+                scores_key,
+                source_key,
+                k,
+                metric,
+                mode,
+            } = request;
+            if values.contains_key(&neighbours_key) || values.contains_key(&scores_key) {
+                panic!("Value {neighbours_key} or {scores_key} already set.");
+            }
+            let source = f32_numbers(&values[&source_key]).unwrap();
+            let vector_size = source.len() / (num_layers * num_mlp_neurons);
+            let vectors = source
+                .to_shape((num_layers * num_mlp_neurons, vector_size))
+                .unwrap();
+
+            println!("Computing nearest neighbours of {source_key}...");
+            let (neighbours, scores) =
+                similarity::nearest_neighbours(vectors.view(), k, metric, mode);
+            // Neighbours are stored by layer and neuron index, which stay meaningful for subsets of the payload.
+            let neighbours = Array4::from_shape_fn(
+                (num_layers, num_mlp_neurons, k, 2),
+                |(layer_index, neuron_index, rank, axis)| {
+                    let neighbour =
+                        neighbours[(layer_index * num_mlp_neurons + neuron_index, rank)];
+                    let index = if axis == 0 {
+                        neighbour / num_mlp_neurons
+                    } else {
+                        neighbour % num_mlp_neurons
+                    };
+                    u32::try_from(index).unwrap()
+                },
+            );
+            let scores = scores.into_shape((num_layers, num_mlp_neurons, k)).unwrap();
+            values.insert(neighbours_key, Value::new(neighbours, Scope::Neuron));
+            values.insert(scores_key, Value::new(scores, Scope::Neuron));
+        }
+
+        for request in connections_requests {
+            let ConnectionsRequest {
+                keys,
+                w_in_key,
+                w_out_key,
+                k,
+            } = request;
+            for key in keys.iter() {
+                if values.contains_key(key) {
+                    panic!("Value {key} already set.");
+                }
+            }
+            let w_in = f32_numbers(&values[&w_in_key]).unwrap();
+            let w_out = f32_numbers(&values[&w_out_key]).unwrap();
+
+            println!("Computing connections from {w_in_key} and {w_out_key}...");
+            let TopConnections {
+                upstream,
+                upstream_weights,
+                downstream,
+                downstream_weights,
+            } = connections::top_connections(
+                w_in.view().into_dimensionality::<Ix3>().unwrap(),
+                w_out.view().into_dimensionality::<Ix3>().unwrap(),
+                k,
+            );
+            values.insert(keys.upstream, Value::new(upstream, Scope::Neuron));
+            values.insert(
+                keys.upstream_weights,
+                Value::new(upstream_weights, Scope::Neuron),
+            );
+            values.insert(keys.downstream, Value::new(downstream, Scope::Neuron));
+            values.insert(
+                keys.downstream_weights,
+                Value::new(downstream_weights, Scope::Neuron),
+            );
+        }
+
+        let mut clusterings = Vec::with_capacity(clustering_requests.len());
+        for request in clustering_requests {
+            let ClusteringRequest {
+                key,
+                source_key,
+                num_clusters,
+                method,
+                metric,
+            } = request;
+            if values.contains_key(&key) {
+                panic!("Value {key} already set.");
+            }
+            let source = f32_numbers(&values[&source_key]).unwrap();
+            let vector_size = source.len() / (num_layers * num_mlp_neurons);
+            let vectors = source
+                .to_shape((num_layers * num_mlp_neurons, vector_size))
+                .unwrap();
+
+            println!("Clustering neurons by {source_key}...");
+            let assignments = clustering::cluster(vectors.view(), num_clusters, method, metric);
+            let assignments = Array2::from_shape_vec(
+                (num_layers, num_mlp_neurons),
+                assignments
+                    .into_iter()
+                    .map(|cluster| u32::try_from(cluster).unwrap())
+                    .collect(),
+            )
+            .unwrap();
+            values.insert(key.clone(), Value::new(assignments, Scope::Neuron));
+            clusterings.push(Clustering {
+                key,
+                source_key,
+                num_clusters,
+            });
+        }
+
         let values = Values::new(values);
 
         Payload::new(
             num_layers,
             num_mlp_neurons,
-            mlp_neuron_template.unwrap(),
+            Templates {
+                mlp_neuron: mlp_neuron_template.unwrap(),
+                index: index_template.unwrap_or_default(),
+                layer: layer_template.unwrap_or_default(),
+                partials,
+            },
+            vocabulary,
             values,
+            clusterings,
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Array2, Array3};
+
+    use super::{PayloadBuilder, RequestError};
+    use crate::{
+        data::{
+            value::{DataType, Scope},
+            ClusteringMethod, SimilarityMetric, SimilarityMode, Value,
+        },
+        html::template::{NeuronTemplate, Partial, SchemaError},
+    };
+
+    /// A builder for 2 layers with 3 neurons each, with a vector and a count for every neuron and a global vector.
+    fn builder() -> PayloadBuilder {
+        let mut builder = PayloadBuilder::new(2, 3);
+        builder.add_value(
+            "directions",
+            Value::new(Array3::<f32>::ones((2, 3, 4)), Scope::Neuron),
+        );
+        builder.add_value(
+            "counts",
+            Value::new(Array2::<u32>::ones((2, 3)), Scope::Neuron),
+        );
+        builder.add_value(
+            "embedding",
+            Value::new(Array2::<f32>::ones((2, 4)), Scope::Global),
+        );
+        builder
+    }
+
+    fn add_neighbours(
+        source_key: &str,
+        k: usize,
+        mode: SimilarityMode,
+    ) -> Result<(), RequestError> {
+        builder().add_nearest_neighbours(
+            "neighbours",
+            "scores",
+            source_key,
+            k,
+            SimilarityMetric::Cosine,
+            mode,
+        )
+    }
+
+    #[test]
+    fn neighbours_need_a_neuron_scoped_f32_source() {
+        assert_eq!(
+            add_neighbours("directions", 5, SimilarityMode::BruteForce),
+            Ok(())
+        );
+        assert_eq!(
+            add_neighbours("missing", 2, SimilarityMode::BruteForce),
+            Err(RequestError::MissingValue("missing".to_string()))
+        );
+        assert_eq!(
+            add_neighbours("embedding", 2, SimilarityMode::BruteForce),
+            Err(RequestError::Scope {
+                key: "embedding".to_string(),
+                required_scope: Scope::Neuron,
+                found_scope: Scope::Global,
+            })
+        );
+        assert_eq!(
+            add_neighbours("counts", 2, SimilarityMode::BruteForce),
+            Err(RequestError::DataType {
+                key: "counts".to_string(),
+                found_data_type: DataType::U32,
+            })
+        );
+        assert_eq!(
+            add_neighbours("directions", 6, SimilarityMode::BruteForce),
+            Err(RequestError::TooManyNeighbours {
+                k: 6,
+                num_neurons: 6
+            })
+        );
+        assert_eq!(
+            add_neighbours("directions", 2, SimilarityMode::Chunked { chunk_size: 0 }),
+            Err(RequestError::EmptyChunks)
+        );
+        let mut builder = builder();
+        assert_eq!(
+            builder.add_nearest_neighbours(
+                "counts",
+                "scores",
+                "directions",
+                2,
+                SimilarityMetric::Cosine,
+                SimilarityMode::BruteForce
+            ),
+            Err(RequestError::ValueSet("counts".to_string()))
+        );
+    }
+
+    #[test]
+    fn clusters_need_a_neuron_scoped_f32_source() {
+        let cluster = |source_key: &str, num_clusters: usize| {
+            builder().cluster_neurons(
+                "cluster",
+                source_key,
+                num_clusters,
+                ClusteringMethod::Agglomerative,
+                SimilarityMetric::Cosine,
+            )
+        };
+        assert_eq!(cluster("directions", 6), Ok(()));
+        assert_eq!(
+            cluster("missing", 2),
+            Err(RequestError::MissingValue("missing".to_string()))
+        );
+        assert!(matches!(
+            cluster("embedding", 2),
+            Err(RequestError::Scope { .. })
+        ));
+        assert!(matches!(
+            cluster("counts", 2),
+            Err(RequestError::DataType { .. })
+        ));
+        for num_clusters in [0, 7] {
+            assert_eq!(
+                cluster("directions", num_clusters),
+                Err(RequestError::NumClusters {
+                    num_clusters,
+                    num_neurons: 6,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn connections_need_global_f32_weights_of_matching_shapes() {
+        let mut builder = builder();
+        builder.add_value(
+            "w_in",
+            Value::new(Array3::<f32>::ones((2, 4, 3)), Scope::Global),
+        );
+        builder.add_value(
+            "w_out",
+            Value::new(Array3::<f32>::ones((2, 3, 4)), Scope::Global),
+        );
+        assert!(matches!(
+            builder.add_connections("connections", "w_in", "missing", 2),
+            Err(RequestError::MissingValue(key)) if key == "missing"
+        ));
+        assert!(matches!(
+            builder.add_connections("connections", "directions", "w_out", 2),
+            Err(RequestError::Scope { .. })
+        ));
+        assert!(matches!(
+            builder.add_connections("connections", "w_in", "embedding", 2),
+            Err(RequestError::Shape { key, found_shape, .. }) if key == "embedding" && found_shape == [2, 4]
+        ));
+        assert!(matches!(
+            builder.add_connections("connections", "w_out", "w_in", 2),
+            Err(RequestError::Shape { key, .. }) if key == "w_out"
+        ));
+        // There may be fewer connections than `k`.
+        assert_eq!(
+            builder.add_connections("connections", "w_in", "w_out", 10),
+            Ok(())
+        );
+        builder.add_value(
+            "directions_upstream",
+            Value::new(Array2::<f32>::ones((2, 3)), Scope::Neuron),
+        );
+        assert_eq!(
+            builder.add_connections("directions", "w_in", "w_out", 2),
+            Err(RequestError::ValueSet("directions_upstream".to_string()))
+        );
+    }
+
+    #[test]
+    fn templates_must_match_the_values_added_before() {
+        let mut builder = builder();
+        let result =
+            builder.mlp_neuron_template(NeuronTemplate::parse("$heatmap(counts)").unwrap());
+        assert!(matches!(
+            result,
+            Err(SchemaError::DataType { name, found_data_type: DataType::U32, .. }) if name == "counts"
+        ));
+        // The conflicting template is not set, so another one can be.
+        builder
+            .mlp_neuron_template(NeuronTemplate::parse("$include(counts)").unwrap())
+            .unwrap();
+
+        let result = builder.add_partial("counts", Partial::parse("$heatmap(counts)").unwrap());
+        assert!(matches!(result, Err(SchemaError::DataType { .. })));
+        builder
+            .add_partial("counts", Partial::parse("$value(counts)").unwrap())
+            .unwrap();
+        assert!(builder.build().is_ok());
+    }
+}
@@ -0,0 +1,356 @@
+use std::fmt::Display;
+
+use ndarray::{ArrayD, ArrayViewD, Axis, IxDyn, Zip};
+use serde::{Deserialize, Serialize};
+
+/// Number of bits used to store each element of a quantized value.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Quantization {
+    U8,
+    U16,
+}
+
+impl Quantization {
+    /// The level storing NaN and infinite elements, which are dequantized to NaN.
+    fn non_finite_level(self) -> f32 {
+        match self {
+            Quantization::U8 => u8::MAX as f32,
+            Quantization::U16 => u16::MAX as f32,
+        }
+    }
+
+    /// The highest level storing finite elements.
+    fn max_level(self) -> f32 {
+        self.non_finite_level() - 1.
+    }
+}
+
+impl Display for Quantization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// The error introduced by quantizing a value, measured over its finite elements.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct QuantizationError {
+    pub max_abs_error: f32,
+    pub rms_error: f32,
+    /// The number of NaN and infinite elements, which are all dequantized to NaN.
+    pub non_finite_count: usize,
+}
+
+impl Display for QuantizationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "max absolute error {:e}, RMS error {:e}",
+            self.max_abs_error, self.rms_error
+        )?;
+        if self.non_finite_count > 0 {
+            write!(
+                f,
+                ", {} non-finite elements stored as NaN",
+                self.non_finite_count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The error of a single group, kept so that the error of a subset of the groups can be computed.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct GroupError {
+    max_abs_error: f32,
+    squared_error_sum: f64,
+    finite_count: usize,
+    non_finite_count: usize,
+}
+
+impl GroupError {
+    fn combine(self, other: &Self) -> Self {
+        Self {
+            max_abs_error: self.max_abs_error.max(other.max_abs_error),
+            squared_error_sum: self.squared_error_sum + other.squared_error_sum,
+            finite_count: self.finite_count + other.finite_count,
+            non_finite_count: self.non_finite_count + other.non_finite_count,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum QuantizedData {
+    U8(ArrayD<u8>),
+    U16(ArrayD<u16>),
+}
+
+/// An `f32` array stored with 8 or 16 bits per element.
+///
+/// The array is split into groups along its first `group_axis_num` axes, which for values are the scope axes,
+/// so e.g. every neuron of a `Neuron` scoped value gets its own scale and offset. The highest level is reserved for
+/// NaN and infinite elements, which are dequantized to NaN.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuantizedArray {
+    data: QuantizedData,
+    /// Shape equal to the first `group_axis_num` axes of the data.
+    scale: ArrayD<f32>,
+    offset: ArrayD<f32>,
+    group_errors: ArrayD<GroupError>,
+}
+
+impl QuantizedArray {
+    pub fn quantize(
+        array: ArrayViewD<f32>,
+        group_axis_num: usize,
+        quantization: Quantization,
+    ) -> Self {
+        let group_shape = &array.shape()[..group_axis_num];
+        let group_size: usize = array.shape()[group_axis_num..].iter().product();
+        let groups = array
+            .to_shape((group_shape.iter().product::<usize>(), group_size))
+            .unwrap();
+
+        let max_level = quantization.max_level();
+        let non_finite_level = quantization.non_finite_level();
+        let mut scale = Vec::with_capacity(groups.nrows());
+        let mut offset = Vec::with_capacity(groups.nrows());
+        let mut group_errors = Vec::with_capacity(groups.nrows());
+        let mut levels = Vec::with_capacity(array.len());
+        for group in groups.rows() {
+            let (min, max) = group
+                .iter()
+                .filter(|value| value.is_finite())
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| {
+                    (min.min(value), max.max(value))
+                });
+            let (min, max) = if min <= max { (min, max) } else { (0., 0.) };
+            let group_scale = if max > min {
+                (max - min) / max_level
+            } else {
+                1.
+            };
+            let mut group_error = GroupError::default();
+            levels.extend(group.iter().map(|&value| {
+                if value.is_finite() {
+                    let level = ((value - min) / group_scale).round().clamp(0., max_level);
+                    // The same arithmetic as dequantizing.
+                    let error = (value - (level * group_scale + min)).abs();
+                    group_error.max_abs_error = group_error.max_abs_error.max(error);
+                    group_error.squared_error_sum += (error as f64).powi(2);
+                    group_error.finite_count += 1;
+                    level
+                } else {
+                    group_error.non_finite_count += 1;
+                    non_finite_level
+                }
+            }));
+            scale.push(group_scale);
+            offset.push(min);
+            group_errors.push(group_error);
+        }
+        let levels = ArrayD::from_shape_vec(array.shape(), levels).unwrap();
+
+        let data = match quantization {
+            Quantization::U8 => QuantizedData::U8(levels.map(|&level| level as u8)),
+            Quantization::U16 => QuantizedData::U16(levels.map(|&level| level as u16)),
+        };
+        let scale = ArrayD::from_shape_vec(group_shape, scale).unwrap();
+        let offset = ArrayD::from_shape_vec(group_shape, offset).unwrap();
+
+        let group_errors = ArrayD::from_shape_vec(group_shape, group_errors).unwrap();
+        Self {
+            data,
+            scale,
+            offset,
+            group_errors,
+        }
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        match self.data {
+            QuantizedData::U8(ref data) => data.shape(),
+            QuantizedData::U16(ref data) => data.shape(),
+        }
+    }
+
+    pub fn quantization(&self) -> Quantization {
+        match self.data {
+            QuantizedData::U8(_) => Quantization::U8,
+            QuantizedData::U16(_) => Quantization::U16,
+        }
+    }
+
+    /// The error over all groups, so the error of a subset only covers the selected groups.
+    pub fn error(&self) -> QuantizationError {
+        let error = self
+            .group_errors
+            .iter()
+            .fold(GroupError::default(), GroupError::combine);
+        QuantizationError {
+            max_abs_error: error.max_abs_error,
+            rms_error: (error.squared_error_sum / error.finite_count.max(1) as f64).sqrt() as f32,
+            non_finite_count: error.non_finite_count,
+        }
+    }
+
+    fn group_axis_num(&self) -> usize {
+        self.scale.ndim()
+    }
+
+    /// Dequantizes the whole array, which allocates an `f32` array of its full size.
+    pub fn dequantize(&self) -> ArrayD<f32> {
+        self.dequantize_group(&[])
+    }
+
+    /// Dequantizes the part of the array given by indexing the first axes with `group_index`.
+    /// `group_index` can have at most as many elements as there are group axes.
+    pub fn dequantize_group(&self, group_index: &[usize]) -> ArrayD<f32> {
+        assert!(group_index.len() <= self.group_axis_num());
+        fn dequantize<T: Copy + Into<f32> + PartialEq>(
+            data: &ArrayD<T>,
+            scale: &ArrayD<f32>,
+            offset: &ArrayD<f32>,
+            group_index: &[usize],
+            non_finite_level: T,
+        ) -> ArrayD<f32> {
+            let mut data = data.view();
+            let mut scale = scale.view();
+            let mut offset = offset.view();
+            for &index in group_index {
+                data.index_axis_inplace(Axis(0), index);
+                scale.index_axis_inplace(Axis(0), index);
+                offset.index_axis_inplace(Axis(0), index);
+            }
+            // Broadcast the per group parameters over the remaining axes. The parameters of subsets are not in
+            // standard layout, so reshaping them may copy.
+            let mut parameter_shape = scale.shape().to_vec();
+            parameter_shape.resize(data.ndim(), 1);
+            let scale = scale.to_shape(IxDyn(&parameter_shape)).unwrap();
+            let offset = offset.to_shape(IxDyn(&parameter_shape)).unwrap();
+            let scale = scale.broadcast(data.shape()).unwrap();
+            let offset = offset.broadcast(data.shape()).unwrap();
+            Zip::from(&data)
+                .and(&scale)
+                .and(&offset)
+                .map_collect(|&level, &scale, &offset| {
+                    if level == non_finite_level {
+                        f32::NAN
+                    } else {
+                        level.into() * scale + offset
+                    }
+                })
+        }
+
+        match self.data {
+            QuantizedData::U8(ref data) => {
+                dequantize(data, &self.scale, &self.offset, group_index, u8::MAX)
+            }
+            QuantizedData::U16(ref data) => {
+                dequantize(data, &self.scale, &self.offset, group_index, u16::MAX)
+            }
+        }
+    }
+
+    /// Keeps only the given indices along the given group axis.
+    pub fn select(&self, axis: Axis, indices: &[usize]) -> Self {
+        assert!(axis.index() < self.group_axis_num());
+        let data = match self.data {
+            QuantizedData::U8(ref data) => QuantizedData::U8(data.select(axis, indices)),
+            QuantizedData::U16(ref data) => QuantizedData::U16(data.select(axis, indices)),
+        };
+        Self {
+            data,
+            scale: self.scale.select(axis, indices),
+            offset: self.offset.select(axis, indices),
+            group_errors: self.group_errors.select(axis, indices),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, Array3, Axis};
+
+    use super::{Quantization, QuantizedArray};
+
+    #[test]
+    fn dequantizing_restores_every_group_within_half_a_level() {
+        let array = Array3::from_shape_fn((2, 3, 50), |(i, j, k)| {
+            ((i * 3 + j + 1) as f32 * 0.37 * k as f32).sin() * (i + 1) as f32 * 10f32.powi(j as i32)
+        })
+        .into_dyn();
+        for quantization in [Quantization::U8, Quantization::U16] {
+            let quantized = QuantizedArray::quantize(array.view(), 2, quantization);
+            let dequantized = quantized.dequantize();
+            assert_eq!(dequantized.shape(), array.shape());
+            for (group, dequantized_group) in array
+                .lanes(Axis(2))
+                .into_iter()
+                .zip(dequantized.lanes(Axis(2)))
+            {
+                let (min, max) = group
+                    .iter()
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| {
+                        (min.min(value), max.max(value))
+                    });
+                // Half a level, plus rounding errors of `f32` arithmetic at the magnitude of the numbers.
+                let tolerance = (max - min) / quantization.max_level() / 2.
+                    + 4. * f32::EPSILON * min.abs().max(max.abs());
+                for (&original, &dequantized) in group.iter().zip(dequantized_group) {
+                    assert!((original - dequantized).abs() <= tolerance);
+                }
+            }
+            let error = quantized.error();
+            assert!(error.max_abs_error > 0.);
+            assert!(error.rms_error <= error.max_abs_error);
+            assert_eq!(error.non_finite_count, 0);
+        }
+    }
+
+    #[test]
+    fn groups_can_be_dequantized_alone() {
+        let array = Array3::from_shape_fn((2, 3, 4), |(i, j, k)| (i * 12 + j * 4 + k) as f32 * 0.5)
+            .into_dyn();
+        let quantized = QuantizedArray::quantize(array.view(), 2, Quantization::U8);
+        let dequantized = quantized.dequantize();
+        assert_eq!(
+            quantized.dequantize_group(&[1]),
+            dequantized.index_axis(Axis(0), 1)
+        );
+        assert_eq!(
+            quantized.dequantize_group(&[1, 2]),
+            dequantized.index_axis(Axis(0), 1).index_axis(Axis(0), 2)
+        );
+    }
+
+    #[test]
+    fn errors_of_selected_groups_cover_only_them() {
+        let array = Array3::from_shape_fn((2, 3, 7), |(i, j, k)| {
+            (k as f32 * 0.3).cos() * 10f32.powi((i * 3 + j) as i32 - 3)
+        })
+        .into_dyn();
+        let quantized = QuantizedArray::quantize(array.view(), 2, Quantization::U8);
+        let selected = quantized.select(Axis(1), &[0]);
+        let expected =
+            QuantizedArray::quantize(array.select(Axis(1), &[0]).view(), 2, Quantization::U8);
+        assert_eq!(selected.error(), expected.error());
+        assert!(selected.error().max_abs_error < quantized.error().max_abs_error);
+    }
+
+    #[test]
+    fn non_finite_elements_are_dequantized_to_nan() {
+        let array = array![[1., f32::NAN, 3.], [f32::INFINITY, -2., f32::NEG_INFINITY]].into_dyn();
+        let quantized = QuantizedArray::quantize(array.view(), 1, Quantization::U8);
+        let dequantized = quantized.dequantize();
+        for (&original, &dequantized) in array.iter().zip(&dequantized) {
+            if original.is_finite() {
+                assert!((original - dequantized).abs() < 1e-6);
+            } else {
+                assert!(dequantized.is_nan());
+            }
+        }
+        let error = quantized.error();
+        assert!(error.max_abs_error < 1e-6);
+        assert_eq!(error.non_finite_count, 3);
+    }
+}
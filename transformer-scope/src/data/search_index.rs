@@ -0,0 +1,422 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use itertools::Itertools;
+use ndarray::{ArrayViewD, Axis};
+use serde::Serialize;
+
+use super::{
+    search_query::{Comparison, SearchQuery, SearchQueryError, SearchTerm},
+    value::{Scope, ValueView},
+    Annotation, Annotations, Payload,
+};
+
+const ANNOTATION_FIELDS: [&str; 3] = ["label", "tag", "author"];
+const INDEX_FIELDS: [&str; 2] = ["layer", "neuron"];
+
+/// The searchable contents of a single neuron.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchDocument {
+    /// Original layer index.
+    pub layer: usize,
+    /// Original neuron index.
+    pub neuron: usize,
+    /// The strings of every `Neuron` scoped string value, joined by spaces.
+    pub text: BTreeMap<String, String>,
+    /// The number of every `Neuron` scoped U32 or F32 value holding a single number per neuron.
+    pub scalars: BTreeMap<String, f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<Annotation>,
+}
+
+fn contains_ignore_case(text: &str, pattern: &str) -> bool {
+    text.to_lowercase().contains(pattern)
+}
+
+impl SearchDocument {
+    fn matches_text(&self, pattern: &str) -> bool {
+        self.text
+            .values()
+            .any(|text| contains_ignore_case(text, pattern))
+            || ANNOTATION_FIELDS
+                .iter()
+                .any(|field| self.matches_field(field, pattern))
+    }
+
+    fn matches_field(&self, field: &str, pattern: &str) -> bool {
+        match (field, &self.annotation) {
+            ("label", Some(annotation)) => contains_ignore_case(&annotation.label, pattern),
+            ("tag", Some(annotation)) => annotation
+                .tags
+                .iter()
+                .any(|tag| contains_ignore_case(tag, pattern)),
+            ("author", Some(annotation)) => contains_ignore_case(&annotation.author, pattern),
+            ("label" | "tag" | "author", None) => false,
+            (field, _) => self
+                .text
+                .get(field)
+                .is_some_and(|text| contains_ignore_case(text, pattern)),
+        }
+    }
+
+    fn number(&self, field: &str) -> Option<f32> {
+        match field {
+            "layer" => Some(self.layer as f32),
+            "neuron" => Some(self.neuron as f32),
+            field => self.scalars.get(field).copied(),
+        }
+    }
+
+    fn matches(&self, term: &SearchTerm) -> bool {
+        match term {
+            SearchTerm::Text(text) => self.matches_text(&text.to_lowercase()),
+            SearchTerm::Field { field, text } => self.matches_field(field, &text.to_lowercase()),
+            SearchTerm::Compare {
+                field,
+                comparison,
+                number,
+            } => self
+                .number(field)
+                .is_some_and(|value| comparison.compare(value, *number)),
+        }
+    }
+}
+
+/// Index of the string values, scalar values and annotations of all neurons of a payload.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchIndex {
+    text_fields: BTreeSet<String>,
+    scalar_fields: BTreeSet<String>,
+    #[serde(serialize_with = "serialize_documents")]
+    documents: BTreeMap<(usize, usize), SearchDocument>,
+}
+
+fn serialize_documents<S: serde::Serializer>(
+    documents: &BTreeMap<(usize, usize), SearchDocument>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(documents.values())
+}
+
+fn neuron_part<'a, T>(
+    array: &'a ArrayViewD<'a, T>,
+    layer_index: usize,
+    neuron_index: usize,
+) -> ArrayViewD<'a, T> {
+    array
+        .index_axis(Axis(0), layer_index)
+        .index_axis_move(Axis(0), neuron_index)
+}
+
+impl SearchIndex {
+    pub fn new(payload: &Payload, annotations: &Annotations) -> Self {
+        let mut documents = BTreeMap::new();
+        for layer_index in 0..payload.num_layers() {
+            for neuron_index in 0..payload.num_mlp_neurons() {
+                let layer = payload.original_layer_index(layer_index);
+                let neuron = payload.original_neuron_index(neuron_index);
+                let document = SearchDocument {
+                    layer,
+                    neuron,
+                    text: BTreeMap::new(),
+                    scalars: BTreeMap::new(),
+                    annotation: annotations.get(layer, neuron).cloned(),
+                };
+                documents.insert((layer_index, neuron_index), document);
+            }
+        }
+
+        let mut text_fields = BTreeSet::new();
+        let mut scalar_fields = BTreeSet::new();
+        for (key, value) in payload.values() {
+            if value.scope() != Scope::Neuron {
+                continue;
+            }
+            match value.view() {
+                ValueView::String(array) => {
+                    text_fields.insert(key.to_string());
+                    for (&(layer_index, neuron_index), document) in documents.iter_mut() {
+                        let text = neuron_part(&array, layer_index, neuron_index)
+                            .iter()
+                            .join(" ");
+                        document.text.insert(key.to_string(), text);
+                    }
+                }
+                ValueView::U32(array) if array.ndim() == 2 => {
+                    scalar_fields.insert(key.to_string());
+                    for (&(layer_index, neuron_index), document) in documents.iter_mut() {
+                        let number = array[[layer_index, neuron_index]] as f32;
+                        document.scalars.insert(key.to_string(), number);
+                    }
+                }
+                ValueView::F32(array) if array.ndim() == 2 => {
+                    scalar_fields.insert(key.to_string());
+                    for (&(layer_index, neuron_index), document) in documents.iter_mut() {
+                        let number = array[[layer_index, neuron_index]];
+                        document.scalars.insert(key.to_string(), number);
+                    }
+                }
+                ValueView::QuantizedF32(array) if array.shape().len() == 2 => {
+                    scalar_fields.insert(key.to_string());
+                    // A single number per neuron, so dequantizing it whole is cheap.
+                    let array = array.dequantize();
+                    for (&(layer_index, neuron_index), document) in documents.iter_mut() {
+                        let number = array[[layer_index, neuron_index]];
+                        document.scalars.insert(key.to_string(), number);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Key the documents by original indices, which are used in queries, paths and annotations.
+        let documents = documents
+            .into_values()
+            .map(|document| ((document.layer, document.neuron), document))
+            .collect();
+        Self {
+            text_fields,
+            scalar_fields,
+            documents,
+        }
+    }
+
+    /// Updates the annotation of the neuron with the given original indices, e.g. after it was edited.
+    pub fn set_annotation(
+        &mut self,
+        layer_index: usize,
+        neuron_index: usize,
+        annotation: Option<Annotation>,
+    ) {
+        if let Some(document) = self.documents.get_mut(&(layer_index, neuron_index)) {
+            document.annotation = annotation;
+        }
+    }
+
+    /// Checks that every field of the query exists.
+    /// Text terms for scalar fields, e.g. `cluster:3`, are turned into comparisons.
+    fn resolve(&self, query: &SearchQuery) -> Result<Vec<SearchTerm>, SearchQueryError> {
+        query
+            .terms()
+            .iter()
+            .map(|term| match term {
+                SearchTerm::Text(_) => Ok(term.clone()),
+                SearchTerm::Field { field, text } => {
+                    if ANNOTATION_FIELDS.contains(&field.as_str())
+                        || self.text_fields.contains(field)
+                    {
+                        Ok(term.clone())
+                    } else if self.scalar_fields.contains(field) {
+                        let number = text.parse().map_err(|_| SearchQueryError::InvalidNumber {
+                            term: format!("{field}:{text}"),
+                            value: text.clone(),
+                        })?;
+                        Ok(SearchTerm::Compare {
+                            field: field.clone(),
+                            comparison: Comparison::Equal,
+                            number,
+                        })
+                    } else {
+                        Err(SearchQueryError::UnknownField(field.clone()))
+                    }
+                }
+                SearchTerm::Compare { field, .. } => {
+                    if INDEX_FIELDS.contains(&field.as_str()) || self.scalar_fields.contains(field)
+                    {
+                        Ok(term.clone())
+                    } else {
+                        Err(SearchQueryError::UnknownField(field.clone()))
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// The original `(layer_index, neuron_index)` of every neuron matching all terms of the query, in order.
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<(usize, usize)>, SearchQueryError> {
+        let terms = self.resolve(query)?;
+        Ok(self
+            .documents
+            .iter()
+            .filter(|(_, document)| terms.iter().all(|term| document.matches(term)))
+            .map(|(&indices, _)| indices)
+            .collect())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Array2, Array3};
+
+    use super::SearchIndex;
+    use crate::{
+        data::{
+            search_query::{Comparison, SearchTerm},
+            value::Scope,
+            Annotation, Annotations, Payload, Quantization, SearchQuery, SearchQueryError, Value,
+        },
+        html::template::NeuronTemplate,
+        PayloadBuilder,
+    };
+
+    /// A payload of 2 layers with 3 neurons each, with a string, U32, F32 and quantized scalar for every neuron
+    /// and a vector for every neuron, which is not searchable.
+    fn payload() -> Payload {
+        let mut builder = PayloadBuilder::new(2, 3);
+        builder
+            .mlp_neuron_template(NeuronTemplate::parse("$value(description)").unwrap())
+            .unwrap();
+        builder.add_value(
+            "description",
+            Value::new(
+                Array2::from_shape_fn((2, 3), |(layer, neuron)| {
+                    format!("neuron {neuron} of layer {layer}")
+                }),
+                Scope::Neuron,
+            ),
+        );
+        builder.add_value(
+            "cluster",
+            Value::new(
+                Array2::from_shape_fn((2, 3), |(layer, neuron)| (layer * 3 + neuron) as u32),
+                Scope::Neuron,
+            ),
+        );
+        builder.add_value(
+            "score",
+            Value::new(
+                Array2::from_shape_fn((2, 3), |(layer, neuron)| (layer * 10 + neuron) as f32 + 0.5),
+                Scope::Neuron,
+            ),
+        );
+        builder.add_value(
+            "quantized",
+            Value::quantized(
+                Array2::from_shape_fn((2, 3), |(_, neuron)| neuron as f32),
+                Scope::Neuron,
+                Quantization::U16,
+            ),
+        );
+        builder.add_value(
+            "directions",
+            Value::new(Array3::<f32>::ones((2, 3, 2)), Scope::Neuron),
+        );
+        builder.build().unwrap()
+    }
+
+    fn annotation(label: &str, tag: &str, author: &str) -> Annotation {
+        Annotation {
+            label: label.to_string(),
+            tags: vec![tag.to_string()],
+            author: author.to_string(),
+        }
+    }
+
+    fn annotations() -> Annotations {
+        let mut annotations = Annotations::new();
+        annotations.set(0, 1, annotation("Edge detector", "vision", "alice"));
+        annotations
+    }
+
+    fn search(index: &SearchIndex, query: &str) -> Result<Vec<(usize, usize)>, SearchQueryError> {
+        index.search(&SearchQuery::parse(query).unwrap())
+    }
+
+    #[test]
+    fn annotations_are_matched() {
+        let index = SearchIndex::new(&payload(), &annotations());
+        assert_eq!(search(&index, "label:edge"), Ok(vec![(0, 1)]));
+        assert_eq!(search(&index, "tag:VISION"), Ok(vec![(0, 1)]));
+        assert_eq!(search(&index, "author:alice"), Ok(vec![(0, 1)]));
+        assert_eq!(search(&index, "author:bob"), Ok(vec![]));
+        // Plain text is matched against annotations as well as string values.
+        assert_eq!(search(&index, "detector"), Ok(vec![(0, 1)]));
+        assert_eq!(search(&index, "label:neuron"), Ok(vec![]));
+        assert_eq!(
+            search(&index, "description:\"neuron 2\""),
+            Ok(vec![(0, 2), (1, 2)])
+        );
+    }
+
+    #[test]
+    fn text_terms_on_scalar_fields_are_comparisons() {
+        let index = SearchIndex::new(&payload(), &annotations());
+        assert_eq!(
+            index.resolve(&SearchQuery::parse("cluster:3").unwrap()),
+            Ok(vec![SearchTerm::Compare {
+                field: "cluster".to_string(),
+                comparison: Comparison::Equal,
+                number: 3.,
+            }])
+        );
+        assert_eq!(search(&index, "cluster:3"), Ok(vec![(1, 0)]));
+        assert_eq!(
+            search(&index, "cluster:three"),
+            Err(SearchQueryError::InvalidNumber {
+                term: "cluster:three".to_string(),
+                value: "three".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let index = SearchIndex::new(&payload(), &annotations());
+        assert_eq!(
+            search(&index, "colour:red"),
+            Err(SearchQueryError::UnknownField("colour".to_string()))
+        );
+        assert_eq!(
+            search(&index, "description>1"),
+            Err(SearchQueryError::UnknownField("description".to_string()))
+        );
+        // Only values with a single number per neuron are scalars.
+        assert_eq!(
+            search(&index, "directions>0"),
+            Err(SearchQueryError::UnknownField("directions".to_string()))
+        );
+    }
+
+    #[test]
+    fn scalars_are_compared() {
+        let index = SearchIndex::new(&payload(), &annotations());
+        assert_eq!(search(&index, "cluster<2"), Ok(vec![(0, 0), (0, 1)]));
+        assert_eq!(search(&index, "score>=11"), Ok(vec![(1, 1), (1, 2)]));
+        assert_eq!(search(&index, "quantized>1.5"), Ok(vec![(0, 2), (1, 2)]));
+        assert_eq!(
+            search(&index, "layer:1 neuron<=1"),
+            Ok(vec![(1, 0), (1, 1)])
+        );
+        // All terms must match.
+        assert_eq!(
+            search(&index, "score<5 quantized>=0.5"),
+            Ok(vec![(0, 1), (0, 2)])
+        );
+    }
+
+    #[test]
+    fn subsets_are_searched_by_original_indices() {
+        let subset = payload().subset(Some(&[1]), Some(&[2, 0])).unwrap();
+        let mut annotations = annotations();
+        annotations.set(1, 2, annotation("Corner detector", "vision", "bob"));
+        let index = SearchIndex::new(&subset, &annotations);
+        assert_eq!(search(&index, "score>0"), Ok(vec![(1, 0), (1, 2)]));
+        assert_eq!(search(&index, "cluster:5"), Ok(vec![(1, 2)]));
+        assert_eq!(search(&index, "neuron:2"), Ok(vec![(1, 2)]));
+        // The annotation of a neuron outside the subset is not searched.
+        assert_eq!(search(&index, "tag:vision"), Ok(vec![(1, 2)]));
+    }
+
+    #[test]
+    fn annotation_updates_are_searched() {
+        let mut index = SearchIndex::new(&payload(), &annotations());
+        index.set_annotation(1, 0, Some(annotation("Edge counter", "text", "bob")));
+        assert_eq!(search(&index, "label:edge"), Ok(vec![(0, 1), (1, 0)]));
+        index.set_annotation(0, 1, None);
+        assert_eq!(search(&index, "label:edge"), Ok(vec![(1, 0)]));
+        assert_eq!(search(&index, "author:alice"), Ok(vec![]));
+    }
+}
@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum SearchQueryError {
+    #[error("Unterminated quote starting at character {0}.")]
+    UnterminatedQuote(usize),
+    #[error("Term `{0}` has no value.")]
+    EmptyValue(String),
+    #[error("`{value}` in term `{term}` is not a number.")]
+    InvalidNumber { term: String, value: String },
+    #[error("Unknown field `{0}`. Fields are `label`, `tag`, `author`, `layer`, `neuron` and the names of string and scalar values.")]
+    UnknownField(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    pub fn compare(self, left: f32, right: f32) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Equal => left == right,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Greater => left > right,
+        }
+    }
+}
+
+/// A single condition of a search query. Text matches are case insensitive substring matches.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchTerm {
+    /// Matches the text in any field, e.g. `flipped` or `"mine flipped"`.
+    Text(String),
+    /// Matches the text in the given field, e.g. `label:"flipped"`.
+    Field { field: String, text: String },
+    /// Compares a number, e.g. `layer:5` or `ownership_heatmap_stds>0.3`.
+    Compare {
+        field: String,
+        comparison: Comparison,
+        number: f32,
+    },
+}
+
+/// A parsed search query. A neuron matches if it matches all terms.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SearchQuery {
+    terms: Vec<SearchTerm>,
+}
+
+/// Splits the query at whitespace outside of double quotes.
+fn split_terms(query: &str) -> Result<Vec<String>, SearchQueryError> {
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    let mut quote_start = None;
+    for (position, character) in query.chars().enumerate() {
+        match character {
+            '"' => {
+                quote_start = match quote_start {
+                    Some(_) => None,
+                    None => Some(position),
+                };
+                current.push(character);
+            }
+            character if character.is_whitespace() && quote_start.is_none() => {
+                if !current.is_empty() {
+                    terms.push(std::mem::take(&mut current));
+                }
+            }
+            character => current.push(character),
+        }
+    }
+    if let Some(quote_start) = quote_start {
+        return Err(SearchQueryError::UnterminatedQuote(quote_start));
+    }
+    if !current.is_empty() {
+        terms.push(current);
+    }
+    Ok(terms)
+}
+
+fn unquote(text: &str) -> String {
+    text.replace('"', "")
+}
+
+fn parse_term(term: &str) -> Result<SearchTerm, SearchQueryError> {
+    // The first operator outside of quotes separates the field from the value.
+    let operator = term
+        .char_indices()
+        .take_while(|&(_, character)| character != '"')
+        .find(|&(_, character)| matches!(character, ':' | '<' | '>' | '='));
+    let Some((operator_index, operator)) = operator else {
+        return Ok(SearchTerm::Text(unquote(term)));
+    };
+
+    let field = term[..operator_index].to_string();
+    let mut rest = &term[operator_index + 1..];
+    let comparison = match operator {
+        ':' => None,
+        '=' => Some(Comparison::Equal),
+        '<' | '>' => {
+            let or_equal = rest.starts_with('=');
+            if or_equal {
+                rest = &rest[1..];
+            }
+            Some(match (operator, or_equal) {
+                ('<', false) => Comparison::Less,
+                ('<', true) => Comparison::LessOrEqual,
+                ('>', false) => Comparison::Greater,
+                _ => Comparison::GreaterOrEqual,
+            })
+        }
+        _ => unreachable!(),
+    };
+    let value = unquote(rest);
+    if field.is_empty() || value.is_empty() {
+        return Err(SearchQueryError::EmptyValue(term.to_string()));
+    }
+
+    let parse_number = || {
+        value
+            .parse::<f32>()
+            .map_err(|_| SearchQueryError::InvalidNumber {
+                term: term.to_string(),
+                value: value.clone(),
+            })
+    };
+    match comparison {
+        Some(comparison) => Ok(SearchTerm::Compare {
+            field,
+            comparison,
+            number: parse_number()?,
+        }),
+        // Layer and neuron indices are numbers, so `layer:5` is a comparison.
+        None if field == "layer" || field == "neuron" => Ok(SearchTerm::Compare {
+            field,
+            comparison: Comparison::Equal,
+            number: parse_number()?,
+        }),
+        None => Ok(SearchTerm::Field { field, text: value }),
+    }
+}
+
+impl SearchQuery {
+    /// Parses a query of whitespace separated terms, e.g. `label:"flipped" layer:5 ownership_heatmap_stds>0.3`.
+    ///
+    /// Terms are either free text, `field:text`, or a comparison `field<number` with `<`, `<=`, `=`, `>=` or `>`.
+    /// Text containing whitespace can be quoted.
+    pub fn parse(query: &str) -> Result<Self, SearchQueryError> {
+        let terms = split_terms(query)?
+            .into_iter()
+            .map(|term| parse_term(&term))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { terms })
+    }
+
+    pub fn terms(&self) -> &[SearchTerm] {
+        &self.terms
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparison, SearchQuery, SearchQueryError, SearchTerm};
+
+    fn terms(query: &str) -> Vec<SearchTerm> {
+        SearchQuery::parse(query).unwrap().terms().to_vec()
+    }
+
+    fn compare(field: &str, comparison: Comparison, number: f32) -> SearchTerm {
+        SearchTerm::Compare {
+            field: field.to_string(),
+            comparison,
+            number,
+        }
+    }
+
+    #[test]
+    fn terms_are_separated_by_whitespace_outside_of_quotes() {
+        assert_eq!(
+            terms(" flipped  \"mine flipped\"\tlabel:\"a b:c\" "),
+            [
+                SearchTerm::Text("flipped".to_string()),
+                SearchTerm::Text("mine flipped".to_string()),
+                SearchTerm::Field {
+                    field: "label".to_string(),
+                    text: "a b:c".to_string(),
+                },
+            ]
+        );
+        assert!(SearchQuery::parse("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn comparisons_are_parsed() {
+        assert_eq!(
+            terms("a<1 b<=2 c=3 d>=-4 e>0.5 layer:5 neuron:7"),
+            [
+                compare("a", Comparison::Less, 1.0),
+                compare("b", Comparison::LessOrEqual, 2.0),
+                compare("c", Comparison::Equal, 3.0),
+                compare("d", Comparison::GreaterOrEqual, -4.0),
+                compare("e", Comparison::Greater, 0.5),
+                compare("layer", Comparison::Equal, 5.0),
+                compare("neuron", Comparison::Equal, 7.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_queries_are_rejected() {
+        assert_eq!(
+            SearchQuery::parse("label:\"mine flipped"),
+            Err(SearchQueryError::UnterminatedQuote(6))
+        );
+        for term in ["label:", ":flipped", "x>=", "tag:\"\""] {
+            assert_eq!(
+                SearchQuery::parse(term),
+                Err(SearchQueryError::EmptyValue(term.to_string()))
+            );
+        }
+        assert_eq!(
+            SearchQuery::parse("flipped layer:first"),
+            Err(SearchQueryError::InvalidNumber {
+                term: "layer:first".to_string(),
+                value: "first".to_string(),
+            })
+        );
+        assert_eq!(
+            SearchQuery::parse("stds>\"high\""),
+            Err(SearchQueryError::InvalidNumber {
+                term: "stds>\"high\"".to_string(),
+                value: "high".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::cmp::Ordering;
+
+use ndarray::{s, Array2, ArrayView2, Axis};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimilarityMetric {
+    /// Cosine of the angle between the vectors.
+    Cosine,
+    /// Pearson correlation, i.e. the cosine similarity of the mean centered vectors.
+    Correlation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimilarityMode {
+    /// Computes the full similarity matrix at once. Fastest, but needs memory quadratic in the number of neurons.
+    BruteForce,
+    /// Computes similarities for `chunk_size` neurons at a time, so memory is linear in the number of neurons.
+    /// Use this for models with many neurons.
+    Chunked { chunk_size: usize },
+}
+
+/// Normalizes every row so that dot products between rows are the requested similarity.
+pub(super) fn normalize_rows(vectors: ArrayView2<f32>, metric: SimilarityMetric) -> Array2<f32> {
+    let mut vectors = vectors.to_owned();
+    for mut row in vectors.rows_mut() {
+        if metric == SimilarityMetric::Correlation {
+            let mean = row.mean().unwrap_or(0.);
+            row -= mean;
+        }
+        let norm = row.dot(&row).sqrt();
+        if norm > 0. {
+            row /= norm;
+        }
+    }
+    vectors
+}
+
+/// Finds the `k` most similar other rows for every row of `vectors`, which has one row per neuron.
+///
+/// Returns the indices of the neighbours and their similarities, both with shape `[num_rows, k]`
+/// and sorted by decreasing similarity.
+pub fn nearest_neighbours(
+    vectors: ArrayView2<f32>,
+    k: usize,
+    metric: SimilarityMetric,
+    mode: SimilarityMode,
+) -> (Array2<usize>, Array2<f32>) {
+    let num_rows = vectors.nrows();
+    assert!(
+        k < num_rows,
+        "Cannot find {k} neighbours among {num_rows} neurons."
+    );
+    let vectors = normalize_rows(vectors, metric);
+    let chunk_size = match mode {
+        SimilarityMode::BruteForce => num_rows,
+        SimilarityMode::Chunked { chunk_size } => {
+            assert!(chunk_size > 0, "Chunk size must be positive.");
+            chunk_size
+        }
+    };
+
+    let mut neighbours = Array2::zeros((num_rows, k));
+    let mut scores = Array2::zeros((num_rows, k));
+    for chunk_start in (0..num_rows).step_by(chunk_size) {
+        let chunk_end = (chunk_start + chunk_size).min(num_rows);
+        let chunk = vectors.slice(s![chunk_start..chunk_end, ..]);
+        let similarities = chunk.dot(&vectors.t());
+
+        for (chunk_row, row_similarities) in similarities.axis_iter(Axis(0)).enumerate() {
+            let row_index = chunk_start + chunk_row;
+            let mut candidates: Vec<_> = row_similarities
+                .iter()
+                .copied()
+                .enumerate()
+                .filter(|&(index, _)| index != row_index)
+                .collect();
+            let compare =
+                |(_, a): &(usize, f32), (_, b): &(usize, f32)| -> Ordering { b.total_cmp(a) };
+            if k < candidates.len() {
+                candidates.select_nth_unstable_by(k, compare);
+                candidates.truncate(k);
+            }
+            candidates.sort_unstable_by(compare);
+
+            for (neighbour_rank, (neighbour_index, score)) in candidates.into_iter().enumerate() {
+                neighbours[(row_index, neighbour_rank)] = neighbour_index;
+                scores[(row_index, neighbour_rank)] = score;
+            }
+        }
+    }
+    (neighbours, scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{arr2, aview1, s, Array2};
+
+    use super::{nearest_neighbours, SimilarityMetric, SimilarityMode};
+
+    fn assert_close(actual: &Array2<f32>, expected: &Array2<f32>) {
+        assert_eq!(actual.dim(), expected.dim());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+        }
+    }
+
+    #[test]
+    fn nearest_neighbours_are_the_most_similar_other_rows() {
+        let vectors = arr2(&[[1.0, 0.0], [2.0, 1.0], [0.0, 1.0]]);
+        let (neighbours, scores) = nearest_neighbours(
+            vectors.view(),
+            2,
+            SimilarityMetric::Cosine,
+            SimilarityMode::BruteForce,
+        );
+        assert_eq!(neighbours, arr2(&[[1, 2], [0, 2], [1, 0]]));
+        let (near, far) = (2.0 / 5f32.sqrt(), 1.0 / 5f32.sqrt());
+        let expected = arr2(&[[near, 0.0], [near, far], [far, 0.0]]);
+        assert_close(&scores, &expected);
+    }
+
+    #[test]
+    fn correlation_ignores_offsets() {
+        let vectors = arr2(&[[1.0, 2.0, 3.0], [11.0, 12.0, 13.0], [3.0, 1.0, 3.0]]);
+        let (neighbours, scores) = nearest_neighbours(
+            vectors.view(),
+            1,
+            SimilarityMetric::Correlation,
+            SimilarityMode::BruteForce,
+        );
+        assert_eq!(neighbours.column(0).slice(s![..2]), aview1(&[1, 0]));
+        assert_close(&scores, &arr2(&[[1.0], [1.0], [0.0]]));
+    }
+
+    #[test]
+    fn chunked_neighbours_match_brute_force() {
+        let vectors = Array2::from_shape_fn((7, 5), |(row, column)| {
+            ((row * 5 + column * 3) as f32 * 0.7).sin()
+        });
+        for metric in [SimilarityMetric::Cosine, SimilarityMetric::Correlation] {
+            let (neighbours, scores) =
+                nearest_neighbours(vectors.view(), 3, metric, SimilarityMode::BruteForce);
+            for chunk_size in [1, 3, 7, 10] {
+                let (chunked_neighbours, chunked_scores) = nearest_neighbours(
+                    vectors.view(),
+                    3,
+                    metric,
+                    SimilarityMode::Chunked { chunk_size },
+                );
+                assert_eq!(chunked_neighbours, neighbours);
+                assert_close(&chunked_scores, &scores);
+            }
+        }
+    }
+}
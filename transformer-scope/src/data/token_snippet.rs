@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+/// A window of tokens with the activation of a neuron on each token.
+/// Used for max activating examples in language models.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenSnippet {
+    tokens: Vec<String>,
+    activations: Vec<f32>,
+    /// Index of the token the snippet was selected for, e.g. the token a max activating example was ranked on.
+    focus: usize,
+}
+
+impl TokenSnippet {
+    pub fn new(tokens: Vec<String>, activations: Vec<f32>, focus: usize) -> Self {
+        assert_eq!(
+            tokens.len(),
+            activations.len(),
+            "A token snippet must have exactly one activation per token."
+        );
+        assert!(
+            focus < tokens.len(),
+            "The focus of a token snippet must be one of its tokens."
+        );
+        Self {
+            tokens,
+            activations,
+            focus,
+        }
+    }
+
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    pub fn activations(&self) -> &[f32] {
+        &self.activations
+    }
+
+    /// Index of the token the snippet was selected for.
+    pub fn focus(&self) -> usize {
+        self.focus
+    }
+
+    /// The activation on the token the snippet was selected for.
+    pub fn focus_activation(&self) -> f32 {
+        self.activations[self.focus]
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// The largest activation in the snippet, or negative infinity if the snippet is empty.
+    pub fn max_activation(&self) -> f32 {
+        self.activations
+            .iter()
+            .copied()
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
+}
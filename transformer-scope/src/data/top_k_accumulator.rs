@@ -0,0 +1,561 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use ndarray::{s, ArrayView2, ArrayView4, Axis};
+use thiserror::Error;
+
+use super::{PayloadBuilder, TokenSnippet};
+
+#[derive(Clone, Debug, Error)]
+pub enum TopKAccumulatorError {
+    #[error("The activations have {found_layers} layers and {found_neurons} neurons, but the accumulator has {num_layers} layers and {num_neurons} neurons.")]
+    ActivationShape {
+        num_layers: usize,
+        num_neurons: usize,
+        found_layers: usize,
+        found_neurons: usize,
+    },
+    #[error("The tokens have shape {found:?}, but must have shape {expected:?} matching the batch size and sequence length of the activations.")]
+    TokenShape {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+    #[error("No vocabulary given and the payload builder has no vocabulary.")]
+    NoVocabulary,
+    #[error("Token id {token} is not in the vocabulary of {vocabulary_size} tokens.")]
+    TokenNotInVocabulary { token: u32, vocabulary_size: usize },
+    #[error("Quantile examples were not collected by this accumulator.")]
+    NoQuantiles,
+}
+
+/// A single activation together with the window of tokens around it.
+#[derive(Debug, Clone)]
+struct Example {
+    activation: f32,
+    tokens: Vec<u32>,
+    activations: Vec<f32>,
+    /// Index of the activating token within `tokens`.
+    focus: usize,
+}
+
+impl Example {
+    fn to_token_snippet(
+        &self,
+        vocabulary: &[String],
+    ) -> Result<TokenSnippet, TopKAccumulatorError> {
+        let tokens = self
+            .tokens
+            .iter()
+            .map(|&token| {
+                vocabulary.get(token as usize).cloned().ok_or(
+                    TopKAccumulatorError::TokenNotInVocabulary {
+                        token,
+                        vocabulary_size: vocabulary.len(),
+                    },
+                )
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(TokenSnippet::new(
+            tokens,
+            self.activations.clone(),
+            self.focus,
+        ))
+    }
+}
+
+impl PartialEq for Example {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Example {}
+
+impl PartialOrd for Example {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Example {
+    /// Reversed so that a `BinaryHeap` of examples is a min-heap with the smallest activation on top.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.activation.total_cmp(&self.activation)
+    }
+}
+
+/// Small xorshift generator so sampling is deterministic and needs no extra dependencies.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    /// Uniform sample in the open interval (0, 1).
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        ((self.0 >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+}
+
+/// Reservoir sampler using Algorithm L, which skips ahead instead of drawing a random number for every activation.
+#[derive(Debug, Clone)]
+struct Reservoir {
+    samples: Vec<Example>,
+    capacity: usize,
+    num_seen: u64,
+    next_index: u64,
+    w: f64,
+}
+
+impl Reservoir {
+    fn new(capacity: usize, rng: &mut Rng) -> Self {
+        let mut reservoir = Self {
+            samples: Vec::with_capacity(capacity),
+            capacity,
+            num_seen: 0,
+            next_index: 0,
+            w: (rng.next_f64().ln() / capacity.max(1) as f64).exp(),
+        };
+        reservoir.next_index = (capacity as u64).saturating_sub(1);
+        reservoir.skip(rng);
+        reservoir
+    }
+
+    fn skip(&mut self, rng: &mut Rng) {
+        self.next_index += (rng.next_f64().ln() / (1. - self.w).ln()).floor() as u64 + 1;
+    }
+
+    fn wants(&self, index: u64) -> bool {
+        self.capacity > 0 && (self.samples.len() < self.capacity || index == self.next_index)
+    }
+
+    fn insert(&mut self, example: Example, rng: &mut Rng) {
+        if self.samples.len() < self.capacity {
+            self.samples.push(example);
+        } else {
+            let replace_index = (rng.next_f64() * self.capacity as f64) as usize;
+            self.samples[replace_index.min(self.capacity - 1)] = example;
+            self.w *= (rng.next_f64().ln() / self.capacity as f64).exp();
+            self.skip(rng);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct NeuronExamples {
+    top: BinaryHeap<Example>,
+    reservoir: Reservoir,
+}
+
+/// Accumulates max activating examples for every neuron from a stream of activation batches,
+/// so the full activations never have to be kept in memory.
+///
+/// For every neuron, the `k` examples with the largest activations are kept.
+/// Optionally, a uniform sample of all activations is kept as well, which is split into quantiles when the examples are emitted.
+#[derive(Debug, Clone)]
+pub struct TopKAccumulator {
+    num_layers: usize,
+    num_neurons: usize,
+    k: usize,
+    num_quantiles: usize,
+    examples_per_quantile: usize,
+    context_before: usize,
+    context_after: usize,
+
+    neurons: Vec<NeuronExamples>,
+    rng: Rng,
+}
+
+impl TopKAccumulator {
+    pub fn new(
+        num_layers: usize,
+        num_neurons: usize,
+        k: usize,
+        num_quantiles: usize,
+        examples_per_quantile: usize,
+        context_before: usize,
+        context_after: usize,
+    ) -> Self {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        let neurons = (0..num_layers * num_neurons)
+            .map(|_| NeuronExamples {
+                top: BinaryHeap::with_capacity(k + 1),
+                reservoir: Reservoir::new(num_quantiles * examples_per_quantile, &mut rng),
+            })
+            .collect();
+        Self {
+            num_layers,
+            num_neurons,
+            k,
+            num_quantiles,
+            examples_per_quantile,
+            context_before,
+            context_after,
+            neurons,
+            rng,
+        }
+    }
+
+    fn example(
+        &self,
+        activations: ArrayView2<f32>,
+        tokens: ArrayView2<u32>,
+        sequence_index: usize,
+        position: usize,
+    ) -> Example {
+        let start = position.saturating_sub(self.context_before);
+        let end = (position + self.context_after + 1).min(tokens.ncols());
+        let activation_row = activations.row(sequence_index);
+        Example {
+            activation: activation_row[position],
+            tokens: tokens.slice(s![sequence_index, start..end]).to_vec(),
+            activations: activation_row.slice(s![start..end]).to_vec(),
+            focus: position - start,
+        }
+    }
+
+    /// Adds a batch of activations with shape `[num_layers, num_neurons, batch_size, sequence_length]`
+    /// and the tokens they were computed on with shape `[batch_size, sequence_length]`.
+    pub fn add_batch(
+        &mut self,
+        activations: ArrayView4<f32>,
+        tokens: ArrayView2<u32>,
+    ) -> Result<(), TopKAccumulatorError> {
+        let (num_layers, num_neurons, batch_size, sequence_length) = activations.dim();
+        if (num_layers, num_neurons) != (self.num_layers, self.num_neurons) {
+            return Err(TopKAccumulatorError::ActivationShape {
+                num_layers: self.num_layers,
+                num_neurons: self.num_neurons,
+                found_layers: num_layers,
+                found_neurons: num_neurons,
+            });
+        }
+        if tokens.dim() != (batch_size, sequence_length) {
+            return Err(TopKAccumulatorError::TokenShape {
+                expected: (batch_size, sequence_length),
+                found: tokens.dim(),
+            });
+        }
+
+        let mut neurons = std::mem::take(&mut self.neurons);
+        for (layer_index, layer_activations) in activations.axis_iter(Axis(0)).enumerate() {
+            for (neuron_index, neuron_activations) in
+                layer_activations.axis_iter(Axis(0)).enumerate()
+            {
+                let neuron = &mut neurons[layer_index * self.num_neurons + neuron_index];
+                for ((sequence_index, position), &activation) in neuron_activations.indexed_iter() {
+                    let is_top = neuron.top.len() < self.k
+                        || neuron
+                            .top
+                            .peek()
+                            .is_some_and(|min| activation > min.activation);
+                    let index = neuron.reservoir.num_seen;
+                    neuron.reservoir.num_seen += 1;
+                    let is_sampled = neuron.reservoir.wants(index);
+                    if !is_top && !is_sampled {
+                        continue;
+                    }
+
+                    let example =
+                        self.example(neuron_activations, tokens, sequence_index, position);
+                    if is_sampled {
+                        neuron.reservoir.insert(example.clone(), &mut self.rng);
+                    }
+                    if is_top {
+                        neuron.top.push(example);
+                        if neuron.top.len() > self.k {
+                            neuron.top.pop();
+                        }
+                    }
+                }
+            }
+        }
+        self.neurons = neurons;
+        Ok(())
+    }
+
+    fn top_k_snippets(
+        &self,
+        vocabulary: &[String],
+    ) -> Result<Vec<Vec<Vec<TokenSnippet>>>, TopKAccumulatorError> {
+        self.neurons
+            .chunks(self.num_neurons)
+            .map(|layer| {
+                layer
+                    .iter()
+                    .map(|neuron| {
+                        // Sorting the reversed order gives the largest activation first.
+                        neuron
+                            .top
+                            .clone()
+                            .into_sorted_vec()
+                            .iter()
+                            .map(|example| example.to_token_snippet(vocabulary))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn quantile_snippets(
+        &self,
+        vocabulary: &[String],
+    ) -> Result<Vec<Vec<Vec<TokenSnippet>>>, TopKAccumulatorError> {
+        self.neurons
+            .chunks(self.num_neurons)
+            .map(|layer| {
+                layer
+                    .iter()
+                    .map(|neuron| {
+                        let mut samples = neuron.reservoir.samples.clone();
+                        samples.sort();
+                        let quantile_size = samples.len().div_ceil(self.num_quantiles.max(1));
+                        samples
+                            .chunks(quantile_size.max(1))
+                            .flat_map(|quantile| quantile.iter().take(self.examples_per_quantile))
+                            .map(|example| example.to_token_snippet(vocabulary))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Adds the accumulated examples to the payload builder as token snippet values.
+    /// The top `k` examples are added under `top_k_key`, sorted by decreasing activation.
+    /// Token ids are converted to text using `vocabulary`, or the vocabulary of the payload builder if it is `None`.
+    /// If `quantiles_key` is given, the sampled examples are added under it, ordered from the highest quantile to the lowest.
+    /// Nothing is added if an error is returned.
+    pub fn add_to_payload_builder(
+        &self,
+        payload_builder: &mut PayloadBuilder,
+        top_k_key: impl Into<String>,
+        vocabulary: Option<&[String]>,
+        quantiles_key: Option<String>,
+    ) -> Result<(), TopKAccumulatorError> {
+        let vocabulary = vocabulary
+            .or_else(|| {
+                payload_builder
+                    .get_vocabulary()
+                    .map(|vocabulary| vocabulary.tokens())
+            })
+            .ok_or(TopKAccumulatorError::NoVocabulary)?;
+        let top_k_snippets = self.top_k_snippets(vocabulary)?;
+        let quantiles = match quantiles_key {
+            Some(quantiles_key) => {
+                if self.num_quantiles == 0 || self.examples_per_quantile == 0 {
+                    return Err(TopKAccumulatorError::NoQuantiles);
+                }
+                Some((quantiles_key, self.quantile_snippets(vocabulary)?))
+            }
+            None => None,
+        };
+        payload_builder.add_token_snippets(top_k_key, top_k_snippets);
+        if let Some((quantiles_key, quantile_snippets)) = quantiles {
+            payload_builder.add_token_snippets(quantiles_key, quantile_snippets);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Array2, Array4};
+
+    use crate::data::PayloadBuilder;
+
+    use super::{Rng, TopKAccumulator, TopKAccumulatorError};
+
+    /// Streams the activations of a single neuron in batches of sequences of the given length.
+    fn accumulate(
+        accumulator: &mut TopKAccumulator,
+        activations: &[f32],
+        sequence_length: usize,
+        batch_size: usize,
+    ) {
+        for batch in activations.chunks(batch_size * sequence_length) {
+            let num_sequences = batch.len() / sequence_length;
+            let batch =
+                Array4::from_shape_vec((1, 1, num_sequences, sequence_length), batch.to_vec())
+                    .unwrap();
+            let tokens =
+                Array2::from_shape_fn((num_sequences, sequence_length), |(_, position)| {
+                    position as u32
+                });
+            accumulator.add_batch(batch.view(), tokens.view()).unwrap();
+        }
+    }
+
+    fn vocabulary(size: usize) -> Vec<String> {
+        (0..size).map(|token| format!("t{token}")).collect()
+    }
+
+    #[test]
+    fn the_largest_activations_are_kept() {
+        let mut rng = Rng(42);
+        let activations: Vec<f32> = (0..6000).map(|_| rng.next_f64() as f32 - 0.5).collect();
+        let mut accumulator = TopKAccumulator::new(1, 1, 25, 0, 0, 2, 1);
+        accumulate(&mut accumulator, &activations, 100, 7);
+
+        let mut expected = activations.clone();
+        expected.sort_by(|a, b| b.total_cmp(a));
+        expected.truncate(25);
+        let snippets = &accumulator.top_k_snippets(&vocabulary(100)).unwrap()[0][0];
+        let kept: Vec<f32> = snippets
+            .iter()
+            .map(|snippet| snippet.focus_activation())
+            .collect();
+        assert_eq!(kept, expected);
+        // Every snippet holds the context around its activation within the sequence.
+        for snippet in snippets {
+            assert!((2..=4).contains(&snippet.len()));
+            assert!(snippet.focus() <= 2 && snippet.len() - snippet.focus() <= 2);
+        }
+    }
+
+    #[test]
+    fn snippets_are_ranked_on_their_focus_token() {
+        // The second largest activation is next to the largest, so its window also contains the largest.
+        let activations = [0., 5., 9., 1., 2., 3.];
+        let mut accumulator = TopKAccumulator::new(1, 1, 2, 0, 0, 1, 1);
+        accumulate(&mut accumulator, &activations, 6, 1);
+
+        let snippets = &accumulator.top_k_snippets(&vocabulary(6)).unwrap()[0][0];
+        let focus_activations: Vec<f32> = snippets
+            .iter()
+            .map(|snippet| snippet.focus_activation())
+            .collect();
+        assert_eq!(focus_activations, [9., 5.]);
+        assert_eq!(snippets[0].tokens(), ["t1", "t2", "t3"]);
+        assert_eq!(snippets[0].focus(), 1);
+        assert_eq!(snippets[1].tokens(), ["t0", "t1", "t2"]);
+        assert_eq!(snippets[1].focus(), 1);
+        assert_eq!(snippets[1].max_activation(), 9.);
+    }
+
+    #[test]
+    fn all_activations_are_sampled_if_they_fit() {
+        let activations: Vec<f32> = (0..60).map(|index| index as f32).collect();
+        let mut accumulator = TopKAccumulator::new(1, 1, 1, 4, 25, 0, 0);
+        accumulate(&mut accumulator, &activations, 10, 2);
+        let mut samples: Vec<f32> = accumulator.neurons[0]
+            .reservoir
+            .samples
+            .iter()
+            .map(|example| example.activation)
+            .collect();
+        samples.sort_by(f32::total_cmp);
+        assert_eq!(samples, activations);
+    }
+
+    #[test]
+    fn samples_are_uniform_over_the_stream() {
+        // Increasing activations, so a sample favouring early or late activations has the wrong quantiles.
+        let num_activations = 100_000;
+        let activations: Vec<f32> = (0..num_activations)
+            .map(|index| index as f32 / num_activations as f32)
+            .collect();
+        let mut accumulator = TopKAccumulator::new(1, 1, 1, 4, 250, 0, 0);
+        accumulate(&mut accumulator, &activations, 1000, 10);
+
+        let samples = &accumulator.neurons[0].reservoir.samples;
+        assert_eq!(samples.len(), 1000);
+        let mut sampled: Vec<f32> = samples.iter().map(|example| example.activation).collect();
+        sampled.sort_by(f32::total_cmp);
+        sampled.dedup();
+        assert_eq!(sampled.len(), 1000, "Activations are sampled at most once.");
+
+        // Quantiles are emitted from the highest to the lowest, each starting with its largest activation.
+        let snippets = &accumulator.quantile_snippets(&vocabulary(1000)).unwrap()[0][0];
+        assert_eq!(snippets.len(), 1000);
+        for (quantile, examples) in snippets.chunks(250).enumerate() {
+            let upper_bound = 1. - quantile as f32 / 4.;
+            let lower_bound = upper_bound - 0.25;
+            for example in examples {
+                assert!(example.focus_activation() <= examples[0].focus_activation());
+            }
+            assert!((examples[0].focus_activation() - upper_bound).abs() < 0.03);
+            assert!((examples[249].focus_activation() - lower_bound).abs() < 0.03);
+        }
+    }
+
+    #[test]
+    fn activations_must_match_the_layers_and_neurons() {
+        let mut accumulator = TopKAccumulator::new(2, 3, 1, 0, 0, 0, 0);
+        let activations = Array4::<f32>::zeros((2, 4, 1, 5));
+        let tokens = Array2::<u32>::zeros((1, 5));
+        assert!(matches!(
+            accumulator.add_batch(activations.view(), tokens.view()),
+            Err(TopKAccumulatorError::ActivationShape {
+                num_layers: 2,
+                num_neurons: 3,
+                found_layers: 2,
+                found_neurons: 4,
+            })
+        ));
+    }
+
+    #[test]
+    fn tokens_must_match_the_activations() {
+        let mut accumulator = TopKAccumulator::new(2, 3, 1, 0, 0, 0, 0);
+        let activations = Array4::<f32>::zeros((2, 3, 1, 5));
+        let tokens = Array2::<u32>::zeros((1, 4));
+        assert!(matches!(
+            accumulator.add_batch(activations.view(), tokens.view()),
+            Err(TopKAccumulatorError::TokenShape {
+                expected: (1, 5),
+                found: (1, 4),
+            })
+        ));
+    }
+
+    #[test]
+    fn a_vocabulary_is_needed() {
+        let mut accumulator = TopKAccumulator::new(1, 1, 1, 0, 0, 0, 0);
+        accumulate(&mut accumulator, &[1., 2.], 2, 1);
+        let mut payload_builder = PayloadBuilder::new(1, 1);
+        assert!(matches!(
+            accumulator.add_to_payload_builder(&mut payload_builder, "top_k", None, None),
+            Err(TopKAccumulatorError::NoVocabulary)
+        ));
+        assert!(!payload_builder.contains_key("top_k"));
+    }
+
+    #[test]
+    fn tokens_must_be_in_the_vocabulary() {
+        let mut accumulator = TopKAccumulator::new(1, 1, 1, 0, 0, 0, 0);
+        accumulate(&mut accumulator, &[1., 2., 3.], 3, 1);
+        let mut payload_builder = PayloadBuilder::new(1, 1);
+        assert!(matches!(
+            accumulator.add_to_payload_builder(
+                &mut payload_builder,
+                "top_k",
+                Some(&vocabulary(2)),
+                None
+            ),
+            Err(TopKAccumulatorError::TokenNotInVocabulary {
+                token: 2,
+                vocabulary_size: 2,
+            })
+        ));
+        assert!(!payload_builder.contains_key("top_k"));
+    }
+
+    #[test]
+    fn quantiles_must_be_collected() {
+        let mut accumulator = TopKAccumulator::new(1, 1, 1, 0, 0, 0, 0);
+        accumulate(&mut accumulator, &[1., 2.], 2, 1);
+        let mut payload_builder = PayloadBuilder::new(1, 1);
+        assert!(matches!(
+            accumulator.add_to_payload_builder(
+                &mut payload_builder,
+                "top_k",
+                Some(&vocabulary(2)),
+                Some("quantiles".to_string())
+            ),
+            Err(TopKAccumulatorError::NoQuantiles)
+        ));
+        assert!(!payload_builder.contains_key("top_k"));
+    }
+}
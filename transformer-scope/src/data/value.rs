@@ -1,20 +1,26 @@
 use std::fmt::Display;
 
 use delegate::delegate;
-use ndarray::{Array, ArrayD, ArrayViewD, Dimension};
+use ndarray::{Array, ArrayD, ArrayViewD, Axis, CowArray, Dimension, IxDyn};
 use serde::{Deserialize, Serialize};
 
 use private::ValueArray;
 
+use super::{quantization::QuantizedArray, Quantization, QuantizationError, TokenSnippet};
+
 mod private {
     use ndarray::ArrayD;
     use serde::{Deserialize, Serialize};
 
+    use crate::data::{quantization::QuantizedArray, TokenSnippet};
+
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub enum ValueArray {
         String(ArrayD<String>),
         U32(ArrayD<u32>),
         F32(ArrayD<f32>),
+        QuantizedF32(Box<QuantizedArray>),
+        TokenSnippets(ArrayD<Vec<TokenSnippet>>),
     }
 
     pub trait Data: Sized {
@@ -38,6 +44,12 @@ mod private {
             ValueArray::F32(array)
         }
     }
+
+    impl Data for Vec<TokenSnippet> {
+        fn to_value_array(array: ArrayD<Self>) -> ValueArray {
+            ValueArray::TokenSnippets(array)
+        }
+    }
 }
 
 pub trait Data: private::Data {}
@@ -49,6 +61,16 @@ pub enum DataType {
     String,
     U32,
     F32,
+    TokenSnippets,
+}
+
+impl DataType {
+    pub const ALL: [DataType; 4] = [
+        DataType::String,
+        DataType::U32,
+        DataType::F32,
+        DataType::TokenSnippets,
+    ];
 }
 
 impl Display for DataType {
@@ -64,6 +86,32 @@ pub enum Scope {
     Neuron,
 }
 
+impl Scope {
+    /// The number of leading axes used to index into the scope, e.g. 2 for layer and neuron.
+    pub fn num_axes(self) -> usize {
+        match self {
+            Scope::Global => 0,
+            Scope::Layer => 1,
+            Scope::Neuron => 2,
+        }
+    }
+
+    /// Whether values of this scope can be shown on pages of the given scope, e.g. `Global` values on every page but
+    /// `Neuron` values only on neuron pages.
+    pub fn is_within(self, page_scope: Scope) -> bool {
+        self.num_axes() <= page_scope.num_axes()
+    }
+
+    /// The indices of the scope axes for the given neuron.
+    pub fn index(self, layer_index: usize, neuron_index: usize) -> Vec<usize> {
+        match self {
+            Scope::Global => vec![],
+            Scope::Layer => vec![layer_index],
+            Scope::Neuron => vec![layer_index, neuron_index],
+        }
+    }
+}
+
 impl Display for Scope {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self)
@@ -74,12 +122,57 @@ pub enum ValueView<'a> {
     String(ArrayViewD<'a, String>),
     U32(ArrayViewD<'a, u32>),
     F32(ArrayViewD<'a, f32>),
+    /// An `F32` value stored quantized, which must be dequantized to read its numbers.
+    QuantizedF32(&'a QuantizedArray),
+    TokenSnippets(ArrayViewD<'a, Vec<TokenSnippet>>),
+}
+
+/// The range of the finite numbers of a value, computed when the value is created so that e.g. heatmaps of every
+/// neuron share the same colours.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct NumberStatistics {
+    pub min: f32,
+    pub max: f32,
+    /// The smallest number greater than zero, if there is one, which bounds logarithmic scales.
+    pub min_positive: Option<f32>,
+}
+
+impl NumberStatistics {
+    /// The statistics of the finite numbers, or `None` if there are none.
+    pub fn of<'a>(numbers: impl IntoIterator<Item = &'a f32>) -> Option<Self> {
+        let mut statistics: Option<Self> = None;
+        for &number in numbers.into_iter().filter(|number| number.is_finite()) {
+            let min_positive = (number > 0.).then_some(number);
+            statistics = Some(match statistics {
+                None => Self {
+                    min: number,
+                    max: number,
+                    min_positive,
+                },
+                Some(statistics) => Self {
+                    min: statistics.min.min(number),
+                    max: statistics.max.max(number),
+                    min_positive: match (statistics.min_positive, min_positive) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    },
+                },
+            });
+        }
+        statistics
+    }
+
+    /// The largest absolute number.
+    pub fn max_abs(&self) -> f32 {
+        self.min.abs().max(self.max.abs())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Value {
     array: ValueArray,
     scope: Scope,
+    statistics: Option<NumberStatistics>,
 }
 
 impl Value {
@@ -89,7 +182,50 @@ impl Value {
         D: Dimension,
     {
         let array = A::to_value_array(array.into_dyn());
-        Self { array, scope }
+        let statistics = match array {
+            ValueArray::U32(ref array) => {
+                let numbers: Vec<f32> = array.iter().map(|&number| number as f32).collect();
+                NumberStatistics::of(&numbers)
+            }
+            ValueArray::F32(ref array) => NumberStatistics::of(array),
+            ValueArray::TokenSnippets(ref array) => NumberStatistics::of(
+                array
+                    .iter()
+                    .flatten()
+                    .flat_map(|snippet| snippet.activations()),
+            ),
+            ValueArray::String(_) | ValueArray::QuantizedF32(_) => None,
+        };
+        Self {
+            array,
+            scope,
+            statistics,
+        }
+    }
+
+    /// Creates an `F32` value stored with fewer bits per element.
+    /// Every group of elements that shares scope indices, e.g. every neuron for `Neuron` scoped values, gets its own scale and offset.
+    /// NaN and infinite elements are stored as NaN.
+    pub fn quantized<D>(array: Array<f32, D>, scope: Scope, quantization: Quantization) -> Self
+    where
+        D: Dimension,
+    {
+        let group_axis_num = scope.num_axes();
+        assert!(
+            array.ndim() >= group_axis_num,
+            "A value with scope `{scope}` must have at least {group_axis_num} axes."
+        );
+        let statistics = NumberStatistics::of(&array);
+        let array = ValueArray::QuantizedF32(Box::new(QuantizedArray::quantize(
+            array.view().into_dyn(),
+            group_axis_num,
+            quantization,
+        )));
+        Self {
+            array,
+            scope,
+            statistics,
+        }
     }
 
     pub fn data_type(&self) -> DataType {
@@ -97,6 +233,8 @@ impl Value {
             ValueArray::String(_) => DataType::String,
             ValueArray::U32(_) => DataType::U32,
             ValueArray::F32(_) => DataType::F32,
+            ValueArray::QuantizedF32(_) => DataType::F32,
+            ValueArray::TokenSnippets(_) => DataType::TokenSnippets,
         }
     }
 
@@ -104,11 +242,19 @@ impl Value {
         self.scope
     }
 
-    pub fn view(&self) -> ValueView {
+    /// The range of the numbers of a `U32` or `F32` value, or of the activations of a `TokenSnippets` value.
+    /// Computed before quantization and kept by [`Value::select`], so subsets of a payload show the same colours.
+    pub fn statistics(&self) -> Option<NumberStatistics> {
+        self.statistics
+    }
+
+    pub fn view(&self) -> ValueView<'_> {
         match self.array {
             ValueArray::String(ref array) => ValueView::String(array.view()),
             ValueArray::U32(ref array) => ValueView::U32(array.view()),
             ValueArray::F32(ref array) => ValueView::F32(array.view()),
+            ValueArray::QuantizedF32(ref array) => ValueView::QuantizedF32(array),
+            ValueArray::TokenSnippets(ref array) => ValueView::TokenSnippets(array.view()),
         }
     }
 
@@ -117,11 +263,73 @@ impl Value {
             ValueArray::String(ref array) => array,
             ValueArray::U32(ref array) => array,
             ValueArray::F32(ref array) => array,
+            ValueArray::QuantizedF32(ref array) => array,
+            ValueArray::TokenSnippets(ref array) => array,
         } {
             pub fn shape(&self) -> &[usize];
         }
     }
 
+    /// Creates a new value containing only the given layers and neurons.
+    /// Only the axes covered by the scope are sliced, so global values are returned unchanged.
+    pub fn select(&self, layer_indices: &[usize], neuron_indices: &[usize]) -> Self {
+        fn select_array<A: Clone>(
+            array: &ArrayD<A>,
+            scope: Scope,
+            layer_indices: &[usize],
+            neuron_indices: &[usize],
+        ) -> ArrayD<A> {
+            match scope {
+                Scope::Global => array.clone(),
+                Scope::Layer => array.select(Axis(0), layer_indices),
+                Scope::Neuron => array
+                    .select(Axis(0), layer_indices)
+                    .select(Axis(1), neuron_indices),
+            }
+        }
+
+        let array = match self.array {
+            ValueArray::String(ref array) => ValueArray::String(select_array(
+                array,
+                self.scope,
+                layer_indices,
+                neuron_indices,
+            )),
+            ValueArray::U32(ref array) => ValueArray::U32(select_array(
+                array,
+                self.scope,
+                layer_indices,
+                neuron_indices,
+            )),
+            ValueArray::F32(ref array) => ValueArray::F32(select_array(
+                array,
+                self.scope,
+                layer_indices,
+                neuron_indices,
+            )),
+            ValueArray::QuantizedF32(ref array) => {
+                ValueArray::QuantizedF32(Box::new(match self.scope {
+                    Scope::Global => array.as_ref().clone(),
+                    Scope::Layer => array.select(Axis(0), layer_indices),
+                    Scope::Neuron => array
+                        .select(Axis(0), layer_indices)
+                        .select(Axis(1), neuron_indices),
+                }))
+            }
+            ValueArray::TokenSnippets(ref array) => ValueArray::TokenSnippets(select_array(
+                array,
+                self.scope,
+                layer_indices,
+                neuron_indices,
+            )),
+        };
+        Self {
+            array,
+            scope: self.scope,
+            statistics: self.statistics,
+        }
+    }
+
     pub fn as_string(&self) -> Option<&ArrayD<String>> {
         match self.array {
             ValueArray::String(ref array) => Some(array),
@@ -136,10 +344,138 @@ impl Value {
         }
     }
 
-    pub fn as_f32(&self) -> Option<&ArrayD<f32>> {
+    /// The array of an `F32` value, or `None` if it is quantized. See [`Value::dequantize`].
+    pub fn as_f32(&self) -> Option<ArrayViewD<'_, f32>> {
+        match self.array {
+            ValueArray::F32(ref array) => Some(array.view()),
+            _ => None,
+        }
+    }
+
+    /// Dequantizes the whole array of a quantized `F32` value, or returns `None` if it is not quantized.
+    /// This allocates an `f32` array of the full size, so prefer [`Value::neuron_f32`] for the part of a neuron.
+    pub fn dequantize(&self) -> Option<ArrayD<f32>> {
+        match self.array {
+            ValueArray::QuantizedF32(ref array) => Some(array.dequantize()),
+            _ => None,
+        }
+    }
+
+    /// The part of an `F32` value belonging to the given neuron, i.e. with the scope axes indexed away.
+    /// `Global` values are returned whole and `Layer` values are only indexed by the layer.
+    /// Only this part is dequantized for quantized values.
+    pub fn neuron_f32(
+        &self,
+        layer_index: usize,
+        neuron_index: usize,
+    ) -> Option<CowArray<'_, f32, IxDyn>> {
+        let scope_index = self.scope.index(layer_index, neuron_index);
+        match self.array {
+            ValueArray::F32(ref array) => {
+                let mut array = array.view();
+                for &index in scope_index.iter() {
+                    array.index_axis_inplace(Axis(0), index);
+                }
+                Some(array.into())
+            }
+            ValueArray::QuantizedF32(ref array) => {
+                Some(array.dequantize_group(&scope_index).into())
+            }
+            _ => None,
+        }
+    }
+
+    /// The numbers of a `U32` or `F32` value with its first axes indexed by `index`, which may index fewer axes than
+    /// the scope, e.g. only the layer of a `Neuron` scoped value. Only this part is dequantized for quantized values.
+    pub fn numbers_f32(&self, index: &[usize]) -> Option<ArrayD<f32>> {
+        assert!(index.len() <= self.scope.num_axes());
+        fn index_axes<'a, A>(mut array: ArrayViewD<'a, A>, index: &[usize]) -> ArrayViewD<'a, A> {
+            for &index in index {
+                array.index_axis_inplace(Axis(0), index);
+            }
+            array
+        }
+        match self.array {
+            ValueArray::U32(ref array) => {
+                Some(index_axes(array.view(), index).mapv(|number| number as f32))
+            }
+            ValueArray::F32(ref array) => Some(index_axes(array.view(), index).to_owned()),
+            ValueArray::QuantizedF32(ref array) => Some(array.dequantize_group(index)),
+            _ => None,
+        }
+    }
+
+    /// The quantization used to store the value, if any.
+    pub fn quantization(&self) -> Option<Quantization> {
         match self.array {
-            ValueArray::F32(ref array) => Some(array),
+            ValueArray::QuantizedF32(ref array) => Some(array.quantization()),
             _ => None,
         }
     }
+
+    /// The error introduced by quantizing the value, if it is quantized.
+    pub fn quantization_error(&self) -> Option<QuantizationError> {
+        match self.array {
+            ValueArray::QuantizedF32(ref array) => Some(array.error()),
+            _ => None,
+        }
+    }
+
+    pub fn as_token_snippets(&self) -> Option<&ArrayD<Vec<TokenSnippet>>> {
+        match self.array {
+            ValueArray::TokenSnippets(ref array) => Some(array),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Array3, Axis};
+
+    use super::{Scope, Value};
+    use crate::Quantization;
+
+    #[test]
+    fn quantized_values_are_only_dequantized_explicitly() {
+        let array = Array3::from_shape_fn((2, 3, 4), |(i, j, k)| (i * 12 + j * 4 + k) as f32);
+        let value = Value::quantized(array.clone(), Scope::Neuron, Quantization::U16);
+        assert!(value.as_f32().is_none());
+        let dequantized = value.dequantize().unwrap();
+        for (&original, &dequantized) in array.iter().zip(&dequantized) {
+            assert!((original - dequantized).abs() < 1e-3);
+        }
+        assert!(Value::new(array, Scope::Neuron).dequantize().is_none());
+    }
+
+    #[test]
+    fn neuron_numbers_of_quantized_values_match_the_whole_value() {
+        let array = Array3::from_shape_fn((2, 3, 4), |(i, j, k)| {
+            (k as f32 - 1.5) * 10f32.powi((i * 3 + j) as i32 - 2)
+        });
+        for scope in [Scope::Global, Scope::Layer, Scope::Neuron] {
+            let value = Value::quantized(array.clone(), scope, Quantization::U8);
+            let dequantized = value.dequantize().unwrap();
+            for (layer_index, neuron_index) in [(0, 0), (1, 2)] {
+                let expected = match scope {
+                    Scope::Global => dequantized.view(),
+                    Scope::Layer => dequantized.index_axis(Axis(0), layer_index),
+                    Scope::Neuron => dequantized
+                        .index_axis(Axis(0), layer_index)
+                        .index_axis_move(Axis(0), neuron_index),
+                };
+                assert_eq!(
+                    value.neuron_f32(layer_index, neuron_index).unwrap(),
+                    expected
+                );
+            }
+        }
+        // Every neuron has its own scale, so even neurons with tiny numbers keep them.
+        let value = Value::quantized(array.clone(), Scope::Neuron, Quantization::U8);
+        let numbers = value.neuron_f32(0, 0).unwrap();
+        let original = array.index_axis(Axis(0), 0).index_axis_move(Axis(0), 0);
+        for (&original, &dequantized) in original.iter().zip(&numbers) {
+            assert!((original - dequantized).abs() <= original.abs() * 1e-2);
+        }
+    }
 }
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+/// Maps token ids to their text, so values can store token ids instead of repeating the same strings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Vocabulary {
+    tokens: Vec<String>,
+    /// The raw bytes of every token, for tokenizers where tokens are not valid UTF-8 on their own.
+    token_bytes: Option<Vec<Vec<u8>>>,
+}
+
+impl Vocabulary {
+    pub fn new(tokens: Vec<String>, token_bytes: Option<Vec<Vec<u8>>>) -> Self {
+        if let Some(token_bytes) = &token_bytes {
+            assert_eq!(
+                tokens.len(),
+                token_bytes.len(),
+                "The byte representation must contain exactly one entry per token."
+            );
+        }
+        Self {
+            tokens,
+            token_bytes,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    pub fn token(&self, token_id: u32) -> Option<&str> {
+        self.tokens.get(token_id as usize).map(String::as_str)
+    }
+
+    pub fn token_bytes(&self, token_id: u32) -> Option<&[u8]> {
+        self.token_bytes
+            .as_ref()
+            .and_then(|token_bytes| token_bytes.get(token_id as usize))
+            .map(Vec::as_slice)
+    }
+}
@@ -0,0 +1,52 @@
+use maud::{html, Markup};
+
+use crate::data::Annotation;
+
+/// Shows the annotation of a neuron, if it has one.
+pub fn annotation(annotation: Option<&Annotation>) -> Markup {
+    html! {
+        @if let Some(annotation) = annotation {
+            div class="annotation" {
+                span class="annotation_label" {(annotation.label)}
+                @for tag in &annotation.tags {
+                    " "
+                    span class="annotation_tag" {(tag)}
+                }
+                @if !annotation.author.is_empty() {
+                    " "
+                    span class="annotation_author" {"by " (annotation.author)}
+                }
+            }
+        }
+    }
+}
+
+/// Form for creating, editing and deleting the annotation of a neuron on the server.
+/// `neuron_path` is the path of the neuron page, which is also the base of the annotation endpoints.
+pub fn annotation_form(annotation: Option<&Annotation>, neuron_path: &str) -> Markup {
+    let (label, tags, author) = annotation
+        .map(|annotation| {
+            (
+                annotation.label.as_str(),
+                annotation.tags.join(", "),
+                annotation.author.as_str(),
+            )
+        })
+        .unwrap_or(("", String::new(), ""));
+    html! {
+        details class="annotation_form" {
+            summary {(if annotation.is_some() {"Edit annotation"} else {"Add annotation"})}
+            form method="post" action={(neuron_path)"/annotation"} {
+                label {"Label " input type="text" name="label" value=(label) required;}
+                label {"Tags " input type="text" name="tags" value=(tags) placeholder="comma separated";}
+                label {"Author " input type="text" name="author" value=(author);}
+                input type="submit" value="Save";
+            }
+            @if annotation.is_some() {
+                form method="post" action={(neuron_path)"/annotation/delete"} {
+                    input type="submit" value="Delete annotation";
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use maud::{html, Markup, DOCTYPE};
+use ndarray::{Array2, Ix2};
+
+use crate::{
+    data::value::{DataType, NumberStatistics, Scope},
+    html::{annotation, heatmap, ColorScale, Colormap, Normalization},
+    Annotations, Clustering, Payload,
+};
+
+/// Means of the two dimensional `Neuron` scoped F32 values over the members of a cluster, by value name, with the
+/// statistics of the values to colour the means like the heatmaps of single neurons.
+fn mean_heatmaps(
+    payload: &Payload,
+    members: &[(usize, usize)],
+) -> Vec<(String, Option<NumberStatistics>, Array2<f32>)> {
+    payload
+        .values()
+        .into_iter()
+        .filter(|(_, value)| {
+            value.scope() == Scope::Neuron
+                && value.data_type() == DataType::F32
+                && value.shape().len() == Scope::Neuron.num_axes() + 2
+        })
+        .filter_map(|(key, value)| {
+            let mut sum: Option<Array2<f32>> = None;
+            for &(layer_index, neuron_index) in members {
+                let neuron_value = value
+                    .neuron_f32(layer_index, neuron_index)
+                    .unwrap()
+                    .into_dimensionality::<Ix2>()
+                    .unwrap();
+                match sum {
+                    Some(ref mut sum) => *sum += &neuron_value,
+                    None => sum = Some(neuron_value.into_owned()),
+                }
+            }
+            sum.map(|sum| {
+                (
+                    key.to_string(),
+                    value.statistics(),
+                    sum / members.len() as f32,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Page listing the members of a cluster together with the mean of every heatmap value over the members.
+pub fn generate_cluster_page(
+    payload: &Payload,
+    annotations: &Annotations,
+    clustering: &Clustering,
+    cluster_index: usize,
+    file: bool,
+) -> Markup {
+    let members = payload.cluster_members(clustering, cluster_index);
+    // Static cluster pages are two directories deep, at `clusters/{key}/C{cluster_index}.html`.
+    let root = if file { "../../" } else { "/" };
+    let file_extension = if file { ".html" } else { "" };
+    let title = format!(
+        "Transformer Scope - {} cluster {cluster_index}",
+        clustering.key
+    );
+
+    html!(
+        (DOCTYPE)
+        head {
+            meta charset="utf-8";
+            title {(title)}
+            link rel="stylesheet" href={(root)"static/style.css"}{};
+        }
+        a href={(if file {"../../index.html"} else {"/"})} {"Back to index"}
+        h1 {(title)}
+        p {
+            (members.len())" neurons clustered by "(clustering.source_key)"."
+        }
+        @if cluster_index > 0 {
+            a href={"C"({cluster_index - 1})(file_extension)} {"Previous"}
+        }
+        " - "
+        @if cluster_index + 1 < clustering.num_clusters {
+            a href={"C"({cluster_index + 1})(file_extension)} {"Next"}
+        }
+        @if !members.is_empty() {
+            @for (key, statistics, mean) in mean_heatmaps(payload, &members) {
+                @let color_scale = ColorScale::new(Colormap::default(), Normalization::Linear, None, None, statistics);
+                h2 {"Mean " (key)}
+                (heatmap(&mean, &color_scale))
+            }
+        }
+        h2 {"Members"}
+        ul class="cluster_members" {
+            @for (layer_index, neuron_index) in members {
+                @let layer_index = payload.original_layer_index(layer_index);
+                @let neuron_index = payload.original_neuron_index(neuron_index);
+                li {
+                    a href={(root)"L"(layer_index)"/N"(neuron_index)(file_extension)} {
+                        "L"(layer_index)" N"(neuron_index)
+                    }
+                    (annotation::annotation(annotations.get(layer_index, neuron_index)))
+                }
+            }
+        }
+    )
+}
@@ -0,0 +1,456 @@
+use maud::{html, Markup};
+
+use crate::data::value::NumberStatistics;
+
+/// Maps numbers between 0 and 1 to colours by interpolating between evenly spaced colour stops.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Colormap {
+    /// Red through black to green, the default colours of heatmaps.
+    #[default]
+    GreenRed,
+    Viridis,
+    Magma,
+    Greys,
+    RdBu,
+    Coolwarm,
+}
+
+const GREEN_RED: &[[f32; 3]] = &[[255., 0., 0.], [0., 0., 0.], [69., 254., 152.]];
+const VIRIDIS: &[[f32; 3]] = &[
+    [68., 1., 84.],
+    [71., 44., 122.],
+    [59., 81., 139.],
+    [44., 113., 142.],
+    [33., 144., 141.],
+    [39., 173., 129.],
+    [92., 200., 99.],
+    [170., 220., 50.],
+    [253., 231., 37.],
+];
+const MAGMA: &[[f32; 3]] = &[
+    [0., 0., 4.],
+    [28., 16., 68.],
+    [79., 18., 123.],
+    [129., 37., 129.],
+    [181., 54., 122.],
+    [229., 80., 100.],
+    [251., 135., 97.],
+    [254., 194., 135.],
+    [252., 253., 191.],
+];
+const GREYS: &[[f32; 3]] = &[[255., 255., 255.], [0., 0., 0.]];
+const RD_BU: &[[f32; 3]] = &[
+    [103., 0., 31.],
+    [178., 24., 43.],
+    [214., 96., 77.],
+    [244., 165., 130.],
+    [253., 219., 199.],
+    [247., 247., 247.],
+    [209., 229., 240.],
+    [146., 197., 222.],
+    [67., 147., 195.],
+    [33., 102., 172.],
+    [5., 48., 97.],
+];
+const COOLWARM: &[[f32; 3]] = &[
+    [59., 76., 192.],
+    [141., 176., 254.],
+    [221., 221., 221.],
+    [244., 154., 123.],
+    [180., 4., 38.],
+];
+
+impl Colormap {
+    pub const ALL: [Colormap; 6] = [
+        Colormap::GreenRed,
+        Colormap::Viridis,
+        Colormap::Magma,
+        Colormap::Greys,
+        Colormap::RdBu,
+        Colormap::Coolwarm,
+    ];
+
+    /// The name used in templates.
+    pub fn name(self) -> &'static str {
+        match self {
+            Colormap::GreenRed => "green_red",
+            Colormap::Viridis => "viridis",
+            Colormap::Magma => "magma",
+            Colormap::Greys => "greys",
+            Colormap::RdBu => "rdbu",
+            Colormap::Coolwarm => "coolwarm",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|colormap| colormap.name() == name)
+    }
+
+    /// Whether the colormap has a neutral colour in the middle, for numbers on both sides of zero.
+    pub fn is_diverging(self) -> bool {
+        matches!(
+            self,
+            Colormap::GreenRed | Colormap::RdBu | Colormap::Coolwarm
+        )
+    }
+
+    fn stops(self) -> &'static [[f32; 3]] {
+        match self {
+            Colormap::GreenRed => GREEN_RED,
+            Colormap::Viridis => VIRIDIS,
+            Colormap::Magma => MAGMA,
+            Colormap::Greys => GREYS,
+            Colormap::RdBu => RD_BU,
+            Colormap::Coolwarm => COOLWARM,
+        }
+    }
+
+    /// The colour of a number between 0 and 1. Other numbers are clamped and NaN is shown as the middle colour.
+    pub fn color(self, position: f32) -> [u8; 3] {
+        let position = if position.is_nan() {
+            0.5
+        } else {
+            position.clamp(0., 1.)
+        };
+        let stops = self.stops();
+        let scaled = position * (stops.len() - 1) as f32;
+        let lower_index = (scaled.floor() as usize).min(stops.len() - 2);
+        let fraction = scaled - lower_index as f32;
+        let (lower, upper) = (stops[lower_index], stops[lower_index + 1]);
+        let mut color = [0u8; 3];
+        for (channel, color_value) in color.iter_mut().enumerate() {
+            let color_value_float = lower[channel] + (upper[channel] - lower[channel]) * fraction;
+            *color_value = color_value_float.round() as u8;
+        }
+        color
+    }
+}
+
+/// The number of colours sampled from a colormap for the colour bar of a legend.
+const LEGEND_GRADIENT_STOPS: usize = 16;
+
+/// Formats a number shown in a legend or a cell with at most three decimal places, or in scientific notation if it
+/// is very small or large.
+pub(super) fn format_number(number: f32) -> String {
+    if number == 0. || (1e-2..1e4).contains(&number.abs()) {
+        let formatted = format!("{number:.3}");
+        formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    } else {
+        format!("{number:.2e}")
+    }
+}
+
+/// How numbers are mapped to positions in a colormap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// Linear between `vmin` and `vmax`.
+    Linear,
+    /// Linear between `-v` and `v`, where `v` is the largest absolute bound, so zero is in the middle.
+    Symmetric,
+    /// Logarithmic between positive bounds. Numbers of at most zero get the lowest colour.
+    Log,
+    /// Linear, with bounds taken from the numbers shown instead of the whole value, e.g. from a single neuron.
+    Auto,
+}
+
+impl Normalization {
+    pub const ALL: [Normalization; 4] = [
+        Normalization::Linear,
+        Normalization::Symmetric,
+        Normalization::Log,
+        Normalization::Auto,
+    ];
+
+    /// The name used in templates.
+    pub fn name(self) -> &'static str {
+        match self {
+            Normalization::Linear => "linear",
+            Normalization::Symmetric => "symmetric",
+            Normalization::Log => "log",
+            Normalization::Auto => "auto",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|normalization| normalization.name() == name)
+    }
+}
+
+/// Maps numbers to colours with a colormap and a normalization.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorScale {
+    colormap: Colormap,
+    log: bool,
+    vmin: f32,
+    vmax: f32,
+}
+
+impl ColorScale {
+    /// Bounds which are not given are taken from `statistics`, and are centred on zero for diverging colormaps.
+    /// `statistics` should be those of the shown numbers for [`Normalization::Auto`] and of the whole value otherwise.
+    pub fn new(
+        colormap: Colormap,
+        normalization: Normalization,
+        vmin: Option<f32>,
+        vmax: Option<f32>,
+        statistics: Option<NumberStatistics>,
+    ) -> Self {
+        let max_abs = statistics.map_or(0., |statistics| statistics.max_abs());
+        let (vmin, vmax) = match normalization {
+            Normalization::Symmetric => {
+                let bound = match (vmin, vmax) {
+                    (None, None) => max_abs,
+                    (vmin, vmax) => vmin.unwrap_or(0.).abs().max(vmax.unwrap_or(0.).abs()),
+                };
+                (-bound, bound)
+            }
+            Normalization::Log => (
+                vmin.or(statistics.and_then(|statistics| statistics.min_positive))
+                    .unwrap_or(0.),
+                vmax.or(statistics.map(|statistics| statistics.max))
+                    .unwrap_or(0.),
+            ),
+            Normalization::Linear | Normalization::Auto if colormap.is_diverging() => {
+                (vmin.unwrap_or(-max_abs), vmax.unwrap_or(max_abs))
+            }
+            Normalization::Linear | Normalization::Auto => (
+                vmin.or(statistics.map(|statistics| statistics.min))
+                    .unwrap_or(0.),
+                vmax.or(statistics.map(|statistics| statistics.max))
+                    .unwrap_or(0.),
+            ),
+        };
+        Self {
+            colormap,
+            log: normalization == Normalization::Log,
+            vmin,
+            vmax,
+        }
+    }
+
+    /// The position of a number in the colormap. Numbers are shown with the middle colour if the bounds are equal.
+    fn position(&self, number: f32) -> f32 {
+        if self.log {
+            if self.vmin <= 0. || self.vmax <= self.vmin {
+                return 0.5;
+            }
+            if number <= 0. {
+                return 0.;
+            }
+            (number.ln() - self.vmin.ln()) / (self.vmax.ln() - self.vmin.ln())
+        } else if self.vmax > self.vmin {
+            (number - self.vmin) / (self.vmax - self.vmin)
+        } else {
+            0.5
+        }
+    }
+
+    /// The number in the middle of the colormap.
+    fn middle(&self) -> f32 {
+        if self.log && self.vmin > 0. && self.vmax > 0. {
+            (self.vmin * self.vmax).sqrt()
+        } else {
+            (self.vmin + self.vmax) / 2.
+        }
+    }
+
+    pub fn color(&self, number: f32) -> [u8; 3] {
+        self.colormap.color(self.position(number))
+    }
+
+    /// The colour of a number as a CSS colour, e.g. for `background-color`.
+    pub fn css_color(&self, number: f32) -> String {
+        let [red, green, blue] = self.color(number);
+        format!("rgb({red}, {green}, {blue})")
+    }
+
+    /// A colour bar of the colormap labelled with the bounds and the number in the middle.
+    pub fn legend(&self) -> Markup {
+        let gradient: Vec<String> = (0..LEGEND_GRADIENT_STOPS)
+            .map(|stop| {
+                let position = stop as f32 / (LEGEND_GRADIENT_STOPS - 1) as f32;
+                let [red, green, blue] = self.colormap.color(position);
+                format!("rgb({red}, {green}, {blue})")
+            })
+            .collect();
+        html! {
+            div class="color_legend" {
+                div class="color_legend_bar" style={"background: linear-gradient(to right, "(gradient.join(", "))")"} {}
+                div class="color_legend_labels" {
+                    span {(format_number(self.vmin))}
+                    span {(format_number(self.middle()))}
+                    span {(format_number(self.vmax))}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorScale, Colormap, Normalization};
+    use crate::data::value::NumberStatistics;
+
+    fn statistics(numbers: &[f32]) -> Option<NumberStatistics> {
+        NumberStatistics::of(numbers)
+    }
+
+    fn bounds(scale: ColorScale) -> (f32, f32) {
+        (scale.vmin, scale.vmax)
+    }
+
+    #[test]
+    fn colormaps_run_from_their_first_to_their_last_colour() {
+        for (colormap, first, middle, last) in [
+            (Colormap::GreenRed, [255, 0, 0], [0, 0, 0], [69, 254, 152]),
+            (
+                Colormap::Viridis,
+                [68, 1, 84],
+                [33, 144, 141],
+                [253, 231, 37],
+            ),
+            (Colormap::Magma, [0, 0, 4], [181, 54, 122], [252, 253, 191]),
+            (Colormap::Greys, [255, 255, 255], [128, 128, 128], [0, 0, 0]),
+            (Colormap::RdBu, [103, 0, 31], [247, 247, 247], [5, 48, 97]),
+            (
+                Colormap::Coolwarm,
+                [59, 76, 192],
+                [221, 221, 221],
+                [180, 4, 38],
+            ),
+        ] {
+            assert_eq!(colormap.color(0.), first, "{}", colormap.name());
+            assert_eq!(colormap.color(0.5), middle, "{}", colormap.name());
+            assert_eq!(colormap.color(1.), last, "{}", colormap.name());
+            // Numbers outside of the colormap are clamped and NaN gets the middle colour.
+            assert_eq!(colormap.color(-1.), first);
+            assert_eq!(colormap.color(2.), last);
+            assert_eq!(colormap.color(f32::NAN), middle);
+            assert_eq!(Colormap::from_name(colormap.name()), Some(colormap));
+        }
+    }
+
+    #[test]
+    fn linear_bounds_are_taken_from_the_statistics() {
+        let scale = ColorScale::new(
+            Colormap::Viridis,
+            Normalization::Linear,
+            None,
+            None,
+            statistics(&[1., 3., 2.]),
+        );
+        assert_eq!(bounds(scale), (1., 3.));
+        assert_eq!(scale.color(1.), Colormap::Viridis.color(0.));
+        assert_eq!(scale.color(2.), Colormap::Viridis.color(0.5));
+        assert_eq!(scale.color(3.), Colormap::Viridis.color(1.));
+
+        // Given bounds take precedence, for auto as well as linear normalization.
+        let scale = ColorScale::new(
+            Colormap::Viridis,
+            Normalization::Auto,
+            Some(0.),
+            None,
+            statistics(&[1., 3.]),
+        );
+        assert_eq!(bounds(scale), (0., 3.));
+    }
+
+    #[test]
+    fn diverging_colormaps_are_centred_on_zero() {
+        for normalization in [Normalization::Linear, Normalization::Auto] {
+            let scale = ColorScale::new(
+                Colormap::GreenRed,
+                normalization,
+                None,
+                None,
+                statistics(&[1., 4.]),
+            );
+            assert_eq!(bounds(scale), (-4., 4.));
+            assert_eq!(scale.color(0.), [0, 0, 0]);
+            assert_eq!(scale.color(4.), [69, 254, 152]);
+        }
+    }
+
+    #[test]
+    fn symmetric_bounds_include_zero_for_positive_numbers() {
+        let scale = ColorScale::new(
+            Colormap::Viridis,
+            Normalization::Symmetric,
+            None,
+            None,
+            statistics(&[1., 4.]),
+        );
+        assert_eq!(bounds(scale), (-4., 4.));
+        assert_eq!(scale.color(0.), Colormap::Viridis.color(0.5));
+
+        // The largest absolute bound given is used on both sides.
+        let scale = ColorScale::new(
+            Colormap::Viridis,
+            Normalization::Symmetric,
+            Some(-1.),
+            Some(3.),
+            statistics(&[1., 4.]),
+        );
+        assert_eq!(bounds(scale), (-3., 3.));
+    }
+
+    #[test]
+    fn log_bounds_are_positive() {
+        let scale = ColorScale::new(
+            Colormap::Viridis,
+            Normalization::Log,
+            None,
+            None,
+            statistics(&[-2., 0., 1., 100.]),
+        );
+        assert_eq!(bounds(scale), (1., 100.));
+        assert_eq!(scale.color(1.), Colormap::Viridis.color(0.));
+        assert_eq!(scale.color(10.), Colormap::Viridis.color(0.5));
+        assert_eq!(scale.color(100.), Colormap::Viridis.color(1.));
+        assert_eq!(scale.middle(), 10.);
+        // Numbers of at most zero get the lowest colour.
+        assert_eq!(scale.color(0.), Colormap::Viridis.color(0.));
+        assert_eq!(scale.color(-2.), Colormap::Viridis.color(0.));
+
+        // Without positive numbers there is no logarithmic range, so every number gets the middle colour.
+        let scale = ColorScale::new(
+            Colormap::Viridis,
+            Normalization::Log,
+            None,
+            None,
+            statistics(&[-2., 0.]),
+        );
+        assert_eq!(scale.color(-2.), Colormap::Viridis.color(0.5));
+        assert_eq!(scale.color(1.), Colormap::Viridis.color(0.5));
+    }
+
+    #[test]
+    fn equal_bounds_give_the_middle_colour() {
+        let scale = ColorScale::new(
+            Colormap::Magma,
+            Normalization::Linear,
+            None,
+            None,
+            statistics(&[2., 2.]),
+        );
+        assert_eq!(bounds(scale), (2., 2.));
+        for number in [1., 2., 5.] {
+            assert_eq!(scale.color(number), Colormap::Magma.color(0.5));
+        }
+        let scale = ColorScale::new(
+            Colormap::Magma,
+            Normalization::Log,
+            Some(3.),
+            Some(3.),
+            None,
+        );
+        assert_eq!(scale.color(3.), Colormap::Magma.color(0.5));
+    }
+}
@@ -1,9 +1,13 @@
 use maud::{html, Markup};
 use ndarray::ArrayView2;
 
-use super::heatmap;
+use super::ColorScale;
 
-pub fn focus_sequences(activations: ArrayView2<f32>, step_names: ArrayView2<String>) -> Markup {
+pub fn focus_sequences<S: AsRef<str>>(
+    activations: ArrayView2<f32>,
+    step_names: ArrayView2<S>,
+    color_scale: &ColorScale,
+) -> Markup {
     assert_eq!(activations.shape(), step_names.shape());
     html! {
         table class="games" {
@@ -22,19 +26,18 @@ pub fn focus_sequences(activations: ArrayView2<f32>, step_names: ArrayView2<Stri
                         ({row_index + 1})
                     }
                     @for (&activation, step_name) in activation_row.iter().zip(step_name_row.iter()) {
-                        (board_cell(activation, step_name))
+                        (board_cell(activation, color_scale, step_name.as_ref()))
                     }
                 }
             }
         }
+        (color_scale.legend())
     }
 }
 
-fn board_cell(activation: f32, step_name: &str) -> impl maud::Render {
-    let color = heatmap::interpolate_color(activation * 10.);
-
+fn board_cell(activation: f32, color_scale: &ColorScale, step_name: &str) -> impl maud::Render {
     html! {
-        td class="game_step" style={"background-color: rgb("(color[0])", "(color[1])", "(color[2])")"} {
+        td class="game_step" style={"background-color: "(color_scale.css_color(activation))} title=(activation) {
             (step_name)
         }
     }
@@ -3,15 +3,34 @@ use std::{
     path::Path,
 };
 
-use crate::{html, Payload};
+use crate::{html, html::PageImages, Annotations, Payload, SearchIndex};
 
 const STYLE_CSS: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../static/style.css"));
 
-fn generate_site_in_dir_inner(path: &Path, payload: &Payload) {
+/// Writes the PNG images of a page to the `images` directory in `directory`, if it has any.
+fn write_images(directory: &Path, images: PageImages) {
+    let images = images.into_vec();
+    if images.is_empty() {
+        return;
+    }
+    let images_path = directory.join("images");
+    fs::create_dir_all(&images_path).unwrap();
+    for (index, image) in images.iter().enumerate() {
+        fs::write(images_path.join(format!("{index}.png")), image).unwrap();
+    }
+}
+
+fn generate_site_in_dir_inner(path: &Path, payload: &Payload, annotations: &Annotations) {
     fs::create_dir_all(path).unwrap();
 
-    let index_page = html::index::generate_index_page(payload, true);
+    let images = PageImages::default();
+    let index_page = html::index::generate_index_page(payload, annotations, true, &images);
     fs::write(path.join("index.html"), index_page.into_string()).unwrap();
+    write_images(path, images);
+
+    println!("Generating search index...");
+    let search_index = SearchIndex::new(payload, annotations);
+    fs::write(path.join("search_index.json"), search_index.to_json()).unwrap();
 
     // Copy static files.
     let static_path = path.join("static");
@@ -22,18 +41,55 @@ fn generate_site_in_dir_inner(path: &Path, payload: &Payload) {
     let layer_count = payload.num_layers();
     let neuron_count = payload.num_mlp_neurons();
     for layer_index in 0..layer_count {
-        println!("Generating pages for neurons in layer {layer_index}...");
-        let layer_path = path.join(format!("L{layer_index}"));
+        let original_layer_index = payload.original_layer_index(layer_index);
+        println!("Generating pages for neurons in layer {original_layer_index}...");
+        let layer_path = path.join(format!("L{original_layer_index}"));
         fs::create_dir(&layer_path).unwrap();
+        let images = PageImages::default();
+        let layer_page =
+            html::layer::generate_layer_page(layer_index, payload, annotations, true, &images);
+        fs::write(layer_path.join("index.html"), layer_page.into_string()).unwrap();
+        write_images(&layer_path, images);
         for neuron_index in 0..neuron_count {
-            let neuron_page =
-                html::neuron::generate_neuron_page(layer_index, neuron_index, payload, true);
-            let neuron_path = layer_path.join(format!("N{neuron_index}.html"));
+            let images = PageImages::default();
+            let neuron_page = html::neuron::generate_neuron_page(
+                layer_index,
+                neuron_index,
+                payload,
+                annotations,
+                true,
+                &images,
+            );
+            let original_neuron_index = payload.original_neuron_index(neuron_index);
+            let neuron_path = layer_path.join(format!("N{original_neuron_index}.html"));
             fs::write(neuron_path, neuron_page.into_string()).unwrap();
+            // Images of neuron pages are in a directory named like the page, e.g. `L2/N5/images`.
+            write_images(
+                &layer_path.join(format!("N{original_neuron_index}")),
+                images,
+            );
+        }
+    }
+
+    for clustering in payload.clusterings() {
+        println!("Generating pages for clusters of {}...", clustering.key);
+        let clustering_path = path.join("clusters").join(&clustering.key);
+        fs::create_dir_all(&clustering_path).unwrap();
+        for cluster_index in 0..clustering.num_clusters {
+            let cluster_page = html::cluster::generate_cluster_page(
+                payload,
+                annotations,
+                clustering,
+                cluster_index,
+                true,
+            );
+            let cluster_path = clustering_path.join(format!("C{cluster_index}.html"));
+            fs::write(cluster_path, cluster_page.into_string()).unwrap();
         }
     }
 }
 
-pub fn generate_site_in_dir<P: AsRef<Path>>(path: P, payload: &Payload) {
-    generate_site_in_dir_inner(path.as_ref(), payload);
+/// Generates a static site for the payload. Annotations are shown read-only.
+pub fn generate_site_in_dir<P: AsRef<Path>>(path: P, payload: &Payload, annotations: &Annotations) {
+    generate_site_in_dir_inner(path.as_ref(), payload, annotations);
 }
@@ -1,51 +1,106 @@
 use maud::{html, Markup};
-use ndarray::{AsArray, Ix2};
+use ndarray::{Array2, ArrayView2, AsArray, Ix2};
 
-const COLOR_POSITIVE: [f32; 3] = [69., 254., 152.];
-const COLOR_ZERO: [f32; 3] = [0., 0., 0.];
-const COLOR_NEGATIVE: [f32; 3] = [255., 0., 0.];
+use super::{colormap::format_number, ColorScale};
 
-pub fn interpolate_color(value: f32) -> [u8; 3] {
-    let value = value.clamp(-1., 1.);
+/// The text shown in every cell of a heatmap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellText {
+    /// Board coordinates like `A1`, with rows numbered instead of lettered if there are more than 26.
+    Coordinates,
+    /// The number of the cell.
+    Number,
+    /// Nothing, which makes the cells small enough for large matrices.
+    None,
+}
 
-    let interpolation_color = if value > 0. {
-        COLOR_POSITIVE
-    } else {
-        COLOR_NEGATIVE
-    };
-    let interpolation_value = value.abs();
+impl CellText {
+    pub const ALL: [CellText; 3] = [CellText::Coordinates, CellText::Number, CellText::None];
 
-    let mut color = [0u8, 0, 0];
-    for (color_index, color_value) in color.iter_mut().enumerate() {
-        let color_value_float = COLOR_ZERO[color_index]
-            + (interpolation_color[color_index] - COLOR_ZERO[color_index]) * interpolation_value;
-        *color_value = color_value_float.round() as u8;
+    /// The name used in templates.
+    pub fn name(self) -> &'static str {
+        match self {
+            CellText::Coordinates => "coordinates",
+            CellText::Number => "number",
+            CellText::None => "none",
+        }
     }
 
-    color
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|cell_text| cell_text.name() == name)
+    }
 }
 
-pub fn heatmap<'a, A: AsArray<'a, f32, Ix2>>(values: A) -> Markup {
+/// Renders a two dimensional value as a board, coloured by `color_scale`, followed by its legend.
+/// Every cell shows its number when hovered.
+pub fn heatmap<'a, A: AsArray<'a, f32, Ix2>>(values: A, color_scale: &ColorScale) -> Markup {
+    labelled_heatmap(values, color_scale, None, None, CellText::Coordinates)
+}
+
+/// Renders a matrix like [`heatmap`], with optional labels of every row and column shown as headers.
+pub fn labelled_heatmap<'a, A: AsArray<'a, f32, Ix2>>(
+    values: A,
+    color_scale: &ColorScale,
+    row_labels: Option<&[String]>,
+    column_labels: Option<&[String]>,
+    cell_text: CellText,
+) -> Markup {
     let values = values.into();
+    let texts = cell_texts(values, cell_text);
+    let table_class = match cell_text {
+        CellText::None => "heatmap heatmap_compact",
+        CellText::Coordinates | CellText::Number => "heatmap",
+    };
     html! {
-        table {
-            @for (row_index, row) in values.rows().into_iter().enumerate() {
-                tr {
-                    @for (column_index, &value) in row.iter().enumerate() {
-                        (board_cell(value, row_index, column_index))
+        div class="heatmap_scroll" {
+            table class=(table_class) {
+                @if let Some(column_labels) = column_labels {
+                    tr {
+                        @if row_labels.is_some() {
+                            th {}
+                        }
+                        @for label in column_labels {
+                            th class="heatmap_column_label" {(label)}
+                        }
+                    }
+                }
+                @for (row_index, row) in values.rows().into_iter().enumerate() {
+                    tr {
+                        @if let Some(row_labels) = row_labels {
+                            th class="heatmap_row_label" {(row_labels[row_index])}
+                        }
+                        @for (column_index, &value) in row.iter().enumerate() {
+                            td style={"background-color: "(color_scale.css_color(value))} title=(value) {
+                                @if let Some(texts) = &texts {
+                                    (texts[[row_index, column_index]])
+                                }
+                            }
+                        }
                     }
                 }
             }
         }
+        (color_scale.legend())
     }
 }
 
-fn board_cell(value: f32, row_index: usize, column_index: usize) -> impl maud::Render {
-    let color = interpolate_color(value * 10.);
-
-    html! {
-        td style={"background-color: rgb("(color[0])", "(color[1])", "(color[2])")"} {
-            (char::from(b'A' + row_index as u8))({column_index + 1})
-        }
+/// The text of every cell of a heatmap, or `None` if the cells are empty.
+pub(crate) fn cell_texts(values: ArrayView2<f32>, cell_text: CellText) -> Option<Array2<String>> {
+    let lettered_rows = values.nrows() <= 26;
+    match cell_text {
+        CellText::Coordinates => Some(Array2::from_shape_fn(
+            values.dim(),
+            |(row_index, column_index)| {
+                if lettered_rows {
+                    format!("{}{}", char::from(b'A' + row_index as u8), column_index + 1)
+                } else {
+                    format!("{},{}", row_index + 1, column_index + 1)
+                }
+            },
+        )),
+        CellText::Number => Some(values.mapv(format_number)),
+        CellText::None => None,
     }
 }
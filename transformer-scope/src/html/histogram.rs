@@ -0,0 +1,102 @@
+use maud::{html, Markup};
+
+const WIDTH: f32 = 400.;
+const HEIGHT: f32 = 120.;
+/// Space below the bars for the axis labels.
+const LABEL_HEIGHT: f32 = 16.;
+
+/// Counts the finite values in `bins` equally wide bins between the smallest and largest value.
+/// Returns the counts together with the range of the values.
+fn bin_counts<'a>(values: impl Iterator<Item = &'a f32>, bins: usize) -> (Vec<usize>, f32, f32) {
+    assert!(bins > 0, "A histogram needs at least one bin.");
+    let values: Vec<f32> = values.copied().filter(|value| value.is_finite()).collect();
+    let (min, max) = values
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| {
+            (min.min(value), max.max(value))
+        });
+    let mut counts = vec![0; bins];
+    if values.is_empty() {
+        return (counts, 0., 0.);
+    }
+    let bin_width = (max - min) / bins as f32;
+    for value in values {
+        let bin = if bin_width > 0. {
+            (((value - min) / bin_width) as usize).min(bins - 1)
+        } else {
+            0
+        };
+        counts[bin] += 1;
+    }
+    (counts, min, max)
+}
+
+/// Renders the distribution of the values as an inline SVG bar chart.
+pub fn histogram<'a>(values: impl Iterator<Item = &'a f32>, bins: usize) -> Markup {
+    let (counts, min, max) = bin_counts(values, bins);
+    let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+    let bar_width = WIDTH / bins as f32;
+    let bin_width = (max - min) / bins as f32;
+
+    let total_height = HEIGHT + LABEL_HEIGHT;
+    let label_y = total_height - 3.;
+
+    html! {
+        svg class="histogram" width=(WIDTH) height=(total_height)
+            viewBox={"0 0 "(WIDTH)" "(total_height)} xmlns="http://www.w3.org/2000/svg" {
+            @for (bin, &count) in counts.iter().enumerate() {
+                @let bar_height = HEIGHT * count as f32 / max_count as f32;
+                @let bar_x = bin as f32 * bar_width;
+                @let bar_y = HEIGHT - bar_height;
+                @let bin_start = min + bin as f32 * bin_width;
+                @let bin_end = bin_start + bin_width;
+                rect class="histogram_bar" x=(bar_x) y=(bar_y) width=(bar_width) height=(bar_height) {
+                    title {(format!("{bin_start:.3} to {bin_end:.3}: {count}"))}
+                }
+            }
+            line class="histogram_axis" x1="0" y1=(HEIGHT) x2=(WIDTH) y2=(HEIGHT) {}
+            text class="histogram_label" x="0" y=(label_y) text-anchor="start" {
+                (format!("{min:.3}"))
+            }
+            text class="histogram_label" x=(WIDTH) y=(label_y) text-anchor="end" {
+                (format!("{max:.3}"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bin_counts, histogram};
+
+    #[test]
+    fn values_are_counted_in_equally_wide_bins() {
+        let values = [0., 0.5, 1., 2.4, 2.5, 3.9, 4.];
+        // Bins are [0, 1), [1, 2), [2, 3), [3, 4] with the maximum in the last bin.
+        assert_eq!(bin_counts(values.iter(), 4), (vec![2, 1, 2, 2], 0., 4.));
+    }
+
+    #[test]
+    fn constant_values_are_counted_in_the_first_bin() {
+        let values = [2.5; 3];
+        assert_eq!(bin_counts(values.iter(), 4), (vec![3, 0, 0, 0], 2.5, 2.5));
+        let svg = histogram(values.iter(), 4).into_string();
+        assert!(!svg.contains("NaN") && !svg.contains("inf"));
+    }
+
+    #[test]
+    fn non_finite_values_are_skipped() {
+        let values = [f32::NAN, -1., f32::INFINITY, 1., f32::NEG_INFINITY];
+        assert_eq!(bin_counts(values.iter(), 2), (vec![1, 1], -1., 1.));
+        let values = [f32::NAN, f32::INFINITY];
+        assert_eq!(bin_counts(values.iter(), 2), (vec![0, 0], 0., 0.));
+    }
+
+    #[test]
+    fn a_single_bin_counts_every_value() {
+        let values = [-3., 0., 7., f32::NAN];
+        assert_eq!(bin_counts(values.iter(), 1), (vec![3], -3., 7.));
+        let svg = histogram(values.iter(), 1).into_string();
+        assert_eq!(svg.matches("<rect").count(), 1);
+    }
+}
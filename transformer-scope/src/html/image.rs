@@ -0,0 +1,186 @@
+use std::cell::RefCell;
+
+use maud::{html, Markup};
+use ndarray::ArrayView2;
+
+use super::ColorScale;
+
+/// How an element showing a matrix of numbers is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rendering {
+    /// An HTML table with a cell per number.
+    Table,
+    /// An inline SVG image, which can be copied into other documents.
+    Svg,
+    /// A PNG image with a pixel per number, stored next to the page.
+    Png,
+}
+
+impl Rendering {
+    pub const ALL: [Rendering; 3] = [Rendering::Table, Rendering::Svg, Rendering::Png];
+
+    /// The name used in templates.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rendering::Table => "table",
+            Rendering::Svg => "svg",
+            Rendering::Png => "png",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|rendering| rendering.name() == name)
+    }
+}
+
+/// The PNG images generated for a page, which refers to them by their index in order of generation.
+#[derive(Debug, Default)]
+pub struct PageImages(RefCell<Vec<Vec<u8>>>);
+
+impl PageImages {
+    /// Adds the bytes of a PNG image and returns its index.
+    pub(crate) fn add(&self, png: Vec<u8>) -> usize {
+        let mut images = self.0.borrow_mut();
+        images.push(png);
+        images.len() - 1
+    }
+
+    pub fn into_vec(self) -> Vec<Vec<u8>> {
+        self.0.into_inner()
+    }
+}
+
+/// The side length of a cell of an SVG image with text in its cells.
+const SVG_CELL_SIZE: usize = 24;
+/// The side length of a cell of an SVG image without text, and of a pixel of a PNG image on the page.
+const SVG_COMPACT_CELL_SIZE: usize = 12;
+/// The approximate width of a character of labels, to leave room for them.
+const SVG_CHARACTER_WIDTH: usize = 7;
+
+/// The space needed by the longest of the labels.
+fn label_space(labels: Option<&[String]>) -> usize {
+    labels.map_or(0, |labels| {
+        labels
+            .iter()
+            .map(|label| label.chars().count())
+            .max()
+            .unwrap_or(0)
+            * SVG_CHARACTER_WIDTH
+            + 4
+    })
+}
+
+/// Draws a matrix as an SVG image with a rectangle per number, followed by the legend of its colours.
+/// Labels of the rows and columns are drawn to the left and on top, and `texts` inside the cells. The image is styled
+/// by its own attributes, so it looks the same when copied out of the page.
+pub(crate) fn matrix_svg<S: AsRef<str>>(
+    values: ArrayView2<f32>,
+    color_scale: &ColorScale,
+    row_labels: Option<&[String]>,
+    column_labels: Option<&[String]>,
+    texts: Option<ArrayView2<S>>,
+) -> Markup {
+    let cell_size = if texts.is_some() {
+        SVG_CELL_SIZE
+    } else {
+        SVG_COMPACT_CELL_SIZE
+    };
+    let left = label_space(row_labels);
+    let top = label_space(column_labels);
+    let width = left + values.ncols() * cell_size;
+    let height = top + values.nrows() * cell_size;
+    html! {
+        div class="heatmap_scroll" {
+            svg class="heatmap_svg" xmlns="http://www.w3.org/2000/svg" width=(width) height=(height) viewBox={"0 0 "(width)" "(height)} font-family="sans-serif" font-size="11" {
+                @if let Some(row_labels) = row_labels {
+                    @for (row_index, label) in row_labels.iter().enumerate() {
+                        text x=({left - 4}) y=({top + row_index * cell_size + cell_size / 2}) text-anchor="end" dominant-baseline="central" {(label)}
+                    }
+                }
+                @if let Some(column_labels) = column_labels {
+                    @for (column_index, label) in column_labels.iter().enumerate() {
+                        @let x = left + column_index * cell_size + cell_size / 2;
+                        @let y = top - 4;
+                        text x=(x) y=(y) transform={"rotate(-90 "(x)" "(y)")"} dominant-baseline="central" {(label)}
+                    }
+                }
+                @for ((row_index, column_index), &value) in values.indexed_iter() {
+                    @let x = left + column_index * cell_size;
+                    @let y = top + row_index * cell_size;
+                    rect x=(x) y=(y) width=(cell_size) height=(cell_size) fill=(color_scale.css_color(value)) {
+                        title {(value)}
+                    }
+                    @if let Some(texts) = &texts {
+                        text x=({x + cell_size / 2}) y=({y + cell_size / 2}) text-anchor="middle" dominant-baseline="central" fill="white" {
+                            (texts[[row_index, column_index]].as_ref())
+                        }
+                    }
+                }
+            }
+        }
+        (color_scale.legend())
+    }
+}
+
+/// Encodes a matrix as an RGB PNG image with a pixel per number, or returns `None` for an empty matrix, since PNG
+/// images cannot be empty.
+pub(crate) fn matrix_png(values: ArrayView2<f32>, color_scale: &ColorScale) -> Option<Vec<u8>> {
+    if values.is_empty() {
+        return None;
+    }
+    let pixels: Vec<u8> = values
+        .iter()
+        .flat_map(|&value| color_scale.color(value))
+        .collect();
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, values.ncols() as u32, values.nrows() as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .expect("Encoding a non-empty image in memory does not fail.");
+    Some(png)
+}
+
+/// Shows a PNG image of a matrix enlarged to a cell per pixel, followed by the legend of its colours.
+pub(crate) fn png_image(
+    url: &str,
+    rows: usize,
+    columns: usize,
+    color_scale: &ColorScale,
+) -> Markup {
+    html! {
+        div class="heatmap_scroll" {
+            img class="heatmap_png" src=(url) width=({columns * SVG_COMPACT_CELL_SIZE}) height=({rows * SVG_COMPACT_CELL_SIZE}) alt="Heatmap";
+        }
+        (color_scale.legend())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array2;
+
+    use super::matrix_png;
+    use crate::html::{ColorScale, Colormap, Normalization};
+
+    #[test]
+    fn only_non_empty_matrices_are_encoded() {
+        let color_scale = ColorScale::new(
+            Colormap::default(),
+            Normalization::Linear,
+            Some(0.),
+            Some(1.),
+            None,
+        );
+        for shape in [(0, 0), (0, 3), (2, 0)] {
+            assert!(matrix_png(Array2::zeros(shape).view(), &color_scale).is_none());
+        }
+        let png = matrix_png(Array2::zeros((2, 3)).view(), &color_scale).unwrap();
+        let info = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        assert_eq!((info.info().width, info.info().height), (3, 2));
+    }
+}
@@ -1,44 +1,91 @@
 use maud::{html, Markup};
 use ndarray::{ArrayView2, Axis};
 
-use crate::Payload;
+use crate::{
+    html::{annotation, layer, PageImages},
+    Annotations, Payload,
+};
 
-pub fn generate_index_page(payload: &Payload, site: bool) -> Markup {
-    let ranked_neurons = payload.ranked_neurons();
+pub fn generate_index_page(
+    payload: &Payload,
+    annotations: &Annotations,
+    site: bool,
+    images: &PageImages,
+) -> Markup {
+    payload
+        .index_template()
+        .generate(payload, annotations, site, images)
+}
 
+/// Links to the page of every cluster, with the number of neurons in it.
+pub(crate) fn generate_clusters_list(payload: &Payload, site: bool) -> Markup {
     html!(
-        head {
-            meta charset="utf-8";
-            title {"TransformerScope"}
-            link rel="stylesheet" href="static/style.css"{};
+        @for clustering in payload.clusterings() {
+            h2 {"Clusters by " (clustering.source_key)}
+            ul class="clusters" {
+                @for cluster_index in 0..clustering.num_clusters {
+                    @let size = payload.cluster_members(clustering, cluster_index).len();
+                    li {
+                        a href={"clusters/"(clustering.key)"/C"(cluster_index)(if site {".html"} else {""})} {
+                            "Cluster "(cluster_index)
+                        }
+                        " ("(size)" neurons)"
+                    }
+                }
+            }
         }
-        body {
-            h1 {"Welcome to TransformerScope!"}
-            p { "This is a web app for visualizing various aspects of transformer models." }
-            p {"Based on the original idea of the "
-            a href="https://neuroscope.io/gelu-3l/0/314.html"{"Neuroscope"} " by Neel
-            Nanda."}
+    )
+}
+
+/// Lists every value in the payload with its data type, scope and shape.
+pub(crate) fn generate_value_list(payload: &Payload) -> Markup {
+    html!(
+        table class="values" {
+            tr {
+                th {"Name"}
+                th {"Data type"}
+                th {"Scope"}
+                th {"Shape"}
+            }
+            @for (key, value) in payload.values() {
+                tr {
+                    td {(key)}
+                    td {(value.data_type())}
+                    td {(value.scope())}
+                    td {(format!("{:?}", value.shape()))}
+                }
+            }
         }
-        (generate_ranked_neurons_table(ranked_neurons.view(), site))
     )
 }
 
-pub fn generate_ranked_neurons_table(ranked_neurons: ArrayView2<u32>, site: bool) -> Markup {
+pub fn generate_ranked_neurons_table(
+    payload: &Payload,
+    annotations: &Annotations,
+    ranked_neurons: ArrayView2<u32>,
+    site: bool,
+) -> Markup {
     let (num_layers, _num_neurons) = ranked_neurons.dim();
     html!(
         table {
             tr {
                 th;
                 @for layer_index in 0..num_layers {
-                    th {(format!("Layer {layer_index}"))}
+                    @let layer_index = payload.original_layer_index(layer_index);
+                    th {
+                        a href=(layer::layer_link(layer_index, true, site)) {"Layer "(layer_index)}
+                    }
                 }
             }
             @for (rank, neurons_of_rank) in ranked_neurons.axis_iter(Axis(1)).enumerate() {
                 tr {
                     th {(rank)}
-                    @for (layer_index, neuron_index) in neurons_of_rank.iter().enumerate() {
+                    @for (layer_index, &neuron_index) in neurons_of_rank.iter().enumerate() {
+                        @let layer_index = payload.original_layer_index(layer_index);
+                        @let neuron_index = payload.original_neuron_index(neuron_index as usize);
                         td{
                             a href={"L"(layer_index)"/N"(neuron_index)(if site {".html"} else {""})} {(neuron_index)}
+                            (annotation::annotation(annotations.get(layer_index, neuron_index)))
                         }
                     }
                 }
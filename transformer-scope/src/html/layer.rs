@@ -0,0 +1,87 @@
+use maud::{html, Markup};
+use ndarray::ArrayView1;
+
+use crate::{
+    html::{annotation, ColorScale, PageImages},
+    Annotations, Payload,
+};
+
+pub fn generate_layer_page(
+    layer_index: usize,
+    payload: &Payload,
+    annotations: &Annotations,
+    file: bool,
+    images: &PageImages,
+) -> Markup {
+    payload
+        .layer_template()
+        .generate(payload, annotations, file, layer_index, images)
+}
+
+/// The link to a layer page from the index, a neuron page or another layer page.
+/// Static layer pages are at `L{layer_index}/index.html`, next to the pages of their neurons.
+pub(crate) fn layer_link(original_layer_index: usize, from_index: bool, file: bool) -> String {
+    let directory = if from_index { "" } else { "../" };
+    let file_name = if file { "/index.html" } else { "" };
+    format!("{directory}L{original_layer_index}{file_name}")
+}
+
+/// A number of every neuron of a layer shown as coloured cells linking to the neurons, `columns` cells per row,
+/// followed by the legend of the colours.
+pub(crate) fn layer_heatmap(
+    payload: &Payload,
+    numbers: ArrayView1<f32>,
+    color_scale: &ColorScale,
+    columns: usize,
+    layer_index: usize,
+    file: bool,
+) -> Markup {
+    let file_extension = if file { ".html" } else { "" };
+    let original_layer_index = payload.original_layer_index(layer_index);
+    let neurons: Vec<(usize, f32)> = numbers.iter().copied().enumerate().collect();
+    html! {
+        table class="layer_heatmap" {
+            @for row in neurons.chunks(columns) {
+                tr {
+                    @for &(neuron_index, number) in row {
+                        @let original_neuron_index = payload.original_neuron_index(neuron_index);
+                        td style={"background-color: "(color_scale.css_color(number))} title=(number) {
+                            a href={"../L"(original_layer_index)"/N"(original_neuron_index)(file_extension)} {
+                                (original_neuron_index)
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        (color_scale.legend())
+    }
+}
+
+/// Lists neurons of a layer as links with their number and annotation, in the given order.
+pub(crate) fn top_neurons(
+    payload: &Payload,
+    annotations: &Annotations,
+    neurons: &[(usize, f32)],
+    precision: usize,
+    layer_index: usize,
+    file: bool,
+) -> Markup {
+    let file_extension = if file { ".html" } else { "" };
+    let original_layer_index = payload.original_layer_index(layer_index);
+    html! {
+        ol class="neuron_links" {
+            @for &(neuron_index, number) in neurons {
+                @let original_neuron_index = payload.original_neuron_index(neuron_index);
+                li {
+                    a href={"../L"(original_layer_index)"/N"(original_neuron_index)(file_extension)} {
+                        "N"(original_neuron_index)
+                    }
+                    " "
+                    span class="neuron_link_score" {(format!("{number:.precision$}"))}
+                    (annotation::annotation(annotations.get(original_layer_index, original_neuron_index)))
+                }
+            }
+        }
+    }
+}
@@ -3,9 +3,30 @@ pub mod template;
 pub use index::generate_index_page;
 mod neuron;
 pub use neuron::generate_neuron_page;
+mod layer;
+pub use layer::generate_layer_page;
+mod colormap;
+pub use colormap::{ColorScale, Colormap, Normalization};
+mod image;
+pub use image::{PageImages, Rendering};
 mod heatmap;
-pub use heatmap::heatmap;
+pub use heatmap::{heatmap, labelled_heatmap, CellText};
 mod focus_sequences;
 pub use focus_sequences::focus_sequences;
 mod generate_site;
 pub use generate_site::generate_site_in_dir;
+mod token_snippets;
+pub use token_snippets::token_snippets;
+mod annotation;
+mod neuron_links;
+pub use neuron_links::neuron_links;
+mod cluster;
+pub use cluster::generate_cluster_page;
+mod search;
+pub use search::generate_search_page;
+mod histogram;
+pub use histogram::histogram;
+mod top_k;
+pub use top_k::top_k;
+mod statistics;
+pub use statistics::statistics;
@@ -1,14 +1,21 @@
 use maud::Markup;
 
-use crate::Payload;
+use crate::{html::PageImages, Annotations, Payload};
 
 pub fn generate_neuron_page(
     layer_index: usize,
     neuron_index: usize,
     payload: &Payload,
+    annotations: &Annotations,
     file: bool,
+    images: &PageImages,
 ) -> Markup {
-    payload
-        .neuron_template()
-        .generate(payload, file, layer_index, neuron_index)
+    payload.neuron_template().generate(
+        payload,
+        annotations,
+        file,
+        layer_index,
+        neuron_index,
+        images,
+    )
 }
@@ -0,0 +1,38 @@
+use maud::{html, Markup};
+use ndarray::{ArrayView1, ArrayView2};
+
+use crate::Payload;
+
+/// Lists neurons as links to their pages together with a score, e.g. their similarity.
+/// `neurons` has shape `[num_links, 2]` and holds the original layer and neuron index of every linked neuron.
+/// Neurons that are not in the payload, e.g. because it is a subset, are listed without a link.
+pub fn neuron_links(
+    payload: &Payload,
+    neurons: ArrayView2<u32>,
+    scores: ArrayView1<f32>,
+    file: bool,
+) -> Markup {
+    assert_eq!(neurons.ncols(), 2);
+    assert_eq!(neurons.nrows(), scores.len());
+    let file_extension = if file { ".html" } else { "" };
+    html! {
+        ol class="neuron_links" {
+            @for (neuron, &score) in neurons.rows().into_iter().zip(scores.iter()) {
+                @let (layer_index, neuron_index) = (neuron[0] as usize, neuron[1] as usize);
+                @let in_payload = payload.layer_index_from_original(layer_index).is_some()
+                    && payload.neuron_index_from_original(neuron_index).is_some();
+                li {
+                    @if in_payload {
+                        a href={"../L"(layer_index)"/N"(neuron_index)(file_extension)} {
+                            "L"(layer_index)" N"(neuron_index)
+                        }
+                    } @else {
+                        "L"(layer_index)" N"(neuron_index)
+                    }
+                    " "
+                    span class="neuron_link_score" {(format!("{score:.3}"))}
+                }
+            }
+        }
+    }
+}
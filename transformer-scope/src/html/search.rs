@@ -0,0 +1,61 @@
+use maud::{html, Markup, DOCTYPE};
+
+use crate::{html::annotation, Annotations, SearchQueryError};
+
+/// Maximum number of results listed on the search page.
+const MAX_RESULTS: usize = 1000;
+
+pub fn search_form(query: &str) -> Markup {
+    html! {
+        form class="search_form" method="get" action="/search" {
+            input type="search" name="q" value=(query)
+                placeholder="label:\"flipped\" layer:5 ownership_heatmap_stds>0.3";
+            " "
+            button type="submit" {"Search"}
+        }
+    }
+}
+
+/// Page with the neurons matching a search query, given by their original indices.
+/// Only generated by the server, since static sites come with a prebuilt index instead.
+pub fn generate_search_page(
+    annotations: &Annotations,
+    query: &str,
+    results: Result<&[(usize, usize)], &SearchQueryError>,
+) -> Markup {
+    html!(
+        (DOCTYPE)
+        head {
+            meta charset="utf-8";
+            title {"Transformer Scope - Search"}
+            link rel="stylesheet" href="/static/style.css"{};
+        }
+        a href="/" {"Back to index"}
+        h1 {"Search"}
+        (search_form(query))
+        @match results {
+            Err(error) => {
+                p class="search_error" {(error)}
+            }
+            Ok(results) => {
+                p {
+                    (results.len())" matching neurons"
+                    @if results.len() > MAX_RESULTS {
+                        ", showing the first "(MAX_RESULTS)
+                    }
+                    "."
+                }
+                ul class="search_results" {
+                    @for &(layer_index, neuron_index) in results.iter().take(MAX_RESULTS) {
+                        li {
+                            a href={"/L"(layer_index)"/N"(neuron_index)} {
+                                "L"(layer_index)" N"(neuron_index)
+                            }
+                            (annotation::annotation(annotations.get(layer_index, neuron_index)))
+                        }
+                    }
+                }
+            }
+        }
+    )
+}
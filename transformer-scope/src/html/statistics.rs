@@ -0,0 +1,32 @@
+use maud::{html, Markup};
+use ndarray::ArrayViewD;
+
+/// A table of the count, mean, standard deviation, minimum and maximum of the numbers.
+pub fn statistics(numbers: ArrayViewD<f32>) -> Markup {
+    if numbers.is_empty() {
+        return html! { p {"No values."} };
+    }
+    let count = numbers.len();
+    let mean = numbers.sum() / count as f32;
+    let std = (numbers.mapv(|number| (number - mean).powi(2)).sum() / count as f32).sqrt();
+    let min = numbers.fold(f32::INFINITY, |min, &number| min.min(number));
+    let max = numbers.fold(f32::NEG_INFINITY, |max, &number| max.max(number));
+    html! {
+        table class="statistics" {
+            tr {
+                th {"Count"}
+                th {"Mean"}
+                th {"Std"}
+                th {"Min"}
+                th {"Max"}
+            }
+            tr {
+                td {(count)}
+                td {(format!("{mean:.3}"))}
+                td {(format!("{std:.3}"))}
+                td {(format!("{min:.3}"))}
+                td {(format!("{max:.3}"))}
+            }
+        }
+    }
+}
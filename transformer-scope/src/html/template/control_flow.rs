@@ -0,0 +1,842 @@
+use ndarray::{ArrayD, ArrayViewD, Axis};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{
+        value::{DataType, Scope},
+        Comparison, Value,
+    },
+    Payload,
+};
+
+use super::{
+    element::{invalid_argument_error, schema_arguments, scope_slice},
+    parser::{unquote, ElementCall, ParseResult},
+    schema::{keyword, ArgumentType, ElementSchema, KeywordArguments},
+    ArgumentError, ArgumentErrorType,
+};
+
+/// Names of the elements opening, continuing and closing blocks.
+pub(super) const BLOCK_NAMES: [&str; 5] = ["if", "elif", "else", "end", "for"];
+
+/// The variables of the loops enclosing a part of a template, innermost last.
+///
+/// While validating, every variable holds the largest number of iterations of its loop. While generating, it holds
+/// the current index.
+#[derive(Debug, Clone, Default)]
+pub(super) struct LoopVariables(Vec<(String, usize)>);
+
+impl LoopVariables {
+    pub fn get(&self, variable: &str) -> Option<usize> {
+        self.0
+            .iter()
+            .rev()
+            .find(|(name, _)| name == variable)
+            .map(|&(_, value)| value)
+    }
+
+    pub fn with(&self, variable: &str, value: usize) -> Self {
+        let mut variables = self.clone();
+        variables.0.push((variable.to_string(), value));
+        variables
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.chars().next().is_some_and(|first| !first.is_numeric())
+        && text
+            .chars()
+            .all(|character| character.is_alphanumeric() || character == '_')
+}
+
+/// A value name in a template, optionally indexed by loop variables, e.g. `scores[i]` or `boards[i][j]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct ValueReference<'a> {
+    pub name: &'a str,
+    pub indices: Vec<&'a str>,
+}
+
+impl<'a> ValueReference<'a> {
+    pub fn parse(text: &'a str) -> Result<Self, String> {
+        let Some(start) = text.find('[') else {
+            return Ok(Self {
+                name: text,
+                indices: Vec::new(),
+            });
+        };
+        let (name, mut rest) = text.split_at(start);
+        if name.is_empty() {
+            return Err("a value name must come before the loop variables".to_string());
+        }
+        let mut indices = Vec::new();
+        while !rest.is_empty() {
+            match rest.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+                Some((index, remaining)) if is_identifier(index.trim()) => {
+                    indices.push(index.trim());
+                    rest = remaining;
+                }
+                _ => {
+                    return Err(format!(
+                        "values can only be indexed by loop variables in brackets, e.g. '{name}[i]'"
+                    ))
+                }
+            }
+        }
+        Ok(Self { name, indices })
+    }
+
+    /// The current indices of the loop variables.
+    fn loop_indices(&self, loops: &LoopVariables) -> Vec<usize> {
+        self.indices
+            .iter()
+            .map(|index| {
+                loops
+                    .get(index)
+                    .expect("Loop variables are checked when parsing.")
+            })
+            .collect()
+    }
+}
+
+/// Indexes away the axes following the scope axes with the current indices of the loop variables.
+pub(super) fn index_loops<'a, A>(
+    mut array: ArrayViewD<'a, A>,
+    reference: &ValueReference,
+    loops: &LoopVariables,
+) -> ArrayViewD<'a, A> {
+    for index in reference.loop_indices(loops) {
+        array.index_axis_inplace(Axis(0), index);
+    }
+    array
+}
+
+/// Looks up a value referenced in a template and checks that the loops indexing it stay within its axes.
+/// Returns the value together with its shape after the scope and indexed axes.
+pub(super) fn indexed_value<'p>(
+    payload: &'p Payload,
+    reference: &ValueReference,
+    loops: &LoopVariables,
+) -> Result<(&'p Value, &'p [usize]), ArgumentErrorType> {
+    let value = payload
+        .value(reference.name)
+        .ok_or_else(|| ArgumentErrorType::MissingValue(reference.name.to_owned()))?;
+    let shape = &value.shape()[value.scope().num_axes()..];
+    if shape.len() < reference.indices.len() {
+        return Err(ArgumentErrorType::Other(format!(
+            "The value has {} axes after its scope, fewer than the {} loop variables indexing it.",
+            shape.len(),
+            reference.indices.len()
+        )));
+    }
+    for (axis, (&index, &size)) in reference.indices.iter().zip(shape).enumerate() {
+        let iterations = loops
+            .get(index)
+            .expect("Loop variables are checked when parsing.");
+        if iterations > size {
+            return Err(ArgumentErrorType::Other(format!(
+                "The loop over '{index}' runs up to {iterations} times, but axis {axis} of the value only has size {size}."
+            )));
+        }
+    }
+    Ok((value, &shape[reference.indices.len()..]))
+}
+
+/// Checks that a value referenced in a template can be shown on pages of the given scope, e.g. that the index page
+/// only shows `Global` values. Missing values are reported by the other checks.
+pub(super) fn check_scope(
+    payload: &Payload,
+    value_key: &str,
+    scope: Scope,
+) -> Result<(), ArgumentError> {
+    let name = ValueReference::parse(value_key).map_or(value_key, |reference| reference.name);
+    match payload.value(name) {
+        Some(value) if !value.scope().is_within(scope) => Err(ArgumentError {
+            error_type: ArgumentErrorType::Scope {
+                required_scope: scope,
+                found_scope: value.scope(),
+            },
+            value_name: value_key.to_owned(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// The numbers of a `U32` or `F32` value for the given neuron and loop indices.
+fn numbers(
+    value: &Value,
+    reference: &ValueReference,
+    loops: &LoopVariables,
+    layer_index: usize,
+    neuron_index: usize,
+) -> ArrayD<f32> {
+    match value.data_type() {
+        DataType::U32 => index_loops(
+            scope_slice(
+                value.as_u32().unwrap().view(),
+                value.scope(),
+                layer_index,
+                neuron_index,
+            ),
+            reference,
+            loops,
+        )
+        .mapv(|number| number as f32),
+        DataType::F32 => {
+            let array = value.neuron_f32(layer_index, neuron_index).unwrap();
+            index_loops(array.view(), reference, loops).to_owned()
+        }
+        _ => panic!("Only numbers can be compared. This should be guaranteed by validation."),
+    }
+}
+
+/// Checks that a referenced value holds numbers with the given number of axes after the scope and loop indices.
+pub(super) fn validate_numbers(
+    payload: &Payload,
+    value_key: &str,
+    loops: &LoopVariables,
+    required_axis_num: usize,
+) -> Result<(), ArgumentError> {
+    ValueReference::parse(value_key)
+        .map_err(ArgumentErrorType::Other)
+        .and_then(|reference| indexed_value(payload, &reference, loops))
+        .and_then(|(value, shape)| {
+            if shape.len() != required_axis_num {
+                Err(ArgumentErrorType::AxisNum {
+                    required_axis_num,
+                    found_axis_num: shape.len(),
+                })
+            } else if !matches!(value.data_type(), DataType::U32 | DataType::F32) {
+                Err(ArgumentErrorType::DataType {
+                    required_data_type: DataType::F32,
+                    found_data_type: value.data_type(),
+                })
+            } else {
+                Ok(())
+            }
+        })
+        .map_err(|error_type| ArgumentError {
+            error_type,
+            value_name: value_key.to_owned(),
+        })
+}
+
+/// The condition of an `$if` or `$elif` block, e.g. `exists(rank) and rank < 100`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Condition {
+    /// True if the payload has a value with the name.
+    Exists(String),
+    /// Compares `layer`, `neuron` or a number of a value, possibly indexed by loop variables, with a number.
+    Compare {
+        value: String,
+        comparison: Comparison,
+        number: f32,
+    },
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ConditionToken {
+    Word(String),
+    Comparison(Comparison),
+    OpenParen,
+    CloseParen,
+}
+
+fn tokenize_condition(condition: &str) -> Vec<ConditionToken> {
+    let mut tokens = Vec::new();
+    let mut characters = condition.chars().peekable();
+    while let Some(character) = characters.next() {
+        let token = match character {
+            character if character.is_whitespace() => continue,
+            '(' => ConditionToken::OpenParen,
+            ')' => ConditionToken::CloseParen,
+            '"' | '\'' => {
+                let mut quoted = character.to_string();
+                while let Some(next) = characters.next() {
+                    quoted.push(next);
+                    if next == '\\' {
+                        quoted.extend(characters.next());
+                    } else if next == character {
+                        break;
+                    }
+                }
+                ConditionToken::Word(unquote(&quoted))
+            }
+            '<' | '>' | '=' => {
+                let or_equal = characters.next_if_eq(&'=').is_some();
+                ConditionToken::Comparison(match (character, or_equal) {
+                    ('<', false) => Comparison::Less,
+                    ('<', true) => Comparison::LessOrEqual,
+                    ('>', false) => Comparison::Greater,
+                    ('>', true) => Comparison::GreaterOrEqual,
+                    _ => Comparison::Equal,
+                })
+            }
+            character => {
+                let mut word = character.to_string();
+                while let Some(character) = characters.next_if(|&character| {
+                    !character.is_whitespace()
+                        && !matches!(character, '(' | ')' | '<' | '>' | '=' | '"' | '\'')
+                }) {
+                    word.push(character);
+                }
+                ConditionToken::Word(word)
+            }
+        };
+        tokens.push(token);
+    }
+    tokens
+}
+
+struct ConditionParser {
+    tokens: std::iter::Peekable<std::vec::IntoIter<ConditionToken>>,
+}
+
+impl ConditionParser {
+    fn keyword(&mut self, keyword: &str) -> bool {
+        self.tokens
+            .next_if(|token| matches!(token, ConditionToken::Word(word) if word == keyword))
+            .is_some()
+    }
+
+    fn expect(&mut self, expected: ConditionToken, description: &str) -> Result<(), String> {
+        match self.tokens.next() {
+            Some(token) if token == expected => Ok(()),
+            _ => Err(format!("expected {description}")),
+        }
+    }
+
+    fn or(&mut self) -> Result<Condition, String> {
+        let mut condition = self.and()?;
+        while self.keyword("or") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, String> {
+        let mut condition = self.not()?;
+        while self.keyword("and") {
+            condition = Condition::And(Box::new(condition), Box::new(self.not()?));
+        }
+        Ok(condition)
+    }
+
+    fn not(&mut self) -> Result<Condition, String> {
+        if self.keyword("not") {
+            Ok(Condition::Not(Box::new(self.not()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Condition, String> {
+        match self.tokens.next() {
+            Some(ConditionToken::OpenParen) => {
+                let condition = self.or()?;
+                self.expect(ConditionToken::CloseParen, "')'")?;
+                Ok(condition)
+            }
+            Some(ConditionToken::Word(word)) if word == "exists" => {
+                self.expect(ConditionToken::OpenParen, "'(' after 'exists'")?;
+                let value = match self.tokens.next() {
+                    Some(ConditionToken::Word(value)) => value,
+                    _ => return Err("expected a value name in 'exists(...)'".to_string()),
+                };
+                self.expect(ConditionToken::CloseParen, "')' after the value name")?;
+                Ok(Condition::Exists(value))
+            }
+            Some(ConditionToken::Word(value)) => {
+                let Some(ConditionToken::Comparison(comparison)) = self.tokens.next() else {
+                    return Err(format!(
+                        "expected a comparison like '{value} < 100' or 'exists({value})'"
+                    ));
+                };
+                match self.tokens.next() {
+                    Some(ConditionToken::Word(number)) => {
+                        let number = number
+                            .parse()
+                            .map_err(|_| format!("'{number}' is not a number"))?;
+                        Ok(Condition::Compare {
+                            value,
+                            comparison,
+                            number,
+                        })
+                    }
+                    _ => Err(format!("expected a number to compare '{value}' with")),
+                }
+            }
+            _ => Err("expected a comparison, 'exists(...)', 'not' or '('".to_string()),
+        }
+    }
+}
+
+impl Condition {
+    /// Parses a condition of comparisons like `rank < 100` and `exists(value_name)`, combined with `not`, `and`, `or`
+    /// and parentheses. Comparisons are `<`, `<=`, `=`, `==`, `>=` and `>`. Value names can be quoted, e.g.
+    /// `exists("my value")`.
+    pub(super) fn parse(condition: &str) -> Result<Self, String> {
+        let mut parser = ConditionParser {
+            tokens: tokenize_condition(condition).into_iter().peekable(),
+        };
+        let condition = parser.or()?;
+        match parser.tokens.next() {
+            None => Ok(condition),
+            Some(_) => Err("expected 'and', 'or' or the end of the condition".to_string()),
+        }
+    }
+
+    /// The values compared by the condition, which may be indexed by loop variables.
+    pub(super) fn compared_values(&self) -> Vec<&str> {
+        match self {
+            Condition::Exists(_) => Vec::new(),
+            Condition::Compare { value, .. } if is_neuron_index(value) => Vec::new(),
+            Condition::Compare { value, .. } => vec![value],
+            Condition::Not(condition) => condition.compared_values(),
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                let mut values = left.compared_values();
+                values.extend(right.compared_values());
+                values
+            }
+        }
+    }
+
+    /// Whether the condition checks which values exist.
+    pub(super) fn checks_existence(&self) -> bool {
+        match self {
+            Condition::Exists(_) => true,
+            Condition::Compare { .. } => false,
+            Condition::Not(condition) => condition.checks_existence(),
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                left.checks_existence() || right.checks_existence()
+            }
+        }
+    }
+
+    /// Checks the compared values on pages of the given scope. Returns the result of the condition if it is the same
+    /// for every page, which is the case if it only depends on which values exist. Like evaluation, `and` and `or`
+    /// stop at the first operand deciding the result, so `exists(rank) and rank < 100` is valid without a value
+    /// named `rank`.
+    pub(super) fn validate(
+        &self,
+        payload: &Payload,
+        scope: Scope,
+        loops: &LoopVariables,
+    ) -> Result<Option<bool>, ArgumentError> {
+        match self {
+            Condition::Exists(value) => Ok(Some(payload.value(value).is_some())),
+            Condition::Compare { value, .. } if is_neuron_index(value) => {
+                let required_scope = if value == "layer" {
+                    Scope::Layer
+                } else {
+                    Scope::Neuron
+                };
+                if required_scope.is_within(scope) {
+                    Ok(None)
+                } else {
+                    Err(ArgumentError {
+                        error_type: ArgumentErrorType::Other(format!(
+                            "Only pages of a {} have a '{value}' to compare.",
+                            required_scope.to_string().to_lowercase()
+                        )),
+                        value_name: value.clone(),
+                    })
+                }
+            }
+            Condition::Compare { value, .. } => {
+                validate_numbers(payload, value, loops, 0)?;
+                check_scope(payload, value, scope)?;
+                Ok(None)
+            }
+            Condition::Not(condition) => Ok(condition
+                .validate(payload, scope, loops)?
+                .map(|result| !result)),
+            Condition::And(left, right) => match left.validate(payload, scope, loops)? {
+                Some(false) => Ok(Some(false)),
+                Some(true) => right.validate(payload, scope, loops),
+                None => Ok(right
+                    .validate(payload, scope, loops)?
+                    .filter(|&result| !result)),
+            },
+            Condition::Or(left, right) => match left.validate(payload, scope, loops)? {
+                Some(true) => Ok(Some(true)),
+                Some(false) => right.validate(payload, scope, loops),
+                None => Ok(right
+                    .validate(payload, scope, loops)?
+                    .filter(|&result| result)),
+            },
+        }
+    }
+
+    pub(super) fn evaluate(
+        &self,
+        payload: &Payload,
+        loops: &LoopVariables,
+        layer_index: usize,
+        neuron_index: usize,
+    ) -> bool {
+        match self {
+            Condition::Exists(value) => payload.value(value).is_some(),
+            Condition::Compare {
+                value,
+                comparison,
+                number,
+            } => {
+                let compared = match value.as_str() {
+                    "layer" => payload.original_layer_index(layer_index) as f32,
+                    "neuron" => payload.original_neuron_index(neuron_index) as f32,
+                    value => {
+                        let reference = ValueReference::parse(value).unwrap();
+                        let numbers = numbers(
+                            payload.value(reference.name).unwrap(),
+                            &reference,
+                            loops,
+                            layer_index,
+                            neuron_index,
+                        );
+                        *numbers.first().unwrap()
+                    }
+                };
+                comparison.compare(compared, *number)
+            }
+            Condition::Not(condition) => {
+                !condition.evaluate(payload, loops, layer_index, neuron_index)
+            }
+            Condition::And(left, right) => {
+                left.evaluate(payload, loops, layer_index, neuron_index)
+                    && right.evaluate(payload, loops, layer_index, neuron_index)
+            }
+            Condition::Or(left, right) => {
+                left.evaluate(payload, loops, layer_index, neuron_index)
+                    || right.evaluate(payload, loops, layer_index, neuron_index)
+            }
+        }
+    }
+}
+
+/// `layer` and `neuron` compare the original indices of the page's neuron.
+fn is_neuron_index(value: &str) -> bool {
+    value == "layer" || value == "neuron"
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+impl SortOrder {
+    /// Reads the `sort` keyword argument of a call, which is `"ascending"` or `"descending"` if given.
+    pub(super) fn from_options(
+        call: &ElementCall,
+        options: &KeywordArguments,
+    ) -> ParseResult<Option<Self>> {
+        match options.string("sort") {
+            None => Ok(None),
+            Some("ascending") => Ok(Some(SortOrder::Ascending)),
+            Some("descending") => Ok(Some(SortOrder::Descending)),
+            Some(_) => Err(invalid_argument_error(
+                call,
+                options.argument("sort"),
+                "'sort' must be \"ascending\" or \"descending\"",
+            )),
+        }
+    }
+}
+
+const FOR_SCHEMA: ElementSchema = ElementSchema {
+    name: "for",
+    positional: &["variable", "value"],
+    keywords: &[
+        keyword("limit", ArgumentType::Integer),
+        keyword("sort", ArgumentType::String),
+    ],
+};
+
+/// A `$for` block repeating its body for indices along the first axis of a value after its scope,
+/// e.g. `$for(i, neighbour_scores, limit=5)`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Loop {
+    variable: String,
+    value: String,
+    limit: Option<usize>,
+    /// Visits the indices ordered by the numbers of the value instead of in order.
+    sort: Option<SortOrder>,
+}
+
+impl Loop {
+    pub(super) fn from_call(call: &ElementCall) -> ParseResult<Self> {
+        let ([variable, value], options) = schema_arguments(call, &FOR_SCHEMA)?;
+        if !is_identifier(&variable) {
+            return Err(invalid_argument_error(
+                call,
+                &call.arguments[0].value,
+                "the loop variable must be a name like 'i'",
+            ));
+        }
+        let sort = SortOrder::from_options(call, &options)?;
+        Ok(Self {
+            variable,
+            value,
+            limit: options.integer("limit"),
+            sort,
+        })
+    }
+
+    pub fn variable(&self) -> &str {
+        &self.variable
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn sort(&self) -> Option<SortOrder> {
+        self.sort
+    }
+
+    /// Checks the looped over value. Returns the largest number of iterations of the loop.
+    pub(super) fn validate(
+        &self,
+        payload: &Payload,
+        loops: &LoopVariables,
+    ) -> Result<usize, ArgumentError> {
+        let to_argument_error = |error_type| ArgumentError {
+            error_type,
+            value_name: self.value.clone(),
+        };
+        if self.sort.is_some() {
+            validate_numbers(payload, &self.value, loops, 1)?;
+        }
+        let reference = ValueReference::parse(&self.value)
+            .map_err(|reason| to_argument_error(ArgumentErrorType::Other(reason)))?;
+        let (_, shape) = indexed_value(payload, &reference, loops).map_err(to_argument_error)?;
+        let Some(&length) = shape.first() else {
+            return Err(to_argument_error(ArgumentErrorType::Other(
+                "A loop needs a value with at least one axis after its scope and loop indices."
+                    .to_string(),
+            )));
+        };
+        Ok(self.limit.map_or(length, |limit| limit.min(length)))
+    }
+
+    /// The indices visited by the loop for the given neuron.
+    pub(super) fn indices(
+        &self,
+        payload: &Payload,
+        loops: &LoopVariables,
+        layer_index: usize,
+        neuron_index: usize,
+    ) -> Vec<usize> {
+        let reference = ValueReference::parse(&self.value).unwrap();
+        let value = payload.value(reference.name).unwrap();
+        let mut indices: Vec<usize> = match self.sort {
+            None => {
+                let length = value.shape()[value.scope().num_axes() + reference.indices.len()];
+                (0..length).collect()
+            }
+            Some(order) => {
+                let numbers = numbers(value, &reference, loops, layer_index, neuron_index);
+                let mut indices: Vec<usize> = (0..numbers.len()).collect();
+                indices.sort_by(|&a, &b| match order {
+                    SortOrder::Ascending => numbers[a].total_cmp(&numbers[b]),
+                    SortOrder::Descending => numbers[b].total_cmp(&numbers[a]),
+                });
+                indices
+            }
+        };
+        if let Some(limit) = self.limit {
+            indices.truncate(limit);
+        }
+        indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{arr0, Array2, Array3};
+
+    use super::{Condition, Loop, LoopVariables};
+    use crate::{
+        data::{value::Scope, Comparison, Value},
+        html::template::{parser::parse_template, ArgumentErrorType, NeuronTemplate},
+        Payload, PayloadBuilder,
+    };
+
+    fn compare(value: &str, comparison: Comparison, number: f32) -> Box<Condition> {
+        Box::new(Condition::Compare {
+            value: value.to_string(),
+            comparison,
+            number,
+        })
+    }
+
+    /// A payload of 2 layers with 3 neurons with the `Neuron` scoped value `scores` of 4 numbers per neuron.
+    fn payload() -> Payload {
+        let mut builder = PayloadBuilder::new(2, 3);
+        builder
+            .mlp_neuron_template(NeuronTemplate::parse("$value(title)").unwrap())
+            .unwrap();
+        builder.add_value(
+            "title",
+            Value::new(arr0("model".to_string()), Scope::Global),
+        );
+        builder.add_value(
+            "scores",
+            Value::new(
+                Array3::from_shape_fn((2, 3, 4), |(layer, neuron, index)| {
+                    [0.5, 3.0, -1.0, 2.0][index] * (layer * 3 + neuron) as f32
+                }),
+                Scope::Neuron,
+            ),
+        );
+        builder.add_value(
+            "stds",
+            Value::new(Array2::<f32>::zeros((2, 3)), Scope::Neuron),
+        );
+        builder.set_rank_values("stds");
+        builder.build().unwrap()
+    }
+
+    fn validate(condition: &str, scope: Scope) -> Result<Option<bool>, ArgumentErrorType> {
+        Condition::parse(condition)
+            .unwrap()
+            .validate(&payload(), scope, &LoopVariables::default())
+            .map_err(|error| error.error_type)
+    }
+
+    fn parse_loop(template: &str) -> Loop {
+        Loop::from_call(&parse_template(template).unwrap().parts[0].0).unwrap()
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and_which_binds_tighter_than_or() {
+        assert_eq!(
+            Condition::parse("a < 1 or not b >= 2 and c == 3").unwrap(),
+            Condition::Or(
+                compare("a", Comparison::Less, 1.0),
+                Box::new(Condition::And(
+                    Box::new(Condition::Not(compare(
+                        "b",
+                        Comparison::GreaterOrEqual,
+                        2.0
+                    ))),
+                    compare("c", Comparison::Equal, 3.0),
+                )),
+            )
+        );
+        assert_eq!(
+            Condition::parse("(a < 1 or exists(\"my value\")) and b > 2").unwrap(),
+            Condition::And(
+                Box::new(Condition::Or(
+                    compare("a", Comparison::Less, 1.0),
+                    Box::new(Condition::Exists("my value".to_string())),
+                )),
+                compare("b", Comparison::Greater, 2.0),
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_conditions_are_rejected() {
+        for (condition, error) in [
+            ("a <", "expected a number to compare 'a' with"),
+            ("a < b", "'b' is not a number"),
+            ("a", "expected a comparison like 'a < 100' or 'exists(a)'"),
+            ("(a < 1", "expected ')'"),
+            ("exists(a", "expected ')' after the value name"),
+            (
+                "a < 1 b < 2",
+                "expected 'and', 'or' or the end of the condition",
+            ),
+            ("", "expected a comparison, 'exists(...)', 'not' or '('"),
+        ] {
+            assert_eq!(Condition::parse(condition).unwrap_err(), error);
+        }
+    }
+
+    #[test]
+    fn conditions_deciding_every_page_are_evaluated_while_validating() {
+        assert_eq!(
+            validate("exists(scores)", Scope::Neuron).unwrap(),
+            Some(true)
+        );
+        assert_eq!(
+            validate("not exists(scores)", Scope::Neuron).unwrap(),
+            Some(false)
+        );
+        assert_eq!(validate("stds < 1", Scope::Neuron).unwrap(), None);
+        assert_eq!(
+            validate("stds < 1 and exists(missing)", Scope::Neuron).unwrap(),
+            Some(false)
+        );
+        assert_eq!(
+            validate("stds < 1 or exists(scores)", Scope::Neuron).unwrap(),
+            Some(true)
+        );
+        assert_eq!(
+            validate("stds < 1 and exists(scores)", Scope::Neuron).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn validation_stops_at_the_operand_deciding_the_result() {
+        assert_eq!(
+            validate("exists(missing) and missing < 1", Scope::Neuron).unwrap(),
+            Some(false)
+        );
+        assert_eq!(
+            validate("exists(scores) or missing < 1", Scope::Neuron).unwrap(),
+            Some(true)
+        );
+        assert!(matches!(
+            validate("exists(scores) and missing < 1", Scope::Neuron),
+            Err(ArgumentErrorType::MissingValue(name)) if name == "missing"
+        ));
+        assert!(matches!(
+            validate("exists(missing) or layer < 1", Scope::Global),
+            Err(ArgumentErrorType::Other(_))
+        ));
+        assert_eq!(
+            validate("exists(missing) or layer < 1", Scope::Layer).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn loops_visit_sorted_indices_up_to_the_limit() {
+        let payload = payload();
+        let loops = LoopVariables::default();
+        // The scores of layer 1, neuron 2 are [2.5, 15, -5, 10].
+        let indices = |template| parse_loop(template).indices(&payload, &loops, 1, 2);
+        assert_eq!(indices("$for(i, scores)"), [0, 1, 2, 3]);
+        assert_eq!(indices("$for(i, scores, limit=2)"), [0, 1]);
+        assert_eq!(indices("$for(i, scores, sort=\"ascending\")"), [2, 0, 3, 1]);
+        assert_eq!(
+            indices("$for(i, scores, sort=\"descending\", limit=2)"),
+            [1, 3]
+        );
+
+        let validate = |template| parse_loop(template).validate(&payload, &loops).unwrap();
+        assert_eq!(validate("$for(i, scores, limit=2)"), 2);
+        assert_eq!(validate("$for(i, scores, sort=\"ascending\", limit=10)"), 4);
+    }
+
+    #[test]
+    fn only_numbers_with_one_axis_can_be_sorted() {
+        let loop_ = parse_loop("$for(i, title, sort=\"ascending\")");
+        let error = loop_
+            .validate(&payload(), &LoopVariables::default())
+            .unwrap_err();
+        assert!(matches!(
+            error.error_type,
+            ArgumentErrorType::AxisNum {
+                required_axis_num: 1,
+                found_axis_num: 0
+            }
+        ));
+    }
+}
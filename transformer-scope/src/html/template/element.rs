@@ -1,176 +1,1378 @@
 use maud::{html, Markup};
-use ndarray::{s, Ix2};
+use ndarray::{s, Array1, ArrayView2, ArrayViewD, Axis, Ix1, Ix2};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    data::value::{DataType, Scope},
-    html::{focus_sequences, heatmap},
+    data::{
+        value::{DataType, NumberStatistics, Scope, ValueView},
+        ConnectionKeys, Value, NO_CONNECTION,
+    },
+    html::{
+        focus_sequences, heatmap, histogram, image, index, layer, neuron_links, search, statistics,
+        token_snippets, top_k, CellText, ColorScale, Colormap, Normalization, Rendering,
+    },
     Payload,
 };
 
-use super::{ArgumentError, ArgumentErrorType};
+use super::{
+    control_flow::{
+        check_scope, index_loops, indexed_value, validate_numbers, LoopVariables, SortOrder,
+        ValueReference, BLOCK_NAMES,
+    },
+    parser::{self, ElementCall, ParseResult, Spanned, TemplateParseErrorKind},
+    payload_schema::ValueRequirement,
+    schema::{self, ArgumentValue, ElementSchema, KeywordArguments, ELEMENT_SCHEMAS},
+    tree::Page,
+    ArgumentError, ArgumentErrorType,
+};
+
+/// Indexes away the scope axes of a value.
+pub(super) fn scope_slice<A>(
+    mut array: ArrayViewD<A>,
+    scope: Scope,
+    layer_index: usize,
+    neuron_index: usize,
+) -> ArrayViewD<A> {
+    for index in scope.index(layer_index, neuron_index) {
+        array.index_axis_inplace(Axis(0), index);
+    }
+    array
+}
+
+/// Slices away the scope axes of a value with two axes per scope.
+fn scope_slice_2d<A>(
+    array: ArrayViewD<A>,
+    scope: Scope,
+    layer_index: usize,
+    neuron_index: usize,
+) -> ArrayView2<A> {
+    scope_slice(array, scope, layer_index, neuron_index)
+        .into_dimensionality()
+        .unwrap()
+}
+
+/// The shape of a value after the scope axes.
+fn scoped_shape(value: &Value) -> &[usize] {
+    &value.shape()[value.scope().num_axes()..]
+}
+
+/// Checks that a value containing token ids can be shown as text using the payload vocabulary.
+fn validate_token_ids(value: &Value, payload: &Payload) -> Result<(), ArgumentErrorType> {
+    let vocabulary = payload
+        .vocabulary()
+        .ok_or(ArgumentErrorType::MissingVocabulary)?;
+    let token_ids = value.as_u32().unwrap();
+    match token_ids.iter().copied().max() {
+        Some(token_id) if token_id as usize >= vocabulary.len() => {
+            Err(ArgumentErrorType::TokenOutOfRange {
+                token_id,
+                vocabulary_size: vocabulary.len(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a value holds a label for each of the `size` rows or columns of a heatmap, as strings or token ids.
+fn validate_labels(
+    payload: &Payload,
+    labels_key: &str,
+    size: usize,
+    axis_name: &str,
+) -> Result<(), ArgumentError> {
+    payload
+        .value(labels_key)
+        .ok_or_else(|| ArgumentErrorType::MissingValue(labels_key.to_owned()))
+        .and_then(|labels| {
+            let labels_shape = scoped_shape(labels);
+            if labels_shape.len() != 1 {
+                Err(ArgumentErrorType::AxisNum {
+                    required_axis_num: 1,
+                    found_axis_num: labels_shape.len(),
+                })
+            } else if labels_shape[0] != size {
+                Err(ArgumentErrorType::Other(format!(
+                    "The heatmap has {size} {axis_name}, but there are {} labels.",
+                    labels_shape[0]
+                )))
+            } else {
+                validate_label_data_type(labels, payload)
+            }
+        })
+        .map_err(|error_type| ArgumentError {
+            error_type,
+            value_name: labels_key.to_owned(),
+        })
+}
+
+/// Checks that a value holds labels as strings or as token ids in the vocabulary.
+fn validate_label_data_type(labels: &Value, payload: &Payload) -> Result<(), ArgumentErrorType> {
+    match labels.data_type() {
+        DataType::String => Ok(()),
+        DataType::U32 => validate_token_ids(labels, payload),
+        found_data_type => Err(ArgumentErrorType::DataType {
+            required_data_type: DataType::String,
+            found_data_type,
+        }),
+    }
+}
+
+/// The labels of the rows or columns of a heatmap, or of the numbers of 'top_k', as text, with token ids looked up in
+/// the vocabulary.
+fn labels(
+    payload: &Payload,
+    labels_key: &str,
+    layer_index: usize,
+    neuron_index: usize,
+) -> Vec<String> {
+    let labels = payload.value(labels_key).unwrap();
+    match labels.view() {
+        ValueView::String(strings) => {
+            scope_slice(strings, labels.scope(), layer_index, neuron_index)
+                .iter()
+                .cloned()
+                .collect()
+        }
+        ValueView::U32(token_ids) => {
+            let vocabulary = payload
+                .vocabulary()
+                .expect("Token ids require a vocabulary. This should be guaranteed by validation.");
+            scope_slice(token_ids, labels.scope(), layer_index, neuron_index)
+                .iter()
+                .map(|&token_id| vocabulary.token(token_id).unwrap().to_owned())
+                .collect()
+        }
+        _ => {
+            panic!("Labels must be strings or token ids. This should be guaranteed by validation.")
+        }
+    }
+}
+
+/// The tokens of the vocabulary labelling the entries of the last axis of a value of the given shape. Entries of
+/// matrices are labelled by their row as well, e.g. `the [3]`.
+fn vocabulary_labels(payload: &Payload, shape: &[usize]) -> Vec<String> {
+    let tokens = payload
+        .vocabulary()
+        .expect("Vocabulary labels require a vocabulary. This should be guaranteed by validation.")
+        .tokens();
+    match shape {
+        [_] => tokens.to_vec(),
+        _ => (0..shape[0])
+            .flat_map(|row_index| {
+                tokens
+                    .iter()
+                    .map(move |token| format!("{token} [{row_index}]"))
+            })
+            .collect(),
+    }
+}
+
+/// Checks that two values can be shown as links to neurons with scores, as done by the elements 'neighbours' and 'connections'.
+fn validate_neuron_links(
+    payload: &Payload,
+    neighbours_key: &str,
+    scores_key: &str,
+    element_name: &str,
+) -> Result<(), ArgumentError> {
+    let neighbours = payload
+        .value(neighbours_key)
+        .ok_or_else(|| ArgumentErrorType::MissingValue(neighbours_key.to_owned()))
+        .and_then(|neighbours| {
+            let neighbours_shape = scoped_shape(neighbours);
+            if neighbours_shape.len() != 2 {
+                Err(ArgumentErrorType::AxisNum {
+                    required_axis_num: 2,
+                    found_axis_num: neighbours_shape.len(),
+                })
+            } else if neighbours_shape[1] != 2 {
+                Err(ArgumentErrorType::Other(format!(
+                    "The last axis of the first argument to the element '{element_name}' \
+                     must contain a layer and a neuron index, but has size {}.",
+                    neighbours_shape[1]
+                )))
+            } else if neighbours.data_type() != DataType::U32 {
+                Err(ArgumentErrorType::DataType {
+                    required_data_type: DataType::U32,
+                    found_data_type: neighbours.data_type(),
+                })
+            } else {
+                Ok(neighbours)
+            }
+        })
+        .map_err(|error_type| ArgumentError {
+            error_type,
+            value_name: neighbours_key.to_owned(),
+        })?;
+
+    payload
+        .value(scores_key)
+        .ok_or_else(|| ArgumentErrorType::MissingValue(scores_key.to_owned()))
+        .and_then(|scores| {
+            let scores_shape = scoped_shape(scores);
+            if scores_shape.len() != 1 {
+                Err(ArgumentErrorType::AxisNum {
+                    required_axis_num: 1,
+                    found_axis_num: scores_shape.len(),
+                })
+            } else if scores_shape[0] != scoped_shape(neighbours)[0] {
+                Err(ArgumentErrorType::Other(format!(
+                    "The element '{element_name}' needs a score for every neighbour, \
+                     but there are {} neighbours and {} scores.",
+                    scoped_shape(neighbours)[0],
+                    scores_shape[0]
+                )))
+            } else if scores.data_type() != DataType::F32 {
+                Err(ArgumentErrorType::DataType {
+                    required_data_type: DataType::F32,
+                    found_data_type: scores.data_type(),
+                })
+            } else {
+                Ok(())
+            }
+        })
+        .map_err(|error_type| ArgumentError {
+            error_type,
+            value_name: scores_key.to_owned(),
+        })
+}
+
+/// Checks that a value holds a number for every neuron, as shown by the elements 'layer_heatmap' and 'top_neurons'.
+fn validate_neuron_numbers(payload: &Payload, value_key: &str) -> Result<(), ArgumentError> {
+    validate_numbers(payload, value_key, &LoopVariables::default(), 0)?;
+    let scope = payload.value(value_key).unwrap().scope();
+    if scope != Scope::Neuron {
+        return Err(ArgumentError {
+            error_type: ArgumentErrorType::Scope {
+                required_scope: Scope::Neuron,
+                found_scope: scope,
+            },
+            value_name: value_key.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// The number of every neuron in a layer, of a `Neuron` scoped value without further axes.
+fn layer_numbers(payload: &Payload, value_key: &str, layer_index: usize) -> Array1<f32> {
+    payload
+        .value(value_key)
+        .unwrap()
+        .numbers_f32(&[layer_index])
+        .expect("Only numbers are shown for every neuron. This should be guaranteed by validation.")
+        .into_dimensionality::<Ix1>()
+        .unwrap()
+}
 
+/// The colours chosen by the keyword arguments of an element showing numbers of `value`.
+/// Bounds which are not given are taken from the statistics of the whole value, or of the `shown` numbers with
+/// `norm="auto"`.
+fn color_scale<'a>(
+    options: &KeywordArguments,
+    value: &Value,
+    shown: impl IntoIterator<Item = &'a f32>,
+) -> ColorScale {
+    let colormap = options
+        .string("colormap")
+        .map_or_else(Colormap::default, |name| {
+            Colormap::from_name(name).expect("Checked when parsing.")
+        });
+    let normalization = options
+        .string("norm")
+        .map_or(Normalization::Linear, |name| {
+            Normalization::from_name(name).expect("Checked when parsing.")
+        });
+    let statistics = match normalization {
+        Normalization::Auto => NumberStatistics::of(shown),
+        _ => value.statistics().or_else(|| NumberStatistics::of(shown)),
+    };
+    ColorScale::new(
+        colormap,
+        normalization,
+        options.number("vmin").map(|vmin| vmin as f32),
+        options.number("vmax").map(|vmax| vmax as f32),
+        statistics,
+    )
+}
+
+/// Checks the keyword arguments `colormap`, `norm`, `vmin` and `vmax` of an element showing numbers as colours.
+fn validate_color_options(call: &ElementCall, options: &KeywordArguments) -> ParseResult<()> {
+    if let Some(name) = options.string("colormap") {
+        if Colormap::from_name(name).is_none() {
+            let names: Vec<_> = Colormap::ALL
+                .iter()
+                .map(|colormap| colormap.name())
+                .collect();
+            let reason = match parser::suggestions(name, &names).as_slice() {
+                [] => format!(
+                    "unknown colormap, expected one of {}",
+                    names
+                        .iter()
+                        .map(|name| format!("\"{name}\""))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                suggestions => format!("unknown colormap{}", parser::did_you_mean(suggestions)),
+            };
+            return Err(invalid_argument_error(
+                call,
+                options.argument("colormap"),
+                &reason,
+            ));
+        }
+    }
+    let normalization = match options.string("norm") {
+        None => Normalization::Linear,
+        Some(name) => Normalization::from_name(name).ok_or_else(|| {
+            invalid_argument_error(
+                call,
+                options.argument("norm"),
+                "'norm' must be \"linear\", \"symmetric\", \"log\" or \"auto\"",
+            )
+        })?,
+    };
+    if let (Some(vmin), Some(vmax)) = (options.number("vmin"), options.number("vmax")) {
+        if vmin as f32 >= vmax as f32 {
+            return Err(invalid_argument_error(
+                call,
+                options.argument("vmax"),
+                &format!("'vmin' must be less than 'vmax', but they are {vmin} and {vmax}"),
+            ));
+        }
+    }
+    if normalization == Normalization::Log {
+        for name in ["vmin", "vmax"] {
+            if options.number(name).is_some_and(|bound| bound <= 0.) {
+                return Err(invalid_argument_error(
+                    call,
+                    options.argument(name),
+                    &format!("'{name}' must be positive with a logarithmic 'norm'"),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the keyword argument `render` of an element showing a matrix, and returns how it is drawn.
+fn validate_rendering(call: &ElementCall, options: &KeywordArguments) -> ParseResult<Rendering> {
+    match options.string("render") {
+        None => Ok(Rendering::Table),
+        Some(name) => Rendering::from_name(name).ok_or_else(|| {
+            invalid_argument_error(
+                call,
+                options.argument("render"),
+                "'render' must be \"table\", \"svg\" or \"png\"",
+            )
+        }),
+    }
+}
+
+fn rendering(options: &KeywordArguments) -> Rendering {
+    options.string("render").map_or(Rendering::Table, |name| {
+        Rendering::from_name(name).expect("Checked when parsing.")
+    })
+}
+
+/// Stores a matrix as a PNG image of the page and shows it. Empty matrices only show the legend, since images
+/// cannot be empty.
+fn png_matrix(page: &Page, values: ArrayView2<f32>, color_scale: &ColorScale) -> Markup {
+    let Some(png) = image::matrix_png(values, color_scale) else {
+        return color_scale.legend();
+    };
+    let index = page.images.add(png);
+    image::png_image(
+        &page.image_url(index),
+        values.nrows(),
+        values.ncols(),
+        color_scale,
+    )
+}
+
+/// Shows focus sequences as a table, or as an image with the step names in the cells of SVG images.
+fn draw_focus_sequences<S: AsRef<str>>(
+    page: &Page,
+    activations: ArrayView2<f32>,
+    step_names: ArrayView2<S>,
+    color_scale: &ColorScale,
+    rendering: Rendering,
+) -> Markup {
+    assert_eq!(activations.shape(), step_names.shape());
+    match rendering {
+        Rendering::Table => focus_sequences::focus_sequences(activations, step_names, color_scale),
+        Rendering::Svg => {
+            let sequence_numbers: Vec<String> = (1..=activations.nrows())
+                .map(|number| number.to_string())
+                .collect();
+            let step_numbers: Vec<String> = (1..=activations.ncols())
+                .map(|number| number.to_string())
+                .collect();
+            image::matrix_svg(
+                activations,
+                color_scale,
+                Some(&sequence_numbers),
+                Some(&step_numbers),
+                Some(step_names),
+            )
+        }
+        Rendering::Png => png_matrix(page, activations, color_scale),
+    }
+}
+
+const DEFAULT_HISTOGRAM_BINS: usize = 20;
+const DEFAULT_TOP_K: usize = 10;
+/// The labels of 'top_k' naming the payload vocabulary instead of a value, to label the entries of the last axis by
+/// their token ids. No value may have this name.
+pub(super) const VOCABULARY_LABELS: &str = "vocabulary";
+const DEFAULT_LAYER_HEATMAP_COLUMNS: usize = 16;
+const DEFAULT_TOP_NEURONS_LIMIT: usize = 10;
+
+pub(super) fn argument_count_error(
+    call: &ElementCall,
+    expected: &str,
+    found: usize,
+) -> Spanned<TemplateParseErrorKind> {
+    Spanned {
+        value: TemplateParseErrorKind::ArgumentCount {
+            element: call.name.value.clone(),
+            expected: expected.to_string(),
+            found,
+        },
+        offset: call.name.offset,
+    }
+}
+
+pub(super) fn invalid_argument_error(
+    call: &ElementCall,
+    argument: &Spanned<String>,
+    reason: &str,
+) -> Spanned<TemplateParseErrorKind> {
+    Spanned {
+        value: TemplateParseErrorKind::InvalidArgument {
+            element: call.name.value.clone(),
+            argument: argument.value.clone(),
+            reason: reason.to_string(),
+        },
+        offset: argument.offset,
+    }
+}
+
+/// Checks the arguments of a call against the schema of the element.
+/// Returns the values of the `N` positional arguments followed by the typed keyword arguments, including those given
+/// positionally after the value names.
+pub(super) fn schema_arguments<const N: usize>(
+    call: &ElementCall,
+    schema: &ElementSchema,
+) -> ParseResult<([String; N], KeywordArguments)> {
+    debug_assert_eq!(schema.positional.len(), N);
+    let mut positional = Vec::new();
+    let mut keywords = KeywordArguments::default();
+    let mut positional_keywords = schema.positional_keywords();
+    let mut seen_keyword = false;
+    for argument in &call.arguments {
+        let Some(name) = &argument.name else {
+            if seen_keyword {
+                return Err(invalid_argument_error(
+                    call,
+                    &argument.value,
+                    "positional arguments must come before keyword arguments",
+                ));
+            }
+            if positional.len() < N {
+                positional.push(argument.value.value.clone());
+                continue;
+            }
+            let Some(keyword) = positional_keywords.next() else {
+                return Err(argument_count_error(
+                    call,
+                    &expected_argument_count(schema),
+                    call.arguments
+                        .iter()
+                        .filter(|argument| argument.name.is_none())
+                        .count(),
+                ));
+            };
+            let value = ArgumentValue::parse(&argument.value.value, keyword.argument_type)
+                .ok_or_else(|| {
+                    invalid_argument_error(
+                        call,
+                        &argument.value,
+                        &format!("'{}' must be {}", keyword.name, keyword.argument_type),
+                    )
+                })?;
+            keywords.insert(keyword.name, value, &argument.value);
+            continue;
+        };
+        seen_keyword = true;
+        let keyword = schema.keyword(&name.value).ok_or_else(|| {
+            let reason = match parser::suggestions(&name.value, &schema.keyword_names()).as_slice()
+            {
+                [] => format!(
+                    "unknown keyword argument, expected one of {}",
+                    schema
+                        .keyword_names()
+                        .iter()
+                        .map(|name| format!("'{name}'"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                suggestions => format!(
+                    "unknown keyword argument{}",
+                    parser::did_you_mean(suggestions)
+                ),
+            };
+            invalid_argument_error(call, name, &reason)
+        })?;
+        let value = ArgumentValue::parse(&argument.value.value, keyword.argument_type).ok_or_else(
+            || {
+                invalid_argument_error(
+                    call,
+                    &argument.value,
+                    &format!("'{}' must be {}", keyword.name, keyword.argument_type),
+                )
+            },
+        )?;
+        if keywords
+            .insert(keyword.name, value, &argument.value)
+            .is_some()
+        {
+            return Err(invalid_argument_error(
+                call,
+                name,
+                "the keyword argument is given more than once",
+            ));
+        }
+    }
+    let found = positional.len();
+    let positional = positional
+        .try_into()
+        .map_err(|_| argument_count_error(call, &expected_argument_count(schema), found))?;
+    Ok((positional, keywords))
+}
+
+/// The number of positional arguments an element accepts, for errors.
+fn expected_argument_count(schema: &ElementSchema) -> String {
+    let required = schema.positional.len();
+    match (required, schema.positional_keywords().count()) {
+        (1, 0) => "1 argument".to_string(),
+        (required, 0) => format!("{required} arguments"),
+        (required, optional) => format!("{required} to {} arguments", required + optional),
+    }
+}
+
+/// Lists the connections of a neuron in one direction, skipping unused connection slots.
+fn connection_links(
+    payload: &Payload,
+    neurons_key: &str,
+    weights_key: &str,
+    file: bool,
+    layer_index: usize,
+    neuron_index: usize,
+) -> Markup {
+    let neurons_value = payload.value(neurons_key).unwrap();
+    let neurons = scope_slice_2d(
+        neurons_value.as_u32().unwrap().view(),
+        neurons_value.scope(),
+        layer_index,
+        neuron_index,
+    );
+    let weights = payload
+        .value(weights_key)
+        .unwrap()
+        .neuron_f32(layer_index, neuron_index)
+        .unwrap()
+        .into_dimensionality::<Ix1>()
+        .unwrap();
+    let num_connections = neurons
+        .rows()
+        .into_iter()
+        .take_while(|neuron| neuron[0] != NO_CONNECTION)
+        .count();
+    if num_connections == 0 {
+        html! { p {"None."} }
+    } else {
+        neuron_links::neuron_links(
+            payload,
+            neurons.slice(s![..num_connections, ..]),
+            weights.slice(s![..num_connections]),
+            file,
+        )
+    }
+}
+
+/// An element of a template. Every element holds the names of the values it shows, followed by its keyword arguments.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Element {
-    Heatmap(String),
-    Value(String),
+    Heatmap {
+        value: String,
+        options: KeywordArguments,
+    },
+    Value {
+        value: String,
+        options: KeywordArguments,
+    },
     FocusSequences {
         activations: String,
         step_names: String,
+        options: KeywordArguments,
+    },
+    TokenSnippets {
+        snippets: String,
+        options: KeywordArguments,
+    },
+    Neighbours {
+        neighbours: String,
+        scores: String,
+        options: KeywordArguments,
     },
+    Connections {
+        connections: String,
+        options: KeywordArguments,
+    },
+    Histogram {
+        value: String,
+        options: KeywordArguments,
+    },
+    /// The largest and smallest numbers of a value as a bar chart, labelled by a value of the same shape.
+    TopK {
+        value: String,
+        labels: String,
+        options: KeywordArguments,
+    },
+    /// A number of every neuron in the layer as coloured cells. Only shown on layer pages.
+    LayerHeatmap {
+        value: String,
+        options: KeywordArguments,
+    },
+    /// The neurons of the layer with the highest or lowest numbers. Only shown on layer pages.
+    TopNeurons {
+        value: String,
+        options: KeywordArguments,
+    },
+    /// Aggregate statistics of the numbers of a value on the page, e.g. over all neurons of the layer on layer
+    /// pages.
+    Statistics {
+        value: String,
+        options: KeywordArguments,
+    },
+    /// The table of neurons ordered by rank in every layer. Only shown on the index page.
+    RankedNeurons { options: KeywordArguments },
+    /// Links to the clusters of every clustering. Only shown on the index page.
+    Clusters { options: KeywordArguments },
+    /// The name, data type, scope and shape of every value in the payload. Only shown on the index page.
+    ValueList { options: KeywordArguments },
+    /// The search form, omitted in static sites. Only shown on the index page.
+    SearchForm { options: KeywordArguments },
 }
 
 impl Element {
-    fn parse_inner(str: &str) -> Self {
-        let str = str.trim();
-        let (element_name, args_string) = str.split_once('(').unwrap();
-        assert_eq!(args_string.chars().last().unwrap(), ')');
-        let args_string = &args_string[..args_string.len() - 1];
-        let mut arg_strings = args_string.split(',').map(|s| s.trim());
-
+    /// Builds an element from its parsed name and arguments.
+    pub(super) fn from_call(call: &ElementCall) -> ParseResult<Self> {
+        let element_name = call.name.value.as_str();
+        let Some(schema) = schema::element_schema(element_name) else {
+            let element_names: Vec<_> = ELEMENT_SCHEMAS
+                .iter()
+                .map(|schema| schema.name)
+                .chain(BLOCK_NAMES)
+                .collect();
+            return Err(Spanned {
+                value: TemplateParseErrorKind::UnknownElement {
+                    name: element_name.to_string(),
+                    suggestions: parser::suggestions(element_name, &element_names),
+                },
+                offset: call.name.offset,
+            });
+        };
         match element_name {
             "heatmap" => {
-                let heatmap_name = arg_strings.next().unwrap();
-                assert_eq!(arg_strings.next(), None);
-                Element::Heatmap(heatmap_name.to_string())
+                let ([value], options) = schema_arguments(call, schema)?;
+                validate_color_options(call, &options)?;
+                if options
+                    .string("cell_text")
+                    .is_some_and(|name| CellText::from_name(name).is_none())
+                {
+                    return Err(invalid_argument_error(
+                        call,
+                        options.argument("cell_text"),
+                        "'cell_text' must be \"coordinates\", \"number\" or \"none\"",
+                    ));
+                }
+                if validate_rendering(call, &options)? == Rendering::Png {
+                    if let Some(name) = ["row_labels", "column_labels", "cell_text"]
+                        .into_iter()
+                        .find(|&name| options.string(name).is_some())
+                    {
+                        return Err(invalid_argument_error(
+                            call,
+                            options.argument(name),
+                            &format!("'{name}' cannot be drawn in PNG images, use render=\"svg\" instead"),
+                        ));
+                    }
+                }
+                Ok(Element::Heatmap { value, options })
             }
             "value" => {
-                let value_name = arg_strings.next().unwrap();
-                assert_eq!(arg_strings.next(), None);
-                Element::Value(value_name.to_string())
+                let ([value], options) = schema_arguments(call, schema)?;
+                Ok(Element::Value { value, options })
             }
             "focus_sequences" => {
-                let activations_value_name = arg_strings.next().unwrap();
-                let step_names_value_name = arg_strings.next().unwrap();
-                assert_eq!(arg_strings.next(), None);
-                Element::FocusSequences {
-                    activations: activations_value_name.to_string(),
-                    step_names: step_names_value_name.to_string(),
+                let ([activations, step_names], options) = schema_arguments(call, schema)?;
+                validate_color_options(call, &options)?;
+                validate_rendering(call, &options)?;
+                Ok(Element::FocusSequences {
+                    activations,
+                    step_names,
+                    options,
+                })
+            }
+            "token_snippets" => {
+                let ([snippets], options) = schema_arguments(call, schema)?;
+                validate_color_options(call, &options)?;
+                Ok(Element::TokenSnippets { snippets, options })
+            }
+            "neighbours" => {
+                let ([neighbours, scores], options) = schema_arguments(call, schema)?;
+                Ok(Element::Neighbours {
+                    neighbours,
+                    scores,
+                    options,
+                })
+            }
+            "connections" => {
+                let ([connections], options) = schema_arguments(call, schema)?;
+                Ok(Element::Connections {
+                    connections,
+                    options,
+                })
+            }
+            "histogram" => {
+                let ([value], options) = schema_arguments(call, schema)?;
+                if options.integer("bins") == Some(0) {
+                    return Err(invalid_argument_error(
+                        call,
+                        options.argument("bins"),
+                        "the number of bins must be a positive integer",
+                    ));
                 }
+                Ok(Element::Histogram { value, options })
             }
-            _ => panic!("Invalid element name: {element_name}"),
+            "top_k" => {
+                let ([value, labels], options) = schema_arguments(call, schema)?;
+                if options.integer("k") == Some(0) {
+                    return Err(invalid_argument_error(
+                        call,
+                        options.argument("k"),
+                        "'k' must be a positive integer",
+                    ));
+                }
+                Ok(Element::TopK {
+                    value,
+                    labels,
+                    options,
+                })
+            }
+            "layer_heatmap" => {
+                let ([value], options) = schema_arguments(call, schema)?;
+                validate_color_options(call, &options)?;
+                if options.integer("columns") == Some(0) {
+                    return Err(invalid_argument_error(
+                        call,
+                        options.argument("columns"),
+                        "the number of columns must be a positive integer",
+                    ));
+                }
+                Ok(Element::LayerHeatmap { value, options })
+            }
+            "top_neurons" => {
+                let ([value], options) = schema_arguments(call, schema)?;
+                SortOrder::from_options(call, &options)?;
+                Ok(Element::TopNeurons { value, options })
+            }
+            "statistics" => {
+                let ([value], options) = schema_arguments(call, schema)?;
+                Ok(Element::Statistics { value, options })
+            }
+            "ranked_neurons" => {
+                let ([], options) = schema_arguments(call, schema)?;
+                Ok(Element::RankedNeurons { options })
+            }
+            "clusters" => {
+                let ([], options) = schema_arguments(call, schema)?;
+                Ok(Element::Clusters { options })
+            }
+            "values" => {
+                let ([], options) = schema_arguments(call, schema)?;
+                Ok(Element::ValueList { options })
+            }
+            "search" => {
+                let ([], options) = schema_arguments(call, schema)?;
+                Ok(Element::SearchForm { options })
+            }
+            _ => unreachable!("Every element with a schema is handled."),
         }
     }
 
-    pub fn parse<S: AsRef<str>>(str: S) -> Self {
-        Element::parse_inner(str.as_ref())
+    /// Whether the values of the element may be indexed by loop variables, e.g. `$value(scores[i])`.
+    pub(super) fn accepts_loop_indices(&self) -> bool {
+        matches!(self, Element::Heatmap { .. } | Element::Value { .. })
     }
 
-    pub fn generate(&self, payload: &Payload, layer_index: usize, neuron_index: usize) -> Markup {
+    pub fn options(&self) -> &KeywordArguments {
         match self {
-            Element::Heatmap(heatmap_name) => {
-                let heatmap = payload.value(heatmap_name).unwrap().as_f32().unwrap();
-                assert_eq!(
-                    &heatmap.shape()[..2],
-                    &[payload.num_layers(), payload.num_mlp_neurons()]
-                );
-                let heatmap = heatmap.slice(s![layer_index, neuron_index, .., ..]);
-                heatmap::heatmap(heatmap)
-            }
-            Element::Value(value) => match payload.value(value).unwrap().view() {
-                crate::data::value::ValueView::String(array) => {
-                    let array = array.into_dimensionality::<Ix2>().unwrap();
-                    assert_eq!(
-                        array.dim(),
-                        (payload.num_layers(), payload.num_mlp_neurons())
-                    );
-                    let value = &array[(layer_index, neuron_index)];
-                    html! {
-                        (value)
-                    }
+            Element::Heatmap { options, .. }
+            | Element::Value { options, .. }
+            | Element::FocusSequences { options, .. }
+            | Element::TokenSnippets { options, .. }
+            | Element::Neighbours { options, .. }
+            | Element::Connections { options, .. }
+            | Element::Histogram { options, .. }
+            | Element::TopK { options, .. }
+            | Element::LayerHeatmap { options, .. }
+            | Element::TopNeurons { options, .. }
+            | Element::Statistics { options, .. }
+            | Element::RankedNeurons { options }
+            | Element::Clusters { options }
+            | Element::ValueList { options }
+            | Element::SearchForm { options } => options,
+        }
+    }
+
+    /// The names of the values shown by the element, without loop indices. Values aggregated over the neurons of
+    /// a page, as by 'layer_heatmap', are not included, since they may have a finer scope than the page.
+    fn value_keys(&self) -> Vec<String> {
+        match self {
+            Element::Heatmap { value, options } => {
+                let name =
+                    ValueReference::parse(value).map_or(value.as_str(), |reference| reference.name);
+                [
+                    Some(name),
+                    options.string("row_labels"),
+                    options.string("column_labels"),
+                ]
+                .into_iter()
+                .flatten()
+                .map(str::to_owned)
+                .collect()
+            }
+            Element::Value { value, .. } => {
+                let name =
+                    ValueReference::parse(value).map_or(value.as_str(), |reference| reference.name);
+                vec![name.to_owned()]
+            }
+            Element::FocusSequences {
+                activations,
+                step_names,
+                ..
+            } => vec![activations.clone(), step_names.clone()],
+            Element::TokenSnippets { snippets, .. } => vec![snippets.clone()],
+            Element::Neighbours {
+                neighbours, scores, ..
+            } => vec![neighbours.clone(), scores.clone()],
+            Element::Connections { connections, .. } => {
+                let keys = ConnectionKeys::new(connections);
+                vec![
+                    keys.upstream,
+                    keys.upstream_weights,
+                    keys.downstream,
+                    keys.downstream_weights,
+                ]
+            }
+            Element::Histogram { value, .. } => vec![value.clone()],
+            Element::TopK { value, labels, .. } if labels == VOCABULARY_LABELS => {
+                vec![value.clone()]
+            }
+            Element::TopK { value, labels, .. } => vec![value.clone(), labels.clone()],
+            Element::LayerHeatmap { .. }
+            | Element::TopNeurons { .. }
+            | Element::Statistics { .. }
+            | Element::RankedNeurons { .. }
+            | Element::Clusters { .. }
+            | Element::ValueList { .. }
+            | Element::SearchForm { .. } => Vec::new(),
+        }
+    }
+
+    /// The values shown by the element on pages of the given scope.
+    pub(super) fn requirements(&self, page_scope: Scope) -> Vec<ValueRequirement> {
+        use DataType::{TokenSnippets, F32, U32};
+        // Values indexed by loop variables have an axis for every loop variable.
+        let indexed = |value: &str, data_types: &[DataType], axes: usize| {
+            let reference = ValueReference::parse(value).expect("Checked when parsing.");
+            ValueRequirement::new(
+                reference.name,
+                data_types,
+                page_scope,
+                Some(axes + reference.indices.len()),
+            )
+        };
+        match self {
+            Element::Heatmap { value, options } => {
+                let labels = [
+                    options.string("row_labels"),
+                    options.string("column_labels"),
+                ];
+                std::iter::once(indexed(value, &[F32], 2))
+                    .chain(labels.into_iter().flatten().map(|labels| {
+                        ValueRequirement::new(labels, &[DataType::String, U32], page_scope, Some(1))
+                    }))
+                    .collect()
+            }
+            Element::Value { value, .. } => vec![indexed(value, &[F32, U32, DataType::String], 0)],
+            Element::FocusSequences {
+                activations,
+                step_names,
+                ..
+            } => vec![
+                ValueRequirement::new(activations, &[F32], page_scope, Some(2)),
+                ValueRequirement::new(step_names, &[DataType::String, U32], page_scope, Some(2)),
+            ],
+            Element::TokenSnippets { snippets, .. } => {
+                vec![ValueRequirement::new(
+                    snippets,
+                    &[TokenSnippets],
+                    page_scope,
+                    Some(0),
+                )]
+            }
+            Element::Neighbours {
+                neighbours, scores, ..
+            } => vec![
+                ValueRequirement::new(neighbours, &[U32], page_scope, Some(2)),
+                ValueRequirement::new(scores, &[F32], page_scope, Some(1)),
+            ],
+            Element::Connections { connections, .. } => {
+                let keys = ConnectionKeys::new(connections);
+                vec![
+                    ValueRequirement::new(keys.upstream, &[U32], page_scope, Some(2)),
+                    ValueRequirement::new(keys.upstream_weights, &[F32], page_scope, Some(1)),
+                    ValueRequirement::new(keys.downstream, &[U32], page_scope, Some(2)),
+                    ValueRequirement::new(keys.downstream_weights, &[F32], page_scope, Some(1)),
+                ]
+            }
+            Element::Histogram { value, .. } => {
+                vec![ValueRequirement::new(value, &[F32], page_scope, None)]
+            }
+            // Values of one or two axes, labelled by a value of the same shape.
+            Element::TopK { value, labels, .. } if labels == VOCABULARY_LABELS => {
+                vec![ValueRequirement::new(value, &[F32], page_scope, None)]
+            }
+            Element::TopK { value, labels, .. } => vec![
+                ValueRequirement::new(value, &[F32], page_scope, None),
+                ValueRequirement::new(labels, &[DataType::String, U32], page_scope, None),
+            ],
+            Element::LayerHeatmap { value, .. } | Element::TopNeurons { value, .. } => {
+                vec![ValueRequirement::neuron_numbers(value)]
+            }
+            // Statistics aggregate values of any scope over the page.
+            Element::Statistics { value, .. } => {
+                vec![ValueRequirement::new(
+                    value,
+                    &[F32, U32],
+                    Scope::Neuron,
+                    None,
+                )]
+            }
+            Element::RankedNeurons { .. }
+            | Element::Clusters { .. }
+            | Element::ValueList { .. }
+            | Element::SearchForm { .. } => Vec::new(),
+        }
+    }
+
+    /// The name of the element and the scope of the only pages showing it, for elements summarizing the whole model
+    /// or a layer.
+    fn page_element(&self) -> Option<(&'static str, Scope)> {
+        match self {
+            Element::LayerHeatmap { .. } => Some(("layer_heatmap", Scope::Layer)),
+            Element::TopNeurons { .. } => Some(("top_neurons", Scope::Layer)),
+            Element::RankedNeurons { .. } => Some(("ranked_neurons", Scope::Global)),
+            Element::Clusters { .. } => Some(("clusters", Scope::Global)),
+            Element::ValueList { .. } => Some(("values", Scope::Global)),
+            Element::SearchForm { .. } => Some(("search", Scope::Global)),
+            _ => None,
+        }
+    }
+
+    /// Checks that the element and its values can be shown on pages of the given scope.
+    pub(super) fn validate_scope(
+        &self,
+        payload: &Payload,
+        scope: Scope,
+    ) -> Result<(), ArgumentError> {
+        if let Some((name, page_scope)) = self.page_element() {
+            if scope != page_scope {
+                let pages = match page_scope {
+                    Scope::Global => "the index page",
+                    Scope::Layer => "layer pages",
+                    Scope::Neuron => "neuron pages",
+                };
+                return Err(ArgumentError {
+                    error_type: ArgumentErrorType::Other(format!(
+                        "The element '{name}' can only be shown on {pages}."
+                    )),
+                    value_name: name.to_owned(),
+                });
+            }
+        }
+        self.value_keys()
+            .iter()
+            .try_for_each(|value_key| check_scope(payload, value_key, scope))
+    }
+
+    /// Generates the element, preceded by its title if one is given.
+    pub(super) fn generate(&self, page: &Page, loops: &LoopVariables) -> Markup {
+        let content = self.generate_content(page, loops);
+        match self.options().string("title") {
+            Some(title) => html! {
+                h3 {(title)}
+                (content)
+            },
+            None => content,
+        }
+    }
+
+    fn generate_content(&self, page: &Page, loops: &LoopVariables) -> Markup {
+        let Page {
+            payload,
+            annotations,
+            file,
+            layer_index,
+            neuron_index,
+            ..
+        } = *page;
+        match self {
+            Element::Heatmap {
+                value: heatmap_name,
+                options,
+            } => {
+                let reference = ValueReference::parse(heatmap_name).unwrap();
+                let value = payload.value(reference.name).unwrap();
+                let heatmap = value.neuron_f32(layer_index, neuron_index).unwrap();
+                let heatmap = index_loops(heatmap.view(), &reference, loops)
+                    .into_dimensionality::<Ix2>()
+                    .unwrap();
+                let color_scale = color_scale(options, value, heatmap.iter());
+                let row_labels = options
+                    .string("row_labels")
+                    .map(|key| labels(payload, key, layer_index, neuron_index));
+                let column_labels = options
+                    .string("column_labels")
+                    .map(|key| labels(payload, key, layer_index, neuron_index));
+                // Cells of labelled matrices are empty unless asked for, as the headers already name them.
+                let cell_text = match options.string("cell_text") {
+                    Some(name) => CellText::from_name(name).expect("Checked when parsing."),
+                    None if row_labels.is_some() || column_labels.is_some() => CellText::None,
+                    None => CellText::Coordinates,
+                };
+                match rendering(options) {
+                    Rendering::Table => heatmap::labelled_heatmap(
+                        heatmap,
+                        &color_scale,
+                        row_labels.as_deref(),
+                        column_labels.as_deref(),
+                        cell_text,
+                    ),
+                    Rendering::Svg => image::matrix_svg(
+                        heatmap,
+                        &color_scale,
+                        row_labels.as_deref(),
+                        column_labels.as_deref(),
+                        heatmap::cell_texts(heatmap, cell_text)
+                            .as_ref()
+                            .map(|texts| texts.view()),
+                    ),
+                    Rendering::Png => png_matrix(page, heatmap, &color_scale),
                 }
-                crate::data::value::ValueView::U32(array) => {
-                    let array = array.into_dimensionality::<Ix2>().unwrap();
-                    assert_eq!(
-                        array.dim(),
-                        (payload.num_layers(), payload.num_mlp_neurons())
-                    );
-                    let value = array[(layer_index, neuron_index)];
-                    html! {
-                        (value)
+            }
+            Element::Value { value, options } => {
+                let reference = ValueReference::parse(value).unwrap();
+                let value = payload.value(reference.name).unwrap();
+                match value.data_type() {
+                    DataType::String => {
+                        let array = scope_slice(
+                            value.as_string().unwrap().view(),
+                            value.scope(),
+                            layer_index,
+                            neuron_index,
+                        );
+                        let value = index_loops(array, &reference, loops)
+                            .first()
+                            .unwrap()
+                            .clone();
+                        html! {
+                            (value)
+                        }
                     }
-                }
-                crate::data::value::ValueView::F32(array) => {
-                    let array = array.into_dimensionality::<Ix2>().unwrap();
-                    assert_eq!(
-                        array.dim(),
-                        (payload.num_layers(), payload.num_mlp_neurons())
-                    );
-                    let value = array[(layer_index, neuron_index)];
-                    html! {
-                        (value)
+                    DataType::U32 => {
+                        let array = scope_slice(
+                            value.as_u32().unwrap().view(),
+                            value.scope(),
+                            layer_index,
+                            neuron_index,
+                        );
+                        let value = *index_loops(array, &reference, loops).first().unwrap();
+                        html! {
+                            (value)
+                        }
+                    }
+                    DataType::F32 => {
+                        let array = value.neuron_f32(layer_index, neuron_index).unwrap();
+                        let value = *index_loops(array.view(), &reference, loops)
+                            .first()
+                            .unwrap();
+                        match options.integer("precision") {
+                            Some(precision) => html! {
+                                (format!("{value:.precision$}"))
+                            },
+                            None => html! {
+                                (value)
+                            },
+                        }
+                    }
+                    DataType::TokenSnippets => {
+                        panic!("Token snippets cannot be shown with the 'value' element. This should be guaranteed by validation.")
                     }
                 }
-            },
+            }
             Element::FocusSequences {
                 activations,
                 step_names,
+                options,
             } => {
                 let activations_value = payload.value(activations).unwrap();
                 let step_names_value = payload.value(step_names).unwrap();
 
-                let activations = activations_value.as_f32().unwrap();
-                let step_names = step_names_value.as_string().unwrap();
-                assert_eq!(
-                    &activations.shape()[..2],
-                    &[payload.num_layers(), payload.num_mlp_neurons()]
-                );
+                let activations = activations_value
+                    .neuron_f32(layer_index, neuron_index)
+                    .unwrap()
+                    .into_dimensionality::<Ix2>()
+                    .unwrap();
+                let activations = activations.view();
+                let color_scale = color_scale(options, activations_value, activations.iter());
 
-                let activations = activations.slice(s![layer_index, neuron_index, .., ..]);
-                let step_names = match step_names_value.scope() {
-                    Scope::Global => step_names.view().into_dimensionality().unwrap(),
-                    Scope::Layer => step_names.slice(s![layer_index, .., ..]),
-                    Scope::Neuron => step_names.slice(s![layer_index, neuron_index, .., ..]),
+                match step_names_value.view() {
+                    ValueView::String(step_names) => {
+                        let step_names = scope_slice_2d(
+                            step_names,
+                            step_names_value.scope(),
+                            layer_index,
+                            neuron_index,
+                        );
+                        draw_focus_sequences(page, activations, step_names, &color_scale, rendering(options))
+                    }
+                    ValueView::U32(step_tokens) => {
+                        let vocabulary = payload.vocabulary().expect("Token ids require a vocabulary. This should be guaranteed by validation.");
+                        let step_tokens = scope_slice_2d(
+                            step_tokens,
+                            step_names_value.scope(),
+                            layer_index,
+                            neuron_index,
+                        );
+                        let step_names = step_tokens.map(|&token_id| vocabulary.token(token_id).unwrap());
+                        draw_focus_sequences(page, activations, step_names.view(), &color_scale, rendering(options))
+                    }
+                    _ => panic!("Step names must be strings or token ids. This should be guaranteed by validation."),
+                }
+            }
+            Element::TokenSnippets {
+                snippets: snippets_name,
+                options,
+            } => {
+                let snippets_value = payload.value(snippets_name).unwrap();
+                let snippets = snippets_value.as_token_snippets().unwrap();
+                let snippets = match snippets_value.scope() {
+                    Scope::Global => &snippets[[]],
+                    Scope::Layer => &snippets[[layer_index]],
+                    Scope::Neuron => &snippets[[layer_index, neuron_index]],
                 };
-                assert_eq!(activations.shape(), step_names.shape());
-                focus_sequences::focus_sequences(activations, step_names)
+                let limit = options
+                    .integer("limit")
+                    .unwrap_or(snippets.len())
+                    .min(snippets.len());
+                let snippets = &snippets[..limit];
+                let color_scale = color_scale(
+                    options,
+                    snippets_value,
+                    snippets.iter().flat_map(|snippet| snippet.activations()),
+                );
+                token_snippets::token_snippets(snippets, &color_scale)
             }
+            Element::Neighbours {
+                neighbours, scores, ..
+            } => {
+                let neighbours_value = payload.value(neighbours).unwrap();
+                let scores_value = payload.value(scores).unwrap();
+                let neighbours = scope_slice_2d(
+                    neighbours_value.as_u32().unwrap().view(),
+                    neighbours_value.scope(),
+                    layer_index,
+                    neuron_index,
+                );
+                let scores = scores_value
+                    .neuron_f32(layer_index, neuron_index)
+                    .unwrap()
+                    .into_dimensionality::<Ix1>()
+                    .unwrap();
+                neuron_links::neuron_links(payload, neighbours, scores.view(), file)
+            }
+            Element::Connections {
+                connections: connections_name,
+                ..
+            } => {
+                let keys = ConnectionKeys::new(connections_name);
+                html! {
+                    div class="connections" {
+                        h3 {"Upstream"}
+                        (connection_links(payload, &keys.upstream, &keys.upstream_weights, file, layer_index, neuron_index))
+                        h3 {"Downstream"}
+                        (connection_links(payload, &keys.downstream, &keys.downstream_weights, file, layer_index, neuron_index))
+                    }
+                }
+            }
+            Element::Histogram { value, options } => {
+                let values = payload
+                    .value(value)
+                    .unwrap()
+                    .neuron_f32(layer_index, neuron_index)
+                    .unwrap();
+                let bins = options.integer("bins").unwrap_or(DEFAULT_HISTOGRAM_BINS);
+                histogram::histogram(values.iter(), bins)
+            }
+            Element::TopK {
+                value,
+                labels: labels_key,
+                options,
+            } => {
+                let numbers = payload
+                    .value(value)
+                    .unwrap()
+                    .neuron_f32(layer_index, neuron_index)
+                    .unwrap();
+                let labels = if labels_key == VOCABULARY_LABELS {
+                    vocabulary_labels(payload, numbers.shape())
+                } else {
+                    labels(payload, labels_key, layer_index, neuron_index)
+                };
+                // Matrices are ranked by all their numbers, in the same order as their labels.
+                let numbers: Vec<f32> = numbers.iter().copied().collect();
+                let k = options.integer("k").unwrap_or(DEFAULT_TOP_K);
+                top_k::top_k(&numbers, &labels, k)
+            }
+            Element::LayerHeatmap { value, options } => {
+                let numbers = layer_numbers(payload, value, layer_index);
+                let color_scale = color_scale(options, payload.value(value).unwrap(), &numbers);
+                let columns = options
+                    .integer("columns")
+                    .unwrap_or(DEFAULT_LAYER_HEATMAP_COLUMNS);
+                layer::layer_heatmap(
+                    payload,
+                    numbers.view(),
+                    &color_scale,
+                    columns,
+                    layer_index,
+                    file,
+                )
+            }
+            Element::TopNeurons { value, options } => {
+                let numbers = layer_numbers(payload, value, layer_index);
+                let mut neurons: Vec<(usize, f32)> = numbers.iter().copied().enumerate().collect();
+                match options.string("sort") {
+                    Some("ascending") => neurons.sort_by(|(_, a), (_, b)| a.total_cmp(b)),
+                    _ => neurons.sort_by(|(_, a), (_, b)| b.total_cmp(a)),
+                }
+                neurons.truncate(
+                    options
+                        .integer("limit")
+                        .unwrap_or(DEFAULT_TOP_NEURONS_LIMIT),
+                );
+                let precision = match payload.value(value).unwrap().data_type() {
+                    DataType::U32 => 0,
+                    _ => 3,
+                };
+                layer::top_neurons(payload, annotations, &neurons, precision, layer_index, file)
+            }
+            Element::Statistics { value, .. } => {
+                let value = payload.value(value).unwrap();
+                let mut index = page.scope.index(layer_index, neuron_index);
+                index.truncate(value.scope().num_axes());
+                let numbers = value.numbers_f32(&index).expect(
+                    "Statistics are only shown of numbers. This should be guaranteed by validation.",
+                );
+                statistics::statistics(numbers.view())
+            }
+            Element::RankedNeurons { .. } => index::generate_ranked_neurons_table(
+                payload,
+                annotations,
+                payload
+                    .ranked_neurons()
+                    .expect("Ranked neurons are only shown if they exist. This should be guaranteed by validation."),
+                file,
+            ),
+            Element::Clusters { .. } => index::generate_clusters_list(payload, file),
+            Element::ValueList { .. } => index::generate_value_list(payload),
+            Element::SearchForm { .. } => html! {
+                // Static sites have no search endpoint, only the prebuilt `search_index.json`.
+                @if !file {
+                    (search::search_form(""))
+                }
+            },
         }
     }
 
-    pub fn validate_arguments(&self, payload: &Payload) -> Result<(), ArgumentError> {
+    pub(super) fn validate_arguments(
+        &self,
+        payload: &Payload,
+        loops: &LoopVariables,
+    ) -> Result<(), ArgumentError> {
         match self {
-            Element::Heatmap(heatmap_key) => payload
-                .value(heatmap_key)
-                .ok_or_else(|| ArgumentErrorType::MissingValue(heatmap_key.clone()))
-                .and_then(|heatmap| {
-                    let heatmap_axis_num = match heatmap.scope() {
-                        Scope::Global => heatmap.shape().len(),
-                        Scope::Layer => heatmap.shape().len() - 1,
-                        Scope::Neuron => heatmap.shape().len() - 2,
-                    };
-                    if heatmap_axis_num != 2 {
-                        Err(ArgumentErrorType::AxisNum {
-                            required_axis_num: 2,
-                            found_axis_num: heatmap_axis_num,
-                        })
-                    } else if heatmap.data_type() != DataType::F32 {
-                        Err(ArgumentErrorType::DataType {
-                            required_data_type: DataType::F32,
-                            found_data_type: heatmap.data_type(),
-                        })
-                    } else {
-                        Ok(())
-                    }
-                })
-                .map_err(|error_type| ArgumentError {
-                    error_type,
-                    value_name: heatmap_key.to_owned(),
-                }),
-            Element::Value(value_key) => payload
-                .value(value_key)
-                .ok_or_else(|| ArgumentErrorType::MissingValue(value_key.clone()))
-                .and_then(|value| {
-                    let value_axis_num = match value.scope() {
-                        Scope::Global => value.shape().len(),
-                        Scope::Layer => value.shape().len() - 1,
-                        Scope::Neuron => value.shape().len() - 2,
-                    };
-                    if value_axis_num != 0 {
+            Element::Heatmap {
+                value: heatmap_key,
+                options,
+            } => {
+                let heatmap_shape = ValueReference::parse(heatmap_key)
+                    .map_err(ArgumentErrorType::Other)
+                    .and_then(|reference| indexed_value(payload, &reference, loops))
+                    .and_then(|(heatmap, heatmap_shape)| {
+                        if heatmap_shape.len() != 2 {
+                            Err(ArgumentErrorType::AxisNum {
+                                required_axis_num: 2,
+                                found_axis_num: heatmap_shape.len(),
+                            })
+                        } else if heatmap.data_type() != DataType::F32 {
+                            Err(ArgumentErrorType::DataType {
+                                required_data_type: DataType::F32,
+                                found_data_type: heatmap.data_type(),
+                            })
+                        } else {
+                            Ok(heatmap_shape)
+                        }
+                    })
+                    .map_err(|error_type| ArgumentError {
+                        error_type,
+                        value_name: heatmap_key.to_owned(),
+                    })?;
+                if let Some(row_labels) = options.string("row_labels") {
+                    validate_labels(payload, row_labels, heatmap_shape[0], "rows")?;
+                }
+                if let Some(column_labels) = options.string("column_labels") {
+                    validate_labels(payload, column_labels, heatmap_shape[1], "columns")?;
+                }
+                Ok(())
+            }
+            Element::Value {
+                value: value_key, ..
+            } => ValueReference::parse(value_key)
+                .map_err(ArgumentErrorType::Other)
+                .and_then(|reference| indexed_value(payload, &reference, loops))
+                .and_then(|(value, value_shape)| {
+                    if !value_shape.is_empty() {
                         Err(ArgumentErrorType::AxisNum {
                             required_axis_num: 0,
-                            found_axis_num: value_axis_num,
+                            found_axis_num: value_shape.len(),
                         })
+                    } else if value.data_type() == DataType::TokenSnippets {
+                        Err(ArgumentErrorType::Other(
+                            "Token snippets cannot be shown with the element 'value'. Use 'token_snippets' instead.".to_string(),
+                        ))
                     } else {
                         Ok(())
                     }
@@ -182,16 +1384,13 @@ impl Element {
             Element::FocusSequences {
                 activations: activations_key,
                 step_names: step_names_key,
+                ..
             } => {
                 let activations = payload
                     .value(activations_key)
                     .ok_or_else(|| ArgumentErrorType::MissingValue(activations_key.clone()))
                     .and_then(|activations| {
-                        let activations_axis_num = match activations.scope() {
-                            Scope::Global => activations.shape().len(),
-                            Scope::Layer => activations.shape().len() - 1,
-                            Scope::Neuron => activations.shape().len() - 2,
-                        };
+                        let activations_axis_num = scoped_shape(activations).len();
                         if activations_axis_num != 2 {
                             Err(ArgumentErrorType::AxisNum {
                                 required_axis_num: 2,
@@ -214,17 +1413,8 @@ impl Element {
                 payload
                     .value(step_names_key)
                     .ok_or_else(|| ArgumentErrorType::MissingValue(step_names_key.clone())).and_then(|step_names| {
-                        let step_names_shape = match step_names.scope() {
-                            Scope::Global => step_names.shape(),
-                            Scope::Layer => &step_names.shape()[1..],
-                            Scope::Neuron => &step_names.shape()[2..],
-                        };
-
-                        let activations_shape = match activations.scope() {
-                            Scope::Global => activations.shape(),
-                            Scope::Layer => &activations.shape()[1..],
-                            Scope::Neuron => &activations.shape()[2..],
-                        };
+                        let step_names_shape = scoped_shape(step_names);
+                        let activations_shape = scoped_shape(activations);
 
                         if step_names_shape.len() != 2 {
                             Err(ArgumentErrorType::AxisNum {
@@ -235,13 +1425,15 @@ impl Element {
                             Err(ArgumentErrorType::Other(format!("The two arguments to the element 'focus_sequences' must have equal shape (after scope). \
                                     First argument has shape {:?} while second argument has shape {:?}.",
                                     activations_shape, step_names_shape)))
-                        } else if step_names.data_type() != DataType::String {
-                            Err(ArgumentErrorType::DataType {
-                                required_data_type: DataType::String,
-                                found_data_type: step_names.data_type(),
-                            })
                         } else {
-                            Ok(())
+                            match step_names.data_type() {
+                                DataType::String => Ok(()),
+                                DataType::U32 => validate_token_ids(step_names, payload),
+                                found_data_type => Err(ArgumentErrorType::DataType {
+                                    required_data_type: DataType::String,
+                                    found_data_type,
+                                }),
+                            }
                         }
                     })
                     .map_err(|error_type: ArgumentErrorType| ArgumentError {
@@ -249,6 +1441,161 @@ impl Element {
                         value_name: step_names_key.to_owned(),
                     })
             }
+            Element::TokenSnippets {
+                snippets: snippets_key,
+                ..
+            } => payload
+                .value(snippets_key)
+                .ok_or_else(|| ArgumentErrorType::MissingValue(snippets_key.clone()))
+                .and_then(|snippets| {
+                    let snippets_axis_num = scoped_shape(snippets).len();
+                    if snippets_axis_num != 0 {
+                        Err(ArgumentErrorType::AxisNum {
+                            required_axis_num: 0,
+                            found_axis_num: snippets_axis_num,
+                        })
+                    } else if snippets.data_type() != DataType::TokenSnippets {
+                        Err(ArgumentErrorType::DataType {
+                            required_data_type: DataType::TokenSnippets,
+                            found_data_type: snippets.data_type(),
+                        })
+                    } else {
+                        Ok(())
+                    }
+                })
+                .map_err(|error_type| ArgumentError {
+                    error_type,
+                    value_name: snippets_key.to_owned(),
+                }),
+            Element::Neighbours {
+                neighbours: neighbours_key,
+                scores: scores_key,
+                ..
+            } => validate_neuron_links(payload, neighbours_key, scores_key, "neighbours"),
+            Element::Histogram { value: value_key, .. } => payload
+                .value(value_key)
+                .ok_or_else(|| ArgumentErrorType::MissingValue(value_key.clone()))
+                .and_then(|value| {
+                    if value.data_type() != DataType::F32 {
+                        Err(ArgumentErrorType::DataType {
+                            required_data_type: DataType::F32,
+                            found_data_type: value.data_type(),
+                        })
+                    } else {
+                        Ok(())
+                    }
+                })
+                .map_err(|error_type| ArgumentError {
+                    error_type,
+                    value_name: value_key.to_owned(),
+                }),
+            Element::TopK {
+                value: value_key,
+                labels: labels_key,
+                ..
+            } => {
+                let value = payload
+                    .value(value_key)
+                    .ok_or_else(|| ArgumentErrorType::MissingValue(value_key.clone()))
+                    .and_then(|value| {
+                        let axis_num = scoped_shape(value).len();
+                        if !(1..=2).contains(&axis_num) {
+                            Err(ArgumentErrorType::Other(format!(
+                                "The element 'top_k' shows values with 1 or 2 axes (after scope), but the value has {axis_num}."
+                            )))
+                        } else if value.data_type() != DataType::F32 {
+                            Err(ArgumentErrorType::DataType {
+                                required_data_type: DataType::F32,
+                                found_data_type: value.data_type(),
+                            })
+                        } else {
+                            Ok(value)
+                        }
+                    })
+                    .map_err(|error_type| ArgumentError {
+                        error_type,
+                        value_name: value_key.to_owned(),
+                    })?;
+                if labels_key == VOCABULARY_LABELS {
+                    return payload
+                        .vocabulary()
+                        .ok_or(ArgumentErrorType::MissingVocabulary)
+                        .and_then(|vocabulary| {
+                            let entries = scoped_shape(value).last().copied().unwrap_or(0);
+                            if entries != vocabulary.len() {
+                                Err(ArgumentErrorType::Other(format!(
+                                    "The last axis of the value has {entries} entries, but the vocabulary has {} tokens.",
+                                    vocabulary.len()
+                                )))
+                            } else {
+                                Ok(())
+                            }
+                        })
+                        .map_err(|error_type| ArgumentError {
+                            error_type,
+                            value_name: value_key.to_owned(),
+                        });
+                }
+                payload
+                    .value(labels_key)
+                    .ok_or_else(|| ArgumentErrorType::MissingValue(labels_key.clone()))
+                    .and_then(|labels| {
+                        if scoped_shape(labels) != scoped_shape(value) {
+                            Err(ArgumentErrorType::Other(format!(
+                                "The labels of the element 'top_k' must have the shape of its value (after scope). \
+                                    The value has shape {:?} while the labels have shape {:?}.",
+                                scoped_shape(value),
+                                scoped_shape(labels)
+                            )))
+                        } else {
+                            validate_label_data_type(labels, payload)
+                        }
+                    })
+                    .map_err(|error_type| ArgumentError {
+                        error_type,
+                        value_name: labels_key.to_owned(),
+                    })
+            }
+            Element::Connections {
+                connections: key, ..
+            } => {
+                let keys = ConnectionKeys::new(key);
+                validate_neuron_links(payload, &keys.upstream, &keys.upstream_weights, "connections")?;
+                validate_neuron_links(
+                    payload,
+                    &keys.downstream,
+                    &keys.downstream_weights,
+                    "connections",
+                )
+            }
+            Element::LayerHeatmap { value, .. } | Element::TopNeurons { value, .. } => {
+                validate_neuron_numbers(payload, value)
+            }
+            Element::Statistics { value: value_key, .. } => payload
+                .value(value_key)
+                .ok_or_else(|| ArgumentErrorType::MissingValue(value_key.clone()))
+                .and_then(|value| match value.data_type() {
+                    DataType::U32 | DataType::F32 => Ok(()),
+                    found_data_type => Err(ArgumentErrorType::DataType {
+                        required_data_type: DataType::F32,
+                        found_data_type,
+                    }),
+                })
+                .map_err(|error_type| ArgumentError {
+                    error_type,
+                    value_name: value_key.to_owned(),
+                }),
+            // Neurons are only ranked if the payload builder was given rank values.
+            Element::RankedNeurons { .. } => match payload.ranked_neurons() {
+                Some(_) => Ok(()),
+                None => Err(ArgumentError {
+                    error_type: ArgumentErrorType::MissingValue("ranked_neurons".to_owned()),
+                    value_name: "ranked_neurons".to_owned(),
+                }),
+            },
+            Element::Clusters { .. } | Element::ValueList { .. } | Element::SearchForm { .. } => {
+                Ok(())
+            }
         }
     }
 }
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use maud::{html, Markup, PreEscaped};
+use serde::{Deserialize, Serialize};
+
+use crate::{data::value::Scope, html::PageImages, Annotations, Payload};
+
+use super::{
+    tree::{Page, Tree},
+    ArgumentError, Partial, PayloadSchema, TemplateParseError,
+};
+
+/// The index page used unless the payload has its own.
+pub const DEFAULT_INDEX_TEMPLATE: &str = r#"<h1>Welcome to TransformerScope!</h1>
+<p>This is a web app for visualizing various aspects of transformer models.</p>
+<p>Based on the original idea of the <a href="https://neuroscope.io/gelu-3l/0/314.html">Neuroscope</a> by Neel Nanda.</p>
+$search()
+$clusters()
+$if(exists(ranked_neurons))
+$ranked_neurons()
+$end
+"#;
+
+/// The template of the index page. It is written like a [`NeuronTemplate`](super::NeuronTemplate), but may only
+/// show `Global` values, and has the elements `$ranked_neurons()`, `$clusters()`, `$values()` and `$search()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexTemplate {
+    tree: Tree,
+}
+
+impl IndexTemplate {
+    fn parse_inner(template_code: &str) -> Result<Self, TemplateParseError> {
+        let tree = Tree::parse(template_code)?;
+        Ok(Self { tree })
+    }
+
+    pub fn parse<S: AsRef<str>>(template_code: S) -> Result<Self, TemplateParseError> {
+        Self::parse_inner(template_code.as_ref())
+    }
+
+    pub fn generate(
+        &self,
+        payload: &Payload,
+        annotations: &Annotations,
+        site: bool,
+        images: &PageImages,
+    ) -> Markup {
+        // Global values have no scope axes, so any neuron indices select them.
+        let body = self.tree.generate(&Page {
+            payload,
+            annotations,
+            file: site,
+            scope: Scope::Global,
+            layer_index: 0,
+            neuron_index: 0,
+            images,
+        });
+        html!(
+            head {
+                meta charset="utf-8";
+                title {"TransformerScope"}
+                link rel="stylesheet" href="static/style.css"{};
+            }
+            body {
+                (PreEscaped(body))
+            }
+        )
+    }
+
+    /// Replaces every `$include` by the nodes of the included partial.
+    pub(crate) fn resolve_includes(
+        &self,
+        partials: &BTreeMap<String, Partial>,
+    ) -> Result<Self, ArgumentError> {
+        let tree = self.tree.resolve_includes(partials)?;
+        Ok(Self { tree })
+    }
+
+    /// The values shown by the template with the given partials included, with the data types, scopes and numbers of
+    /// axes they may have.
+    pub fn schema(
+        &self,
+        partials: &BTreeMap<String, Partial>,
+    ) -> Result<PayloadSchema, ArgumentError> {
+        Ok(self.tree.resolve_includes(partials)?.schema(Scope::Global))
+    }
+
+    pub fn validate_arguments(&self, payload: &Payload) -> Result<(), ArgumentError> {
+        self.tree.validate(payload, Scope::Global)
+    }
+}
+
+impl Default for IndexTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_INDEX_TEMPLATE).expect("The default index template is valid.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array2;
+
+    use super::IndexTemplate;
+    use crate::{
+        data::{value::Scope, Value},
+        html::{
+            template::{ArgumentErrorType, NeuronTemplate},
+            PageImages,
+        },
+        Annotations, PayloadBuilder,
+    };
+
+    fn builder() -> PayloadBuilder {
+        let mut builder = PayloadBuilder::new(2, 3);
+        builder
+            .mlp_neuron_template(NeuronTemplate::parse("$value(scores)").unwrap())
+            .unwrap();
+        builder.add_value(
+            "scores",
+            Value::new(
+                Array2::from_shape_fn((2, 3), |(layer, neuron)| (layer * 3 + neuron) as f32),
+                Scope::Neuron,
+            ),
+        );
+        builder
+    }
+
+    fn index(builder: PayloadBuilder) -> String {
+        let payload = builder.build().unwrap();
+        payload
+            .index_template()
+            .generate(&payload, &Annotations::new(), false, &PageImages::default())
+            .into_string()
+    }
+
+    #[test]
+    fn the_default_template_shows_ranked_neurons_if_they_exist() {
+        let unranked = index(builder());
+        assert!(unranked.contains("Welcome to TransformerScope!"));
+        assert!(!unranked.contains("<table>"));
+
+        let mut builder = builder();
+        builder.set_rank_values("scores");
+        assert!(index(builder).contains("<table>"));
+    }
+
+    #[test]
+    fn ranked_neurons_need_rank_values() {
+        let mut builder = builder();
+        builder
+            .index_template(IndexTemplate::parse("$ranked_neurons()").unwrap())
+            .unwrap();
+        let Err(error) = builder.build() else {
+            panic!("Ranked neurons were shown without rank values.");
+        };
+        assert!(matches!(
+            error.error_type,
+            ArgumentErrorType::MissingValue(ref name) if name == "ranked_neurons"
+        ));
+
+        let mut builder = self::builder();
+        builder
+            .index_template(IndexTemplate::parse("$ranked_neurons()").unwrap())
+            .unwrap();
+        builder.set_rank_values("scores");
+        assert!(builder.build().is_ok());
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::value::Scope,
+    html::{layer::layer_link, PageImages},
+    Annotations, Payload,
+};
+
+use super::{
+    tree::{Page, Tree},
+    ArgumentError, Partial, PayloadSchema, TemplateParseError,
+};
+
+/// The layer pages used unless the payload has its own template for them.
+pub const DEFAULT_LAYER_TEMPLATE: &str = r#"$if(exists(rank))
+$top_neurons(rank, sort="ascending", limit=20, title="Top neurons by rank")
+$end
+"#;
+
+/// The template of the layer pages. It is written like a [`NeuronTemplate`](super::NeuronTemplate), but may only
+/// show `Global` and `Layer` values, and has the elements `$layer_heatmap(value)` and `$top_neurons(value)` showing a
+/// number of every neuron in the layer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerTemplate {
+    tree: Tree,
+}
+
+impl LayerTemplate {
+    fn parse_inner(template_code: &str) -> Result<Self, TemplateParseError> {
+        let tree = Tree::parse(template_code)?;
+        Ok(Self { tree })
+    }
+
+    pub fn parse<S: AsRef<str>>(template_code: S) -> Result<Self, TemplateParseError> {
+        Self::parse_inner(template_code.as_ref())
+    }
+
+    pub fn generate(
+        &self,
+        payload: &Payload,
+        annotations: &Annotations,
+        file: bool,
+        layer_index: usize,
+        images: &PageImages,
+    ) -> Markup {
+        // Values on layer pages have at most a layer axis, so any neuron index selects them.
+        let body = self.tree.generate(&Page {
+            payload,
+            annotations,
+            file,
+            scope: Scope::Layer,
+            layer_index,
+            neuron_index: 0,
+            images,
+        });
+
+        let original_layer_index = payload.original_layer_index(layer_index);
+        html!(
+            (DOCTYPE)
+            head {
+                meta charset="utf-8";
+                title { "Transformer Scope - Layer " (original_layer_index)}
+                link rel="stylesheet" href={(if file {".."} else {""})"/static/style.css"}{};
+            }
+            a href={(if file {"../index.html"} else {"/"})} {"Back to index"}
+            h1 {"Transformer Scope - Layer " (original_layer_index)}
+            (generate_navigation_links(payload, layer_index, file))
+            (PreEscaped(body))
+        )
+    }
+
+    /// Replaces every `$include` by the nodes of the included partial.
+    pub(crate) fn resolve_includes(
+        &self,
+        partials: &BTreeMap<String, Partial>,
+    ) -> Result<Self, ArgumentError> {
+        let tree = self.tree.resolve_includes(partials)?;
+        Ok(Self { tree })
+    }
+
+    /// The values shown by the template with the given partials included, with the data types, scopes and numbers of
+    /// axes they may have.
+    pub fn schema(
+        &self,
+        partials: &BTreeMap<String, Partial>,
+    ) -> Result<PayloadSchema, ArgumentError> {
+        Ok(self.tree.resolve_includes(partials)?.schema(Scope::Layer))
+    }
+
+    pub fn validate_arguments(&self, payload: &Payload) -> Result<(), ArgumentError> {
+        self.tree.validate(payload, Scope::Layer)
+    }
+}
+
+impl Default for LayerTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_LAYER_TEMPLATE).expect("The default layer template is valid.")
+    }
+}
+
+fn generate_navigation_links(payload: &Payload, layer_index: usize, file: bool) -> Markup {
+    let previous_layer_link = if layer_index > 0 {
+        html! {
+            a href=(layer_link(payload.original_layer_index(layer_index - 1), false, file)) {
+                "Previous layer"
+            }
+        }
+    } else {
+        html! {}
+    };
+
+    let next_layer_link = if layer_index < payload.num_layers() - 1 {
+        html! {
+            a href=(layer_link(payload.original_layer_index(layer_index + 1), false, file)) {
+                "Next layer"
+            }
+        }
+    } else {
+        html! {}
+    };
+
+    html! {
+        (previous_layer_link)" - "(next_layer_link)
+    }
+}
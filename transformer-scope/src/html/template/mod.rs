@@ -1,9 +1,28 @@
 use thiserror::Error;
 
+mod control_flow;
 mod element;
 pub use element::Element;
+mod index_template;
+pub use index_template::{IndexTemplate, DEFAULT_INDEX_TEMPLATE};
+mod layer_template;
+pub use layer_template::{LayerTemplate, DEFAULT_LAYER_TEMPLATE};
 mod neuron_template;
 pub use neuron_template::NeuronTemplate;
+mod parser;
+mod partial;
+mod payload_schema;
+pub use parser::{TemplateParseError, TemplateParseErrorKind};
+pub use partial::Partial;
+pub use payload_schema::{PayloadSchema, SchemaError, ValueRequirement};
+mod schema;
+pub use schema::{
+    element_schema, ArgumentType, ArgumentValue, ElementSchema, KeywordArguments, KeywordSchema,
+    ELEMENT_SCHEMAS,
+};
+mod templates;
+pub use templates::Templates;
+mod tree;
 
 use crate::data::value::{DataType, Scope};
 
@@ -26,6 +45,27 @@ pub enum ArgumentErrorType {
         required_axis_num: usize,
         found_axis_num: usize,
     },
+    #[error("Argument contains token ids, but the payload has no vocabulary.")]
+    MissingVocabulary,
+    #[error("Argument contains token id {token_id}, but the vocabulary only has {vocabulary_size} tokens.")]
+    TokenOutOfRange {
+        token_id: u32,
+        vocabulary_size: usize,
+    },
+    #[error(
+        "Template includes the partial {name}, which does not exist in payload.{}",
+        parser::did_you_mean(suggestions)
+    )]
+    MissingPartial {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    #[error("The partial is included without an argument for its parameter {0}.")]
+    MissingPartialArgument(String),
+    #[error("The partial is included with the argument {0}, but has no such parameter.")]
+    UnknownPartialArgument(String),
+    #[error("The partial does not parse after replacing its parameters: {0}")]
+    PartialParse(Box<TemplateParseError>),
     #[error("{0}")]
     Other(String),
 }
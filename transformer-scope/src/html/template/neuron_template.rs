@@ -1,90 +1,128 @@
-use itertools::Itertools;
+use std::collections::BTreeMap;
+
 use maud::{html, Markup, PreEscaped, DOCTYPE};
 use serde::{Deserialize, Serialize};
 
-use crate::Payload;
+use crate::{
+    data::value::Scope,
+    html::{annotation, layer::layer_link, PageImages},
+    Annotations, Payload,
+};
 
-use super::{ArgumentError, Element};
+use super::{
+    tree::{Page, Tree},
+    ArgumentError, Partial, PayloadSchema, TemplateParseError,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NeuronTemplate {
-    prefix: String,
-    template: Vec<(Element, String)>,
+    tree: Tree,
 }
 
 impl NeuronTemplate {
-    fn parse_inner(template_code: &str) -> Self {
-        let mut parts = template_code.split('$');
-        let prefix = parts.next().unwrap().to_string();
-        let template: Vec<_> = parts
-            .map(|s| {
-                let mut parts = s.split_inclusive(')');
-                let element_string = parts.next().unwrap();
-                let template_part = parts.join("");
-                let element = Element::parse(element_string);
-                (element, template_part)
-            })
-            .collect();
-        Self { prefix, template }
+    fn parse_inner(template_code: &str) -> Result<Self, TemplateParseError> {
+        let tree = Tree::parse(template_code)?;
+        Ok(Self { tree })
     }
 
-    pub fn parse<S: AsRef<str>>(template_code: S) -> Self {
+    /// Parses a template, in which elements like `$heatmap(value_name)` are replaced by generated HTML.
+    ///
+    /// A literal `$` is written as `$$`. Arguments can be quoted with single or double quotes, and unquoted
+    /// arguments may contain balanced parentheses.
+    ///
+    /// Parts of the template can be shown conditionally with `$if(condition) ... $elif(condition) ... $else ... $end`,
+    /// where conditions compare numbers like `rank < 100` or `layer >= 2`, check `exists(value_name)`, and combine
+    /// these with `not`, `and` and `or`. `$for(i, value_name, limit=5) ... $end` repeats its body for the indices
+    /// along the first axis of a value, optionally in the order of its numbers with `sort="descending"`. Within the
+    /// body, `$value` and `$heatmap` can index values with the loop variable, as in `$value(scores[i])`.
+    pub fn parse<S: AsRef<str>>(template_code: S) -> Result<Self, TemplateParseError> {
         Self::parse_inner(template_code.as_ref())
     }
 
     pub fn generate(
         &self,
         payload: &crate::Payload,
+        annotations: &Annotations,
         file: bool,
         layer_index: usize,
         neuron_index: usize,
+        images: &PageImages,
     ) -> Markup {
-        let mut body = self.prefix.to_string();
-        for (element, template_part) in &self.template {
-            let element_markup = element.generate(payload, layer_index, neuron_index);
-            body.push_str(&element_markup.into_string());
-            body.push_str(template_part);
-        }
+        let body = self.tree.generate(&Page {
+            payload,
+            annotations,
+            file,
+            scope: Scope::Neuron,
+            layer_index,
+            neuron_index,
+            images,
+        });
 
+        let original_layer_index = payload.original_layer_index(layer_index);
+        let original_neuron_index = payload.original_neuron_index(neuron_index);
+        let neuron_annotation = annotations.get(original_layer_index, original_neuron_index);
         html!(
             (DOCTYPE)
             head {
                 meta charset="utf-8";
-                title { "Transformer Scope - Layer " (layer_index) " Neuron " (neuron_index)}
+                title { "Transformer Scope - Layer " (original_layer_index) " Neuron " (original_neuron_index)}
                 link rel="stylesheet" href={(if file {".."} else {""})"/static/style.css"}{};
             }
             a href={(if file {"../index.html"} else {"/"})} {"Back to index"}
-            h1 {"Transformer Scope - Layer " (layer_index) " Neuron " (neuron_index)}
-            (generate_navigation_links(payload.num_layers(), payload.num_mlp_neurons(), layer_index, neuron_index, file))
+            " - "
+            a href=(layer_link(original_layer_index, false, file)) {"Layer " (original_layer_index)}
+            h1 {"Transformer Scope - Layer " (original_layer_index) " Neuron " (original_neuron_index)}
+            (annotation::annotation(neuron_annotation))
+            // Annotations can only be edited when served, since static sites are read-only.
+            @if !file {
+                (annotation::annotation_form(neuron_annotation, &format!("/L{original_layer_index}/N{original_neuron_index}")))
+            }
+            (generate_navigation_links(payload, layer_index, neuron_index, file))
             (PreEscaped(body))
         )
     }
 
+    /// Replaces every `$include` by the nodes of the included partial.
+    pub(crate) fn resolve_includes(
+        &self,
+        partials: &BTreeMap<String, Partial>,
+    ) -> Result<Self, ArgumentError> {
+        let tree = self.tree.resolve_includes(partials)?;
+        Ok(Self { tree })
+    }
+
+    /// The values shown by the template with the given partials included, with the data types, scopes and numbers of
+    /// axes they may have.
+    pub fn schema(
+        &self,
+        partials: &BTreeMap<String, Partial>,
+    ) -> Result<PayloadSchema, ArgumentError> {
+        Ok(self.tree.resolve_includes(partials)?.schema(Scope::Neuron))
+    }
+
     pub fn validate_arguments(&self, payload: &Payload) -> Result<(), ArgumentError> {
-        for element in self.template.iter().map(|(element, _)| element) {
-            element.validate_arguments(payload)?;
-        }
-        Ok(())
+        self.tree.validate(payload, Scope::Neuron)
     }
 }
 
 fn generate_navigation_links(
-    num_layers: usize,
-    num_neurons: usize,
+    payload: &Payload,
     layer_index: usize,
     neuron_index: usize,
     file: bool,
 ) -> Markup {
+    let num_layers = payload.num_layers();
+    let num_neurons = payload.num_mlp_neurons();
     let file_extension = if file { ".html" } else { "" };
     let previous_neuron_link = if neuron_index > 0 {
         html! {
-            a href={"N"({neuron_index-1})(file_extension)} {
+            a href={"N"(payload.original_neuron_index(neuron_index-1))(file_extension)} {
                 "Previous"
             }
         }
     } else if layer_index > 0 {
         html! {
-            a href={"../L"({layer_index-1})"/N"({num_neurons-1})(file_extension)} {
+            a href={"../L"(payload.original_layer_index(layer_index-1))"/N"(payload.original_neuron_index(num_neurons-1))(file_extension)} {
                 "Previous layer"
             }
         }
@@ -94,13 +132,13 @@ fn generate_navigation_links(
 
     let next_neuron_link = if neuron_index < num_neurons - 1 {
         html! {
-            a href={"N"({neuron_index+1})(file_extension)} {
+            a href={"N"(payload.original_neuron_index(neuron_index+1))(file_extension)} {
                 "Next"
             }
         }
     } else if layer_index < num_layers - 1 {
         html! {
-            a href={"../L"({layer_index+1})"/N0"(file_extension)} {
+            a href={"../L"(payload.original_layer_index(layer_index+1))"/N"(payload.original_neuron_index(0))(file_extension)} {
                 "Next layer"
             }
         }
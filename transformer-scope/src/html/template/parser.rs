@@ -0,0 +1,812 @@
+use std::fmt::Display;
+
+use thiserror::Error;
+
+/// A value together with the byte offset in the template source where it starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub offset: usize,
+}
+
+impl<T> Spanned<T> {
+    fn new(value: T, offset: usize) -> Self {
+        Self { value, offset }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// HTML between elements.
+    Text(String),
+    Dollar,
+    Identifier(String),
+    OpenParen,
+    CloseParen,
+    Comma,
+    Equals,
+    /// A positional argument, the name or value of a keyword argument.
+    Word(String),
+    /// A quoted string argument, without quotes and escapes.
+    Quoted(String),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Text(_) => write!(f, "text"),
+            Token::Dollar => write!(f, "'$'"),
+            Token::Identifier(identifier) => write!(f, "'{identifier}'"),
+            Token::OpenParen => write!(f, "'('"),
+            Token::CloseParen => write!(f, "')'"),
+            Token::Comma => write!(f, "','"),
+            Token::Equals => write!(f, "'='"),
+            Token::Word(word) => write!(f, "'{word}'"),
+            Token::Quoted(string) => write!(f, "{string:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TemplateParseErrorKind {
+    #[error("Expected an element name after '$'")]
+    MissingElementName,
+    #[error("Expected '(' after the element name '{0}'")]
+    MissingArguments(String),
+    #[error("The arguments of the element '{0}' are not closed with ')'")]
+    UnclosedArguments(String),
+    #[error("Unexpected character '{0}' in element arguments")]
+    UnexpectedCharacter(char),
+    #[error("The string is not closed with a matching quote")]
+    UnterminatedString,
+    #[error("Expected {expected} but found {found}")]
+    UnexpectedToken { expected: String, found: String },
+    #[error("Unknown element '{name}'{}", did_you_mean(suggestions))]
+    UnknownElement {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    #[error("The element '{element}' takes {expected} but was given {found}")]
+    ArgumentCount {
+        element: String,
+        expected: String,
+        found: usize,
+    },
+    #[error("Invalid argument '{argument}' to the element '{element}': {reason}")]
+    InvalidArgument {
+        element: String,
+        argument: String,
+        reason: String,
+    },
+    #[error("Invalid condition '{condition}': {reason}")]
+    InvalidCondition { condition: String, reason: String },
+    #[error("'${0}' does not close an open '$if' or '$for' block")]
+    UnexpectedBlockEnd(String),
+    #[error("The block '${0}' is not closed with '$end'")]
+    UnclosedBlock(String),
+    #[error("The loop variable '{0}' is not defined by an enclosing '$for' block")]
+    UnboundLoopVariable(String),
+    #[error("Invalid partial parameter '{0}'. Parameters are names in double braces like '{{{{value}}}}'")]
+    InvalidParameter(String),
+}
+
+pub(super) fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [suggestion] => format!(". Did you mean '{suggestion}'?"),
+        suggestions => format!(
+            ". Did you mean one of {}?",
+            suggestions
+                .iter()
+                .map(|suggestion| format!("'{suggestion}'"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// An error in a template, located by line and column, both starting at 1.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub struct TemplateParseError {
+    pub kind: TemplateParseErrorKind,
+    pub line: usize,
+    pub column: usize,
+    /// The line of the template containing the error.
+    pub snippet: String,
+}
+
+impl TemplateParseError {
+    pub(super) fn new(kind: TemplateParseErrorKind, source: &str, offset: usize) -> Self {
+        let line_start = source[..offset].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |index| offset + index);
+        Self {
+            kind,
+            line: source[..offset].matches('\n').count() + 1,
+            column: source[line_start..offset].chars().count() + 1,
+            snippet: source[line_start..line_end]
+                .trim_end_matches('\r')
+                .to_string(),
+        }
+    }
+}
+
+impl Display for TemplateParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Point at the error below the snippet.
+        let marker = " ".repeat(self.column - 1);
+        write!(
+            f,
+            "{} at line {}, column {}:\n{}\n{marker}^",
+            self.kind, self.line, self.column, self.snippet
+        )
+    }
+}
+
+/// A failed parse at the given byte offset, turned into a [`TemplateParseError`] once the source is known.
+pub(super) type ParseResult<T> = Result<T, Spanned<TemplateParseErrorKind>>;
+
+fn is_identifier_character(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+fn is_word_character(character: char) -> bool {
+    !character.is_whitespace() && !matches!(character, ')' | ',' | '=' | '$' | '"' | '\'')
+}
+
+type Characters<'a> = std::iter::Peekable<std::str::CharIndices<'a>>;
+
+fn take_while(characters: &mut Characters, predicate: fn(char) -> bool) -> String {
+    let mut taken = String::new();
+    while let Some(&(_, character)) = characters.peek() {
+        if !predicate(character) {
+            break;
+        }
+        taken.push(character);
+        characters.next();
+    }
+    taken
+}
+
+/// Blocks taking no arguments, which may be written without parentheses, e.g. `$else`.
+const BLOCKS_WITHOUT_ARGUMENTS: [&str; 2] = ["else", "end"];
+/// Blocks whose single argument is a condition, which is kept as written up to the closing parenthesis.
+const CONDITION_BLOCKS: [&str; 2] = ["if", "elif"];
+
+/// Text up to the next element. `$$` is an escaped literal `$`.
+fn text(characters: &mut Characters) -> String {
+    let mut text = String::new();
+    while let Some(&(_, character)) = characters.peek() {
+        if character == '$' {
+            let mut lookahead = characters.clone();
+            lookahead.next();
+            if !matches!(lookahead.peek(), Some((_, '$'))) {
+                break;
+            }
+            characters.next();
+        }
+        text.push(character);
+        characters.next();
+    }
+    text
+}
+
+/// Text with balanced parentheses up to a character for which `ends` is true outside of parentheses, or up to an
+/// unmatched `)`. Quoted strings are kept as written, so parentheses in them are not counted.
+fn balanced(characters: &mut Characters, ends: fn(char) -> bool) -> ParseResult<String> {
+    let mut text = String::new();
+    let mut depth = 0usize;
+    while let Some(&(_, character)) = characters.peek() {
+        match character {
+            '"' | '\'' if depth > 0 || !ends(character) => {
+                text.push_str(&quoted_as_written(characters)?);
+                continue;
+            }
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ')' => break,
+            character if depth == 0 && ends(character) => break,
+            _ => {}
+        }
+        text.push(character);
+        characters.next();
+    }
+    Ok(text)
+}
+
+/// An unquoted argument. Parentheses in it must be balanced, and everything inside them, including commas and
+/// whitespace, is part of the argument, e.g. `f(a, b)`.
+fn word(characters: &mut Characters) -> ParseResult<String> {
+    balanced(characters, |character| !is_word_character(character))
+}
+
+/// A condition up to the parenthesis closing the arguments, including any nested parentheses and quoted value names.
+fn condition(characters: &mut Characters) -> ParseResult<String> {
+    balanced(characters, |_| false)
+}
+
+/// A string in single or double quotes as written, including the quotes and escapes.
+fn quoted_as_written(characters: &mut Characters) -> ParseResult<String> {
+    let (quote_offset, quote) = characters.next().unwrap();
+    let mut string = quote.to_string();
+    loop {
+        match characters.next() {
+            Some((_, character)) if character == quote => {
+                string.push(character);
+                return Ok(string);
+            }
+            Some((_, '\\')) => match characters.next() {
+                Some((_, character)) => {
+                    string.push('\\');
+                    string.push(character);
+                }
+                None => break,
+            },
+            Some((_, character)) => string.push(character),
+            None => break,
+        }
+    }
+    Err(Spanned::new(
+        TemplateParseErrorKind::UnterminatedString,
+        quote_offset,
+    ))
+}
+
+/// The content of a string written in quotes, without the quotes. A backslash escapes the following character.
+pub(super) fn unquote(quoted: &str) -> String {
+    let mut string = String::new();
+    let mut characters = quoted[1..quoted.len() - 1].chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => string.extend(characters.next()),
+            character => string.push(character),
+        }
+    }
+    string
+}
+
+/// A string argument in single or double quotes. A backslash escapes the following character.
+fn quoted(characters: &mut Characters) -> ParseResult<String> {
+    quoted_as_written(characters).map(|string| unquote(&string))
+}
+
+/// Splits the source into tokens. Element arguments are only tokenized between the parentheses following an element name.
+fn tokenize(source: &str) -> ParseResult<Vec<Spanned<Token>>> {
+    let mut tokens = Vec::new();
+    let mut characters = source.char_indices().peekable();
+
+    while let Some(&(offset, _)) = characters.peek() {
+        let text = text(&mut characters);
+        if !text.is_empty() {
+            tokens.push(Spanned::new(Token::Text(text), offset));
+            continue;
+        }
+        let (offset, _) = characters.next().unwrap();
+        tokens.push(Spanned::new(Token::Dollar, offset));
+
+        let Some(&(name_offset, _)) = characters.peek() else {
+            break;
+        };
+        let name = take_while(&mut characters, is_identifier_character);
+        let is_condition = CONDITION_BLOCKS.contains(&name.as_str());
+        if !name.is_empty() {
+            tokens.push(Spanned::new(Token::Identifier(name), name_offset));
+        }
+        match characters.peek() {
+            Some(&(paren_offset, '(')) => {
+                characters.next();
+                tokens.push(Spanned::new(Token::OpenParen, paren_offset));
+            }
+            _ => continue,
+        }
+
+        if is_condition {
+            take_while(&mut characters, char::is_whitespace);
+            if let Some(&(offset, _)) = characters.peek() {
+                let condition = condition(&mut characters)?;
+                if !condition.trim().is_empty() {
+                    tokens.push(Spanned::new(
+                        Token::Word(condition.trim_end().to_string()),
+                        offset,
+                    ));
+                }
+            }
+        }
+
+        // Arguments up to the closing parenthesis.
+        while let Some(&(offset, character)) = characters.peek() {
+            let token = match character {
+                ')' => Token::CloseParen,
+                ',' => Token::Comma,
+                '=' => Token::Equals,
+                '"' | '\'' => {
+                    let string = quoted(&mut characters)?;
+                    tokens.push(Spanned::new(Token::Quoted(string), offset));
+                    continue;
+                }
+                character if character.is_whitespace() => {
+                    characters.next();
+                    continue;
+                }
+                character if is_word_character(character) || character == '(' => {
+                    let word = word(&mut characters)?;
+                    tokens.push(Spanned::new(Token::Word(word), offset));
+                    continue;
+                }
+                character => {
+                    return Err(Spanned::new(
+                        TemplateParseErrorKind::UnexpectedCharacter(character),
+                        offset,
+                    ))
+                }
+            };
+            characters.next();
+            tokens.push(Spanned::new(token.clone(), offset));
+            if token == Token::CloseParen {
+                break;
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// An argument of an element, either positional like `acts` or a keyword argument like `bins=20`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Argument {
+    pub name: Option<Spanned<String>>,
+    pub value: Spanned<String>,
+}
+
+/// An element as written in the template, e.g. `$histogram(acts, bins=20)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementCall {
+    pub name: Spanned<String>,
+    pub arguments: Vec<Argument>,
+}
+
+/// A template split into the text before the first element and every element with the text following it.
+pub(super) struct ParsedTemplate {
+    pub prefix: String,
+    pub parts: Vec<(ElementCall, String)>,
+}
+
+struct Parser {
+    tokens: std::iter::Peekable<std::vec::IntoIter<Spanned<Token>>>,
+    source_len: usize,
+}
+
+impl Parser {
+    fn next_offset(&mut self) -> usize {
+        self.tokens
+            .peek()
+            .map_or(self.source_len, |token| token.offset)
+    }
+
+    fn unexpected<T>(&mut self, expected: &str) -> ParseResult<T> {
+        let offset = self.next_offset();
+        let found = self
+            .tokens
+            .peek()
+            .map_or("the end of the template".to_string(), |token| {
+                token.value.to_string()
+            });
+        Err(Spanned::new(
+            TemplateParseErrorKind::UnexpectedToken {
+                expected: expected.to_string(),
+                found,
+            },
+            offset,
+        ))
+    }
+
+    fn text(&mut self) -> String {
+        match self.tokens.peek() {
+            Some(Spanned {
+                value: Token::Text(_),
+                ..
+            }) => match self.tokens.next().unwrap().value {
+                Token::Text(text) => text,
+                _ => unreachable!(),
+            },
+            _ => String::new(),
+        }
+    }
+
+    fn element(&mut self) -> ParseResult<ElementCall> {
+        let dollar_offset = self.tokens.next().unwrap().offset;
+        let name = match self.tokens.next() {
+            Some(Spanned {
+                value: Token::Identifier(name),
+                offset,
+            }) => Spanned::new(name, offset),
+            _ => {
+                return Err(Spanned::new(
+                    TemplateParseErrorKind::MissingElementName,
+                    dollar_offset,
+                ))
+            }
+        };
+        let open_offset = match self.tokens.peek() {
+            Some(Spanned {
+                value: Token::OpenParen,
+                offset,
+            }) => {
+                let offset = *offset;
+                self.tokens.next();
+                offset
+            }
+            _ if BLOCKS_WITHOUT_ARGUMENTS.contains(&name.value.as_str()) => {
+                return Ok(ElementCall {
+                    name,
+                    arguments: Vec::new(),
+                })
+            }
+            _ => {
+                return Err(Spanned::new(
+                    TemplateParseErrorKind::MissingArguments(name.value),
+                    name.offset,
+                ))
+            }
+        };
+
+        let mut arguments = Vec::new();
+        loop {
+            match self.tokens.peek() {
+                None => {
+                    return Err(Spanned::new(
+                        TemplateParseErrorKind::UnclosedArguments(name.value),
+                        open_offset,
+                    ))
+                }
+                Some(Spanned {
+                    value: Token::CloseParen,
+                    ..
+                }) if arguments.is_empty() => break,
+                _ => {}
+            }
+            arguments.push(self.argument()?);
+            match self.tokens.peek().map(|token| &token.value) {
+                Some(Token::Comma) => {
+                    self.tokens.next();
+                }
+                Some(Token::CloseParen) => break,
+                None => {
+                    return Err(Spanned::new(
+                        TemplateParseErrorKind::UnclosedArguments(name.value),
+                        open_offset,
+                    ))
+                }
+                _ => return self.unexpected("',' or ')'"),
+            }
+        }
+        // Closing parenthesis.
+        self.tokens.next();
+        Ok(ElementCall { name, arguments })
+    }
+
+    /// An argument value, either a word or a quoted string. Returns whether the value was quoted.
+    fn argument_value(&mut self) -> ParseResult<(Spanned<String>, bool)> {
+        match self.tokens.peek().map(|token| &token.value) {
+            Some(Token::Word(_) | Token::Quoted(_)) => {
+                let token = self.tokens.next().unwrap();
+                match token.value {
+                    Token::Word(word) => Ok((Spanned::new(word, token.offset), false)),
+                    Token::Quoted(string) => Ok((Spanned::new(string, token.offset), true)),
+                    _ => unreachable!(),
+                }
+            }
+            _ => self.unexpected("an argument"),
+        }
+    }
+
+    fn argument(&mut self) -> ParseResult<Argument> {
+        let (first, quoted) = self.argument_value()?;
+        // Only unquoted words can name keyword arguments.
+        if !quoted
+            && matches!(
+                self.tokens.peek().map(|token| &token.value),
+                Some(Token::Equals)
+            )
+        {
+            self.tokens.next();
+            let (value, _) = self.argument_value()?;
+            Ok(Argument {
+                name: Some(first),
+                value,
+            })
+        } else {
+            Ok(Argument {
+                name: None,
+                value: first,
+            })
+        }
+    }
+
+    fn template(&mut self) -> ParseResult<ParsedTemplate> {
+        let prefix = self.text();
+        let mut parts = Vec::new();
+        while self.tokens.peek().is_some() {
+            let element = self.element()?;
+            parts.push((element, self.text()));
+        }
+        Ok(ParsedTemplate { prefix, parts })
+    }
+}
+
+pub(super) fn parse_template(source: &str) -> Result<ParsedTemplate, TemplateParseError> {
+    let parse = || {
+        let tokens = tokenize(source)?;
+        Parser {
+            tokens: tokens.into_iter().peekable(),
+            source_len: source.len(),
+        }
+        .template()
+    };
+    parse().map_err(|error| TemplateParseError::new(error.value, source, error.offset))
+}
+
+/// Edit distance between two strings, used to suggest element names.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_character) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, &b_character) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_character != b_character);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The known names closest to `name`, for error messages.
+pub(super) fn suggestions(name: &str, known_names: &[&str]) -> Vec<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut suggestions: Vec<_> = known_names
+        .iter()
+        .map(|known_name| (edit_distance(name, known_name), *known_name))
+        .filter(|&(distance, known_name)| {
+            distance <= max_distance || known_name.starts_with(name) || name.starts_with(known_name)
+        })
+        .collect();
+    suggestions.sort();
+    suggestions
+        .into_iter()
+        .map(|(_, known_name)| known_name.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_template, Argument, Spanned, TemplateParseErrorKind};
+    use crate::html::template::{control_flow::Condition, NeuronTemplate};
+
+    fn error(template: &str) -> (TemplateParseErrorKind, usize, usize) {
+        let error = NeuronTemplate::parse(template).unwrap_err();
+        (error.kind, error.line, error.column)
+    }
+
+    fn arguments(template: &str) -> Vec<Argument> {
+        let mut parsed = parse_template(template).unwrap();
+        assert_eq!(parsed.parts.len(), 1);
+        parsed.parts.remove(0).0.arguments
+    }
+
+    fn positional(value: &str, offset: usize) -> Argument {
+        Argument {
+            name: None,
+            value: Spanned::new(value.to_string(), offset),
+        }
+    }
+
+    #[test]
+    fn unknown_elements_are_located_with_suggestions() {
+        assert_eq!(
+            error("<p>\n  $histgram(acts)</p>"),
+            (
+                TemplateParseErrorKind::UnknownElement {
+                    name: "histgram".to_string(),
+                    suggestions: vec!["histogram".to_string()],
+                },
+                2,
+                4
+            )
+        );
+    }
+
+    #[test]
+    fn unbalanced_parentheses_are_located_at_the_arguments() {
+        assert_eq!(
+            error("a $value(f(acts)"),
+            (
+                TemplateParseErrorKind::UnclosedArguments("value".to_string()),
+                1,
+                9
+            )
+        );
+        assert_eq!(
+            error("$value(acts"),
+            (
+                TemplateParseErrorKind::UnclosedArguments("value".to_string()),
+                1,
+                7
+            )
+        );
+    }
+
+    #[test]
+    fn unterminated_strings_are_located_at_the_opening_quote() {
+        assert_eq!(
+            error("\n$value(acts, title=\"Acts)"),
+            (TemplateParseErrorKind::UnterminatedString, 2, 20)
+        );
+        assert_eq!(
+            error("$if(exists('acts)) x $end"),
+            (TemplateParseErrorKind::UnterminatedString, 1, 12)
+        );
+    }
+
+    #[test]
+    fn double_dollars_are_literal_dollars() {
+        let parsed = parse_template("Costs $$5 $value(acts) or $$$$10").unwrap();
+        assert_eq!(parsed.prefix, "Costs $5 ");
+        assert_eq!(parsed.parts[0].0.name.value, "value");
+        assert_eq!(parsed.parts[0].1, " or $$10");
+    }
+
+    #[test]
+    fn quoted_arguments_resolve_backslash_escapes() {
+        assert_eq!(
+            arguments(r#"$value("my \"value\", \\ (1)", 'it\'s')"#),
+            vec![
+                positional(r#"my "value", \ (1)"#, 7),
+                positional("it's", 31)
+            ]
+        );
+    }
+
+    #[test]
+    fn unquoted_arguments_keep_balanced_parentheses() {
+        assert_eq!(
+            arguments(r#"$value(f(a, ")"), b)"#),
+            vec![positional(r#"f(a, ")")"#, 7), positional("b", 18)]
+        );
+    }
+
+    #[test]
+    fn keyword_arguments_follow_positional_arguments() {
+        assert_eq!(
+            arguments(r#"$histogram(acts, bins = 20, title="Bins = 20")"#),
+            vec![
+                positional("acts", 11),
+                Argument {
+                    name: Some(Spanned::new("bins".to_string(), 17)),
+                    value: Spanned::new("20".to_string(), 24),
+                },
+                Argument {
+                    name: Some(Spanned::new("title".to_string(), 28)),
+                    value: Spanned::new("Bins = 20".to_string(), 34),
+                },
+            ]
+        );
+        assert!(matches!(
+            error(r#"$histogram("bins"=20)"#),
+            (TemplateParseErrorKind::UnexpectedToken { .. }, 1, 18)
+        ));
+        assert!(matches!(
+            error("$histogram(acts, bins=20, bins=30)"),
+            (TemplateParseErrorKind::InvalidArgument { .. }, 1, 27)
+        ));
+    }
+
+    #[test]
+    fn invalid_keyword_values_are_located_where_they_are_given() {
+        assert_eq!(
+            error("$top_k(logits, labels, 0)"),
+            (
+                TemplateParseErrorKind::InvalidArgument {
+                    element: "top_k".to_string(),
+                    argument: "0".to_string(),
+                    reason: "'k' must be a positive integer".to_string(),
+                },
+                1,
+                24
+            )
+        );
+        assert!(matches!(
+            error("$top_k(logits, labels, k=0)"),
+            (TemplateParseErrorKind::InvalidArgument { .. }, 1, 26)
+        ));
+        assert!(matches!(
+            error("$histogram(acts, bins=0)"),
+            (TemplateParseErrorKind::InvalidArgument { .. }, 1, 23)
+        ));
+        assert!(matches!(
+            error("$for(i, scores, sort=up) $end"),
+            (TemplateParseErrorKind::InvalidArgument { .. }, 1, 22)
+        ));
+    }
+
+    #[test]
+    fn conditions_keep_quoted_parentheses() {
+        let parsed = parse_template(r#"$if(exists("a)b") and "c d" > 1)x$end"#).unwrap();
+        let condition = &parsed.parts[0].0.arguments[0].value.value;
+        assert_eq!(condition, r#"exists("a)b") and "c d" > 1"#);
+        assert!(matches!(
+            Condition::parse(condition).unwrap(),
+            Condition::And(left, right)
+                if matches!(left.as_ref(), Condition::Exists(value) if value == "a)b")
+                    && matches!(right.as_ref(), Condition::Compare { value, .. } if value == "c d")
+        ));
+    }
+
+    #[test]
+    fn blocks_nest() {
+        NeuronTemplate::parse(
+            "$if(layer > 1)a$elif(neuron < 2)$for(i, scores)$value(scores[i])$end$else c$end",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn misplaced_block_ends_are_located() {
+        assert_eq!(
+            error("a\n$end"),
+            (
+                TemplateParseErrorKind::UnexpectedBlockEnd("end".to_string()),
+                2,
+                2
+            )
+        );
+        assert_eq!(
+            error("$for(i, scores) $else $end"),
+            (
+                TemplateParseErrorKind::UnexpectedBlockEnd("else".to_string()),
+                1,
+                18
+            )
+        );
+        assert_eq!(
+            error("$if(layer > 1) a $else b $elif(layer > 0) c $end"),
+            (
+                TemplateParseErrorKind::UnexpectedBlockEnd("elif".to_string()),
+                1,
+                27
+            )
+        );
+    }
+
+    #[test]
+    fn unclosed_blocks_are_located_at_their_start() {
+        assert_eq!(
+            error("$for(i, scores) $if(i > 0) x $end"),
+            (
+                TemplateParseErrorKind::UnclosedBlock("for".to_string()),
+                1,
+                2
+            )
+        );
+        assert_eq!(
+            error("$if(layer > 1)\n  $for(i, scores) x $end"),
+            (
+                TemplateParseErrorKind::UnclosedBlock("if".to_string()),
+                1,
+                2
+            )
+        );
+    }
+
+    #[test]
+    fn loop_variables_are_bound_by_enclosing_loops() {
+        assert_eq!(
+            error("$for(i, scores) $end $value(scores[i])"),
+            (
+                TemplateParseErrorKind::UnboundLoopVariable("i".to_string()),
+                1,
+                29
+            )
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    parser::{ParseResult, Spanned, TemplateParseErrorKind},
+    TemplateParseError,
+};
+
+/// A named part of a template, stored in the payload and included in templates with
+/// `$include(partial_name, parameter=argument, ...)`.
+///
+/// Parameters are written as `{{parameter}}` anywhere in the partial, including within element arguments, e.g.
+/// `$heatmap({{value}}, title="{{caption}}")`. Including the partial replaces them with the given arguments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Partial {
+    source: String,
+    parameters: BTreeSet<String>,
+}
+
+fn is_parameter_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|first| !first.is_numeric())
+        && name
+            .chars()
+            .all(|character| character.is_alphanumeric() || character == '_')
+}
+
+/// Splits the source into the text between parameters and the parameter names. Returns whether each piece is a
+/// parameter.
+fn split_parameters(source: &str) -> ParseResult<Vec<(&str, bool)>> {
+    let mut pieces = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = source[offset..].find("{{").map(|start| offset + start) {
+        let name_start = start + 2;
+        let name = source[name_start..]
+            .find("}}")
+            .map(|end| source[name_start..name_start + end].trim())
+            .filter(|name| is_parameter_name(name));
+        let Some(name) = name else {
+            let line_end = source[name_start..]
+                .find('\n')
+                .map_or(source.len(), |end| name_start + end);
+            return Err(Spanned {
+                value: TemplateParseErrorKind::InvalidParameter(
+                    source[start..line_end].to_string(),
+                ),
+                offset: start,
+            });
+        };
+        pieces.push((&source[offset..start], false));
+        pieces.push((name, true));
+        offset = name_start + source[name_start..].find("}}").unwrap() + 2;
+    }
+    pieces.push((&source[offset..], false));
+    Ok(pieces)
+}
+
+impl Partial {
+    /// Parses the parameters of a partial. The rest of the partial is parsed when it is included, after replacing
+    /// its parameters.
+    pub fn parse<S: AsRef<str>>(source: S) -> Result<Self, TemplateParseError> {
+        let source = source.as_ref();
+        let parameters = split_parameters(source)
+            .map_err(|error| TemplateParseError::new(error.value, source, error.offset))?
+            .into_iter()
+            .filter(|(_, is_parameter)| *is_parameter)
+            .map(|(parameter, _)| parameter.to_string())
+            .collect();
+        Ok(Self {
+            source: source.to_string(),
+            parameters,
+        })
+    }
+
+    pub fn parameters(&self) -> impl Iterator<Item = &str> {
+        self.parameters.iter().map(String::as_str)
+    }
+
+    /// The source of the partial with every parameter replaced by its argument.
+    /// Every parameter must have an argument, which is guaranteed when including the partial.
+    pub(super) fn substitute(&self, arguments: &BTreeMap<String, String>) -> String {
+        split_parameters(&self.source)
+            .expect("The parameters were checked when parsing.")
+            .into_iter()
+            .map(|(piece, is_parameter)| {
+                if is_parameter {
+                    arguments[piece].as_str()
+                } else {
+                    piece
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::Partial;
+    use crate::html::template::TemplateParseErrorKind;
+
+    #[test]
+    fn parameters_are_replaced_by_their_arguments() {
+        let partial =
+            Partial::parse("<h2>{{ caption }}</h2>$heatmap({{value}}, title=\"{{caption}}\")")
+                .unwrap();
+        assert!(partial.parameters().eq(["caption", "value"]));
+        let arguments = BTreeMap::from([
+            ("caption".to_string(), "Step 1".to_string()),
+            ("value".to_string(), "boards[i]".to_string()),
+        ]);
+        assert_eq!(
+            partial.substitute(&arguments),
+            "<h2>Step 1</h2>$heatmap(boards[i], title=\"Step 1\")"
+        );
+    }
+
+    #[test]
+    fn invalid_parameters_are_located() {
+        for (source, parameter, column) in [
+            ("a\n  {{1x}} b", "{{1x}} b", 3),
+            ("{{value}} {{ }}", "{{ }}", 11),
+            ("$heatmap({{value)\nnext", "{{value)", 10),
+        ] {
+            let error = Partial::parse(source).unwrap_err();
+            assert_eq!(
+                error.kind,
+                TemplateParseErrorKind::InvalidParameter(parameter.to_string())
+            );
+            assert_eq!(error.column, column);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use std::fmt::Display;
+
+use thiserror::Error;
+
+use crate::data::{
+    value::{DataType, Scope},
+    Value,
+};
+
+use super::element::VOCABULARY_LABELS;
+
+/// Joins items like "F32, U32 or String".
+fn one_of<T: Display>(items: &[T]) -> String {
+    let items: Vec<String> = items.iter().map(ToString::to_string).collect();
+    match items.split_last() {
+        None => String::new(),
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {last}", rest.join(", ")),
+    }
+}
+
+/// A value shown by a template, with the data types, scopes and number of axes it may have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueRequirement {
+    pub name: String,
+    pub data_types: Vec<DataType>,
+    pub scopes: Vec<Scope>,
+    /// The number of axes after the scope axes, if the template needs a certain number.
+    pub axes: Option<usize>,
+    /// Whether the value is only shown within `$if` blocks checking which values exist, so the payload may lack it.
+    pub conditional: bool,
+}
+
+impl ValueRequirement {
+    /// A requirement of a value shown on pages of the given scope, which may show values of coarser scopes.
+    pub(super) fn new(
+        name: impl Into<String>,
+        data_types: &[DataType],
+        page_scope: Scope,
+        axes: Option<usize>,
+    ) -> Self {
+        let scopes = [Scope::Global, Scope::Layer, Scope::Neuron]
+            .into_iter()
+            .filter(|scope| scope.is_within(page_scope))
+            .collect();
+        Self {
+            name: name.into(),
+            data_types: data_types.to_vec(),
+            scopes,
+            axes,
+            conditional: false,
+        }
+    }
+
+    /// A requirement of a number for every neuron, as shown on layer pages.
+    pub(super) fn neuron_numbers(name: impl Into<String>) -> Self {
+        Self {
+            scopes: vec![Scope::Neuron],
+            ..Self::new(
+                name,
+                &[DataType::F32, DataType::U32],
+                Scope::Neuron,
+                Some(0),
+            )
+        }
+    }
+
+    pub fn check(&self, value: &Value) -> Result<(), SchemaError> {
+        if !self.data_types.contains(&value.data_type()) {
+            return Err(SchemaError::DataType {
+                name: self.name.clone(),
+                required_data_types: one_of(&self.data_types),
+                found_data_type: value.data_type(),
+            });
+        }
+        if !self.scopes.contains(&value.scope()) {
+            return Err(SchemaError::Scope {
+                name: self.name.clone(),
+                required_scopes: one_of(&self.scopes),
+                found_scope: value.scope(),
+            });
+        }
+        let found_axis_num = value.shape().len() - value.scope().num_axes();
+        match self.axes {
+            Some(required_axis_num) if required_axis_num != found_axis_num => {
+                Err(SchemaError::AxisNum {
+                    name: self.name.clone(),
+                    required_axis_num,
+                    found_axis_num,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Display for ValueRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} with scope {}",
+            self.name,
+            one_of(&self.data_types),
+            one_of(&self.scopes)
+        )?;
+        match self.axes {
+            Some(1) => write!(f, " and 1 axis after the scope")?,
+            Some(axes) => write!(f, " and {axes} axes after the scope")?,
+            None => {}
+        }
+        if self.conditional {
+            write!(f, " (if it exists)")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum SchemaError {
+    #[error("Value {name} has data type {found_data_type}, but the templates show it as {required_data_types}.")]
+    DataType {
+        name: String,
+        required_data_types: String,
+        found_data_type: DataType,
+    },
+    #[error("Value {name} has scope {found_scope}, but the templates show it with scope {required_scopes}.")]
+    Scope {
+        name: String,
+        required_scopes: String,
+        found_scope: Scope,
+    },
+    #[error("Value {name} has {found_axis_num} axes after its scope, but the templates show it with {required_axis_num}.")]
+    AxisNum {
+        name: String,
+        required_axis_num: usize,
+        found_axis_num: usize,
+    },
+    #[error("Value name {0} is reserved, since templates use it for the token vocabulary, e.g. in '$top_k(logits, vocabulary)'.")]
+    ReservedName(String),
+}
+
+/// The values shown by the templates of a payload, derived from the parsed templates before any values are added.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PayloadSchema {
+    requirements: Vec<ValueRequirement>,
+}
+
+impl PayloadSchema {
+    /// Adds a requirement. Requirements differing only in whether they are conditional are merged.
+    pub(super) fn insert(&mut self, requirement: ValueRequirement) {
+        let existing = self.requirements.iter_mut().find(|existing| {
+            ValueRequirement {
+                conditional: requirement.conditional,
+                ..(*existing).clone()
+            } == requirement
+        });
+        match existing {
+            Some(existing) => existing.conditional &= requirement.conditional,
+            None => self.requirements.push(requirement),
+        }
+    }
+
+    pub fn extend(&mut self, schema: PayloadSchema) {
+        for requirement in schema.requirements {
+            self.insert(requirement);
+        }
+    }
+
+    /// The requirements sorted by value name. A value shown in several ways has one requirement for every way.
+    pub fn requirements(&self) -> Vec<&ValueRequirement> {
+        let mut requirements: Vec<_> = self.requirements.iter().collect();
+        requirements.sort_by_key(|requirement| &requirement.name);
+        requirements
+    }
+
+    /// Checks a value against every requirement of its name. Values the templates do not show always pass.
+    pub fn check(&self, name: &str, value: &Value) -> Result<(), SchemaError> {
+        if name == VOCABULARY_LABELS {
+            return Err(SchemaError::ReservedName(name.to_string()));
+        }
+        self.requirements
+            .iter()
+            .filter(|requirement| requirement.name == name)
+            .try_for_each(|requirement| requirement.check(value))
+    }
+}
+
+impl Display for PayloadSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for requirement in self.requirements() {
+            writeln!(f, "{requirement}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array1;
+
+    use super::{PayloadSchema, SchemaError, ValueRequirement};
+    use crate::data::{
+        value::{DataType, Scope},
+        Value,
+    };
+
+    fn requirement(axes: Option<usize>, conditional: bool) -> ValueRequirement {
+        ValueRequirement {
+            conditional,
+            ..ValueRequirement::new("scores", &[DataType::F32], Scope::Neuron, axes)
+        }
+    }
+
+    #[test]
+    fn requirements_are_only_conditional_if_every_use_is() {
+        let mut schema = PayloadSchema::default();
+        schema.insert(requirement(Some(1), true));
+        schema.insert(requirement(Some(1), true));
+        assert_eq!(schema.requirements(), [&requirement(Some(1), true)]);
+        schema.insert(requirement(Some(1), false));
+        schema.insert(requirement(Some(1), true));
+        assert_eq!(schema.requirements(), [&requirement(Some(1), false)]);
+    }
+
+    #[test]
+    fn different_requirements_of_a_value_are_kept() {
+        let mut schema = PayloadSchema::default();
+        schema.insert(requirement(Some(1), false));
+        let mut other = PayloadSchema::default();
+        other.insert(requirement(None, true));
+        other.insert(requirement(Some(1), true));
+        schema.extend(other);
+        assert_eq!(
+            schema.requirements(),
+            [&requirement(Some(1), false), &requirement(None, true)]
+        );
+        assert_eq!(
+            schema.to_string(),
+            "scores: F32 with scope Global, Layer or Neuron and 1 axis after the scope\n\
+             scores: F32 with scope Global, Layer or Neuron (if it exists)\n"
+        );
+    }
+
+    #[test]
+    fn the_vocabulary_name_is_reserved() {
+        let value = Value::new(Array1::<f32>::zeros(3), Scope::Global);
+        assert!(matches!(
+            PayloadSchema::default().check("vocabulary", &value),
+            Err(SchemaError::ReservedName(name)) if name == "vocabulary"
+        ));
+        assert!(PayloadSchema::default().check("logits", &value).is_ok());
+    }
+}
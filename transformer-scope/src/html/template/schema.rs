@@ -0,0 +1,281 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use super::parser::Spanned;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentType {
+    Number,
+    /// A non-negative integer.
+    Integer,
+    String,
+    Bool,
+}
+
+impl Display for ArgumentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ArgumentType::Number => "a number",
+            ArgumentType::Integer => "a non-negative integer",
+            ArgumentType::String => "a string",
+            ArgumentType::Bool => "true or false",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A keyword argument an element accepts.
+#[derive(Debug, Clone, Copy)]
+pub struct KeywordSchema {
+    pub name: &'static str,
+    pub argument_type: ArgumentType,
+    /// Whether the argument may also be given positionally after the value names, as `k` in
+    /// `$top_k(value, labels, 5)`.
+    pub positional: bool,
+}
+
+/// The arguments an element accepts: value names as positional arguments, followed by optional keyword arguments.
+/// Keyword arguments marked as positional may be given in their order after the value names instead.
+#[derive(Debug, Clone, Copy)]
+pub struct ElementSchema {
+    pub name: &'static str,
+    pub positional: &'static [&'static str],
+    pub keywords: &'static [KeywordSchema],
+}
+
+impl ElementSchema {
+    pub fn keyword(&self, name: &str) -> Option<&KeywordSchema> {
+        self.keywords.iter().find(|keyword| keyword.name == name)
+    }
+
+    pub fn keyword_names(&self) -> Vec<&'static str> {
+        self.keywords.iter().map(|keyword| keyword.name).collect()
+    }
+
+    /// The keyword arguments which may be given positionally, in order.
+    pub fn positional_keywords(&self) -> impl Iterator<Item = &KeywordSchema> {
+        self.keywords.iter().filter(|keyword| keyword.positional)
+    }
+}
+
+pub(super) const fn keyword(name: &'static str, argument_type: ArgumentType) -> KeywordSchema {
+    KeywordSchema {
+        name,
+        argument_type,
+        positional: false,
+    }
+}
+
+/// A keyword argument which may also be given positionally after the value names.
+const fn positional_keyword(name: &'static str, argument_type: ArgumentType) -> KeywordSchema {
+    KeywordSchema {
+        name,
+        argument_type,
+        positional: true,
+    }
+}
+
+/// Shown as a heading above the element. Accepted by every element.
+const TITLE: KeywordSchema = keyword("title", ArgumentType::String);
+/// The keyword arguments of elements showing numbers as colours.
+const COLORMAP: KeywordSchema = keyword("colormap", ArgumentType::String);
+const NORM: KeywordSchema = keyword("norm", ArgumentType::String);
+const VMIN: KeywordSchema = keyword("vmin", ArgumentType::Number);
+const VMAX: KeywordSchema = keyword("vmax", ArgumentType::Number);
+/// Whether a matrix is drawn as a table or as an SVG or PNG image.
+const RENDER: KeywordSchema = keyword("render", ArgumentType::String);
+
+pub const ELEMENT_SCHEMAS: &[ElementSchema] = &[
+    ElementSchema {
+        name: "heatmap",
+        positional: &["value"],
+        keywords: &[
+            TITLE,
+            COLORMAP,
+            NORM,
+            VMIN,
+            VMAX,
+            keyword("row_labels", ArgumentType::String),
+            keyword("column_labels", ArgumentType::String),
+            keyword("cell_text", ArgumentType::String),
+            RENDER,
+        ],
+    },
+    ElementSchema {
+        name: "value",
+        positional: &["value"],
+        keywords: &[TITLE, keyword("precision", ArgumentType::Integer)],
+    },
+    ElementSchema {
+        name: "focus_sequences",
+        positional: &["activations", "step_names"],
+        keywords: &[TITLE, COLORMAP, NORM, VMIN, VMAX, RENDER],
+    },
+    ElementSchema {
+        name: "token_snippets",
+        positional: &["snippets"],
+        keywords: &[
+            TITLE,
+            keyword("limit", ArgumentType::Integer),
+            COLORMAP,
+            NORM,
+            VMIN,
+            VMAX,
+        ],
+    },
+    ElementSchema {
+        name: "neighbours",
+        positional: &["neighbours", "scores"],
+        keywords: &[TITLE],
+    },
+    ElementSchema {
+        name: "connections",
+        positional: &["connections"],
+        keywords: &[TITLE],
+    },
+    ElementSchema {
+        name: "histogram",
+        positional: &["value"],
+        keywords: &[TITLE, keyword("bins", ArgumentType::Integer)],
+    },
+    ElementSchema {
+        name: "top_k",
+        positional: &["value", "labels"],
+        keywords: &[TITLE, positional_keyword("k", ArgumentType::Integer)],
+    },
+    ElementSchema {
+        name: "layer_heatmap",
+        positional: &["value"],
+        keywords: &[
+            TITLE,
+            COLORMAP,
+            NORM,
+            VMIN,
+            VMAX,
+            keyword("columns", ArgumentType::Integer),
+        ],
+    },
+    ElementSchema {
+        name: "top_neurons",
+        positional: &["value"],
+        keywords: &[
+            TITLE,
+            keyword("limit", ArgumentType::Integer),
+            keyword("sort", ArgumentType::String),
+        ],
+    },
+    ElementSchema {
+        name: "statistics",
+        positional: &["value"],
+        keywords: &[TITLE],
+    },
+    ElementSchema {
+        name: "ranked_neurons",
+        positional: &[],
+        keywords: &[TITLE],
+    },
+    ElementSchema {
+        name: "clusters",
+        positional: &[],
+        keywords: &[TITLE],
+    },
+    ElementSchema {
+        name: "values",
+        positional: &[],
+        keywords: &[TITLE],
+    },
+    ElementSchema {
+        name: "search",
+        positional: &[],
+        keywords: &[TITLE],
+    },
+];
+
+pub fn element_schema(name: &str) -> Option<&'static ElementSchema> {
+    ELEMENT_SCHEMAS.iter().find(|schema| schema.name == name)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ArgumentValue {
+    Number(f64),
+    Integer(usize),
+    String(String),
+    Bool(bool),
+}
+
+impl ArgumentValue {
+    /// Parses the text of an argument as the given type.
+    pub fn parse(text: &str, argument_type: ArgumentType) -> Option<Self> {
+        match argument_type {
+            ArgumentType::Number => text
+                .parse()
+                .ok()
+                .filter(|number: &f64| number.is_finite())
+                .map(ArgumentValue::Number),
+            ArgumentType::Integer => text.parse().ok().map(ArgumentValue::Integer),
+            ArgumentType::String => Some(ArgumentValue::String(text.to_string())),
+            ArgumentType::Bool => text.parse().ok().map(ArgumentValue::Bool),
+        }
+    }
+}
+
+/// The keyword arguments of an element, checked against its [`ElementSchema`] when parsed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeywordArguments {
+    values: BTreeMap<String, ArgumentValue>,
+    /// The arguments as written, positionally or by name, to locate errors found after type checking. Only known
+    /// while parsing.
+    #[serde(skip)]
+    arguments: BTreeMap<String, Spanned<String>>,
+}
+
+impl KeywordArguments {
+    /// Adds the value of a keyword argument, given by `argument` in the template. Returns the previous value if the
+    /// argument was already given.
+    pub(super) fn insert(
+        &mut self,
+        name: impl Into<String>,
+        value: ArgumentValue,
+        argument: &Spanned<String>,
+    ) -> Option<ArgumentValue> {
+        let name = name.into();
+        self.arguments.insert(name.clone(), argument.clone());
+        self.values.insert(name, value)
+    }
+
+    /// The argument as written in the template, to locate errors. Only available while parsing.
+    pub(super) fn argument(&self, name: &str) -> &Spanned<String> {
+        self.arguments
+            .get(name)
+            .expect("Keyword arguments are checked while they are parsed.")
+    }
+
+    pub fn number(&self, name: &str) -> Option<f64> {
+        match self.values.get(name)? {
+            ArgumentValue::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn integer(&self, name: &str) -> Option<usize> {
+        match self.values.get(name)? {
+            ArgumentValue::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.values.get(name)? {
+            ArgumentValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn bool(&self, name: &str) -> Option<bool> {
+        match self.values.get(name)? {
+            ArgumentValue::Bool(bool) => Some(*bool),
+            _ => None,
+        }
+    }
+}
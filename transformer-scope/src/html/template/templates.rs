@@ -0,0 +1,35 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Payload;
+
+use super::{ArgumentError, IndexTemplate, LayerTemplate, NeuronTemplate, Partial};
+
+/// The templates of the pages of a payload, together with the partials they include.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Templates {
+    pub mlp_neuron: NeuronTemplate,
+    pub index: IndexTemplate,
+    pub layer: LayerTemplate,
+    /// Named parts of templates, shared between templates.
+    pub partials: BTreeMap<String, Partial>,
+}
+
+impl Templates {
+    /// Replaces every `$include` in the templates by the nodes of the included partial.
+    pub(crate) fn resolve_includes(self) -> Result<Self, ArgumentError> {
+        Ok(Self {
+            mlp_neuron: self.mlp_neuron.resolve_includes(&self.partials)?,
+            index: self.index.resolve_includes(&self.partials)?,
+            layer: self.layer.resolve_includes(&self.partials)?,
+            partials: self.partials,
+        })
+    }
+
+    pub fn validate_arguments(&self, payload: &Payload) -> Result<(), ArgumentError> {
+        self.mlp_neuron.validate_arguments(payload)?;
+        self.index.validate_arguments(payload)?;
+        self.layer.validate_arguments(payload)
+    }
+}
@@ -0,0 +1,784 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{
+        value::{DataType, Scope},
+        Annotations,
+    },
+    html::PageImages,
+    Payload,
+};
+
+use super::{
+    control_flow::{check_scope, Condition, Loop, LoopVariables, ValueReference},
+    element::{argument_count_error, invalid_argument_error},
+    parser::{self, ElementCall, ParseResult, ParsedTemplate, Spanned, TemplateParseErrorKind},
+    payload_schema::{PayloadSchema, ValueRequirement},
+    ArgumentError, ArgumentErrorType, Element, Partial, TemplateParseError,
+};
+
+/// A part of a template.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Node {
+    Text(String),
+    Element(Element),
+    /// Generates the body of the first branch whose condition holds, or the `$else` body if none does.
+    If {
+        branches: Vec<(Condition, Vec<Node>)>,
+        otherwise: Vec<Node>,
+    },
+    For {
+        repeat: Loop,
+        body: Vec<Node>,
+    },
+    /// A partial, replaced by its nodes when the payload is built.
+    Include {
+        partial: String,
+        arguments: BTreeMap<String, String>,
+    },
+}
+
+/// Checks that a block continuation like `$else` or `$end` has no arguments.
+fn no_arguments(call: &ElementCall) -> ParseResult<()> {
+    if call.arguments.is_empty() {
+        Ok(())
+    } else {
+        Err(argument_count_error(
+            call,
+            "no arguments",
+            call.arguments.len(),
+        ))
+    }
+}
+
+fn unexpected_block_end(call: &ElementCall) -> Spanned<TemplateParseErrorKind> {
+    Spanned {
+        value: TemplateParseErrorKind::UnexpectedBlockEnd(call.name.value.clone()),
+        offset: call.name.offset,
+    }
+}
+
+fn unclosed_block(call: &ElementCall) -> Spanned<TemplateParseErrorKind> {
+    Spanned {
+        value: TemplateParseErrorKind::UnclosedBlock(call.name.value.clone()),
+        offset: call.name.offset,
+    }
+}
+
+/// An element with the text following it, as in [`ParsedTemplate`].
+type Part = (ElementCall, String);
+
+/// Builds the nodes of a template from its elements, nesting the bodies of `$if` and `$for` blocks.
+struct TreeBuilder {
+    parts: std::vec::IntoIter<Part>,
+    /// Variables of the enclosing loops.
+    loop_variables: Vec<String>,
+}
+
+impl TreeBuilder {
+    /// Nodes up to the next `$elif`, `$else` or `$end`, which is returned with the text following it, or up to the
+    /// end of the template.
+    fn nodes(&mut self, text: String) -> ParseResult<(Vec<Node>, Option<Part>)> {
+        let mut nodes = Vec::new();
+        let mut text = text;
+        loop {
+            if !text.is_empty() {
+                nodes.push(Node::Text(text));
+            }
+            let Some((call, call_text)) = self.parts.next() else {
+                return Ok((nodes, None));
+            };
+            text = match call.name.value.as_str() {
+                "elif" | "else" | "end" => return Ok((nodes, Some((call, call_text)))),
+                "if" => {
+                    let (node, text) = self.if_block(&call, call_text)?;
+                    nodes.push(node);
+                    text
+                }
+                "for" => {
+                    let (node, text) = self.for_block(&call, call_text)?;
+                    nodes.push(node);
+                    text
+                }
+                "include" => {
+                    nodes.push(include(&call)?);
+                    call_text
+                }
+                _ => {
+                    nodes.push(Node::Element(self.element(&call)?));
+                    call_text
+                }
+            };
+        }
+    }
+
+    /// Checks that the loop variables indexing a value are defined by enclosing loops.
+    fn check_reference(
+        &self,
+        call: &ElementCall,
+        argument: &Spanned<String>,
+        value: &str,
+    ) -> ParseResult<()> {
+        let reference = ValueReference::parse(value)
+            .map_err(|reason| invalid_argument_error(call, argument, &reason))?;
+        match reference.indices.iter().find(|index| {
+            !self
+                .loop_variables
+                .iter()
+                .any(|variable| variable == *index)
+        }) {
+            Some(index) => Err(Spanned {
+                value: TemplateParseErrorKind::UnboundLoopVariable(index.to_string()),
+                offset: argument.offset,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn element(&self, call: &ElementCall) -> ParseResult<Element> {
+        let element = Element::from_call(call)?;
+        for argument in call
+            .arguments
+            .iter()
+            .filter(|argument| argument.name.is_none())
+        {
+            if !element.accepts_loop_indices() && argument.value.value.contains('[') {
+                return Err(invalid_argument_error(
+                    call,
+                    &argument.value,
+                    "only the elements 'value' and 'heatmap' can index values by loop variables",
+                ));
+            }
+            self.check_reference(call, &argument.value, &argument.value.value)?;
+        }
+        Ok(element)
+    }
+
+    fn condition(&self, call: &ElementCall) -> ParseResult<Condition> {
+        let argument = match call.arguments.as_slice() {
+            [argument] if argument.name.is_none() => &argument.value,
+            arguments => return Err(argument_count_error(call, "a condition", arguments.len())),
+        };
+        let condition = Condition::parse(&argument.value).map_err(|reason| Spanned {
+            value: TemplateParseErrorKind::InvalidCondition {
+                condition: argument.value.clone(),
+                reason,
+            },
+            offset: argument.offset,
+        })?;
+        for value in condition.compared_values() {
+            self.check_reference(call, argument, value)?;
+        }
+        Ok(condition)
+    }
+
+    /// An `$if` block with its `$elif` and `$else` branches. Returns the text following its `$end`.
+    fn if_block(&mut self, call: &ElementCall, text: String) -> ParseResult<(Node, String)> {
+        let mut branches = Vec::new();
+        let mut condition = self.condition(call)?;
+        let mut text = text;
+        loop {
+            let (body, end) = self.nodes(text)?;
+            branches.push((condition, body));
+            let Some((end, end_text)) = end else {
+                return Err(unclosed_block(call));
+            };
+            match end.name.value.as_str() {
+                "elif" => {
+                    condition = self.condition(&end)?;
+                    text = end_text;
+                }
+                "else" => {
+                    no_arguments(&end)?;
+                    let (otherwise, end) = self.nodes(end_text)?;
+                    return match end {
+                        Some((end, end_text)) if end.name.value == "end" => {
+                            no_arguments(&end)?;
+                            Ok((
+                                Node::If {
+                                    branches,
+                                    otherwise,
+                                },
+                                end_text,
+                            ))
+                        }
+                        Some((end, _)) => Err(unexpected_block_end(&end)),
+                        None => Err(unclosed_block(call)),
+                    };
+                }
+                _ => {
+                    no_arguments(&end)?;
+                    return Ok((
+                        Node::If {
+                            branches,
+                            otherwise: Vec::new(),
+                        },
+                        end_text,
+                    ));
+                }
+            }
+        }
+    }
+
+    /// A `$for` block. Returns the text following its `$end`.
+    fn for_block(&mut self, call: &ElementCall, text: String) -> ParseResult<(Node, String)> {
+        let repeat = Loop::from_call(call)?;
+        self.check_reference(call, &call.arguments[1].value, repeat.value())?;
+        self.loop_variables.push(repeat.variable().to_string());
+        let (body, end) = self.nodes(text)?;
+        self.loop_variables.pop();
+        match end {
+            Some((end, end_text)) if end.name.value == "end" => {
+                no_arguments(&end)?;
+                Ok((Node::For { repeat, body }, end_text))
+            }
+            Some((end, _)) => Err(unexpected_block_end(&end)),
+            None => Err(unclosed_block(call)),
+        }
+    }
+}
+
+/// An `$include(partial_name, parameter=argument, ...)` element.
+fn include(call: &ElementCall) -> ParseResult<Node> {
+    let Some((partial, arguments)) = call
+        .arguments
+        .split_first()
+        .filter(|(partial, _)| partial.name.is_none())
+    else {
+        return Err(argument_count_error(
+            call,
+            "a partial name followed by keyword arguments",
+            call.arguments.len(),
+        ));
+    };
+    let mut keyword_arguments = BTreeMap::new();
+    for argument in arguments {
+        let Some(name) = &argument.name else {
+            return Err(invalid_argument_error(
+                call,
+                &argument.value,
+                "the parameters of a partial are given as keyword arguments, e.g. 'value=ownership_heatmap'",
+            ));
+        };
+        if keyword_arguments
+            .insert(name.value.clone(), argument.value.value.clone())
+            .is_some()
+        {
+            return Err(invalid_argument_error(
+                call,
+                name,
+                "the keyword argument is given more than once",
+            ));
+        }
+    }
+    Ok(Node::Include {
+        partial: partial.value.value.clone(),
+        arguments: keyword_arguments,
+    })
+}
+
+/// Parses the nodes of a template within the given loops.
+fn parse_nodes(
+    template_code: &str,
+    loop_variables: Vec<String>,
+) -> Result<Vec<Node>, TemplateParseError> {
+    let ParsedTemplate { prefix, parts } = parser::parse_template(template_code)?;
+    let mut builder = TreeBuilder {
+        parts: parts.into_iter(),
+        loop_variables,
+    };
+    match builder.nodes(prefix) {
+        Ok((nodes, None)) => Ok(nodes),
+        Ok((_, Some((end, _)))) => Err(unexpected_block_end(&end)),
+        Err(error) => Err(error),
+    }
+    .map_err(|error| TemplateParseError::new(error.value, template_code, error.offset))
+}
+
+/// Replaces the included partials by their nodes, recursively. `included` holds the partials being included, to
+/// detect partials including themselves.
+fn resolve_includes(
+    nodes: &[Node],
+    partials: &BTreeMap<String, Partial>,
+    loop_variables: &mut Vec<String>,
+    included: &mut Vec<String>,
+) -> Result<Vec<Node>, ArgumentError> {
+    let mut resolved = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        match node {
+            Node::Text(_) | Node::Element(_) => resolved.push(node.clone()),
+            Node::If {
+                branches,
+                otherwise,
+            } => {
+                let branches = branches
+                    .iter()
+                    .map(|(condition, branch)| {
+                        resolve_includes(branch, partials, loop_variables, included)
+                            .map(|branch| (condition.clone(), branch))
+                    })
+                    .collect::<Result<_, _>>()?;
+                let otherwise = resolve_includes(otherwise, partials, loop_variables, included)?;
+                resolved.push(Node::If {
+                    branches,
+                    otherwise,
+                });
+            }
+            Node::For { repeat, body } => {
+                loop_variables.push(repeat.variable().to_string());
+                let body = resolve_includes(body, partials, loop_variables, included);
+                loop_variables.pop();
+                resolved.push(Node::For {
+                    repeat: repeat.clone(),
+                    body: body?,
+                });
+            }
+            Node::Include { partial, arguments } => {
+                let partial_error = |error_type| ArgumentError {
+                    error_type,
+                    value_name: partial.clone(),
+                };
+                let Some(partial_template) = partials.get(partial) else {
+                    let names: Vec<&str> = partials.keys().map(String::as_str).collect();
+                    return Err(partial_error(ArgumentErrorType::MissingPartial {
+                        name: partial.clone(),
+                        suggestions: parser::suggestions(partial, &names),
+                    }));
+                };
+                if included.contains(partial) {
+                    return Err(partial_error(ArgumentErrorType::Other(format!(
+                        "The partial includes itself through {} -> {partial}.",
+                        included.join(" -> ")
+                    ))));
+                }
+                if let Some(parameter) = partial_template
+                    .parameters()
+                    .find(|parameter| !arguments.contains_key(*parameter))
+                {
+                    return Err(partial_error(ArgumentErrorType::MissingPartialArgument(
+                        parameter.to_string(),
+                    )));
+                }
+                if let Some(argument) = arguments.keys().find(|argument| {
+                    !partial_template
+                        .parameters()
+                        .any(|parameter| parameter == *argument)
+                }) {
+                    return Err(partial_error(ArgumentErrorType::UnknownPartialArgument(
+                        argument.clone(),
+                    )));
+                }
+                let nodes = parse_nodes(
+                    &partial_template.substitute(arguments),
+                    loop_variables.clone(),
+                )
+                .map_err(|error| partial_error(ArgumentErrorType::PartialParse(Box::new(error))))?;
+                included.push(partial.clone());
+                let nodes = resolve_includes(&nodes, partials, loop_variables, included);
+                included.pop();
+                resolved.extend(nodes?);
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+/// The page a template is generated for. The layer and neuron indices are only used by values of the same or a
+/// finer scope than the page.
+#[derive(Clone, Copy)]
+pub(super) struct Page<'a> {
+    pub payload: &'a Payload,
+    pub annotations: &'a Annotations,
+    /// Whether the page is generated as a file of a static site rather than served.
+    pub file: bool,
+    pub scope: Scope,
+    pub layer_index: usize,
+    pub neuron_index: usize,
+    /// Collects the PNG images of the page.
+    pub images: &'a PageImages,
+}
+
+impl Page<'_> {
+    /// The URL of an image of the page relative to it. Images are stored in the `images` directory next to the
+    /// page, named by their index, e.g. `L2/N5/images/0.png` for neuron 5 of layer 2.
+    pub fn image_url(&self, index: usize) -> String {
+        let directory = match self.scope {
+            Scope::Global => String::new(),
+            // Served layer pages are at `/L{layer_index}` rather than in the directory of their layer.
+            Scope::Layer if self.file => String::new(),
+            Scope::Layer => format!("L{}/", self.payload.original_layer_index(self.layer_index)),
+            Scope::Neuron => format!(
+                "N{}/",
+                self.payload.original_neuron_index(self.neuron_index)
+            ),
+        };
+        format!("{directory}images/{index}.png")
+    }
+}
+
+fn generate_nodes(nodes: &[Node], page: &Page, loops: &LoopVariables, body: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => body.push_str(text),
+            Node::Element(element) => {
+                let element_markup = element.generate(page, loops);
+                body.push_str(&element_markup.into_string());
+            }
+            Node::If {
+                branches,
+                otherwise,
+            } => {
+                let branch = branches
+                    .iter()
+                    .find(|(condition, _)| {
+                        condition.evaluate(page.payload, loops, page.layer_index, page.neuron_index)
+                    })
+                    .map_or(otherwise, |(_, branch)| branch);
+                generate_nodes(branch, page, loops, body);
+            }
+            Node::For {
+                repeat,
+                body: loop_body,
+            } => {
+                let indices =
+                    repeat.indices(page.payload, loops, page.layer_index, page.neuron_index);
+                for index in indices {
+                    let loops = loops.with(repeat.variable(), index);
+                    generate_nodes(loop_body, page, &loops, body);
+                }
+            }
+            Node::Include { .. } => {
+                panic!("Partials are included when the payload is built.")
+            }
+        }
+    }
+}
+
+/// Validates the elements of every branch that can be generated for some page, e.g. not the body of
+/// `$if(exists(value_name))` if the payload has no such value. Elements may only show values available on pages
+/// of the given scope, e.g. only `Global` values on the index page.
+fn validate_nodes(
+    nodes: &[Node],
+    payload: &Payload,
+    scope: Scope,
+    loops: &LoopVariables,
+) -> Result<(), ArgumentError> {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Element(element) => {
+                element.validate_arguments(payload, loops)?;
+                element.validate_scope(payload, scope)?;
+            }
+            Node::If {
+                branches,
+                otherwise,
+            } => {
+                let mut always_taken = false;
+                for (condition, branch) in branches {
+                    match condition.validate(payload, scope, loops)? {
+                        Some(false) => continue,
+                        Some(true) => {
+                            validate_nodes(branch, payload, scope, loops)?;
+                            always_taken = true;
+                            break;
+                        }
+                        None => validate_nodes(branch, payload, scope, loops)?,
+                    }
+                }
+                if !always_taken {
+                    validate_nodes(otherwise, payload, scope, loops)?;
+                }
+            }
+            Node::For { repeat, body } => {
+                let iterations = repeat.validate(payload, loops)?;
+                check_scope(payload, repeat.value(), scope)?;
+                validate_nodes(
+                    body,
+                    payload,
+                    scope,
+                    &loops.with(repeat.variable(), iterations),
+                )?;
+            }
+            Node::Include { .. } => {
+                panic!("Partials are included when the payload is built.")
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds the values shown by the nodes on pages of the given scope to the schema. Values within `$if` blocks checking
+/// which values exist are conditional.
+fn schema_nodes(nodes: &[Node], scope: Scope, conditional: bool, schema: &mut PayloadSchema) {
+    let insert = |schema: &mut PayloadSchema, mut requirement: ValueRequirement| {
+        requirement.conditional = conditional;
+        schema.insert(requirement);
+    };
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Element(element) => {
+                for requirement in element.requirements(scope) {
+                    insert(schema, requirement);
+                }
+            }
+            Node::If {
+                branches,
+                otherwise,
+            } => {
+                let conditional = conditional
+                    || branches
+                        .iter()
+                        .any(|(condition, _)| condition.checks_existence());
+                for (condition, _) in branches {
+                    for value in condition.compared_values() {
+                        let reference =
+                            ValueReference::parse(value).expect("Checked when parsing.");
+                        schema.insert(ValueRequirement {
+                            conditional,
+                            ..ValueRequirement::new(
+                                reference.name,
+                                &[DataType::F32, DataType::U32],
+                                scope,
+                                Some(reference.indices.len()),
+                            )
+                        });
+                    }
+                }
+                for (_, branch) in branches {
+                    schema_nodes(branch, scope, conditional, schema);
+                }
+                schema_nodes(otherwise, scope, conditional, schema);
+            }
+            Node::For { repeat, body } => {
+                let reference =
+                    ValueReference::parse(repeat.value()).expect("Checked when parsing.");
+                // Only sorted loops compare the numbers of the value.
+                let requirement = match repeat.sort() {
+                    Some(_) => ValueRequirement::new(
+                        reference.name,
+                        &[DataType::F32, DataType::U32],
+                        scope,
+                        Some(reference.indices.len() + 1),
+                    ),
+                    None => ValueRequirement::new(reference.name, &DataType::ALL, scope, None),
+                };
+                insert(schema, requirement);
+                schema_nodes(body, scope, conditional, schema);
+            }
+            Node::Include { .. } => {
+                panic!("Partials are included before deriving the schema.")
+            }
+        }
+    }
+}
+
+/// The parsed parts of a template, shared by the templates of the different pages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct Tree(Vec<Node>);
+
+impl Tree {
+    pub fn parse(template_code: &str) -> Result<Self, TemplateParseError> {
+        parse_nodes(template_code, Vec::new()).map(Self)
+    }
+
+    /// Replaces every `$include` by the nodes of the included partial.
+    pub fn resolve_includes(
+        &self,
+        partials: &BTreeMap<String, Partial>,
+    ) -> Result<Self, ArgumentError> {
+        resolve_includes(&self.0, partials, &mut Vec::new(), &mut Vec::new()).map(Self)
+    }
+
+    /// Generates the HTML of the template for the page.
+    pub fn generate(&self, page: &Page) -> String {
+        let mut body = String::new();
+        generate_nodes(&self.0, page, &LoopVariables::default(), &mut body);
+        body
+    }
+
+    /// The values shown on pages of the given scope. Partials must be included first.
+    pub fn schema(&self, scope: Scope) -> PayloadSchema {
+        let mut schema = PayloadSchema::default();
+        schema_nodes(&self.0, scope, false, &mut schema);
+        schema
+    }
+
+    pub fn validate(&self, payload: &Payload, scope: Scope) -> Result<(), ArgumentError> {
+        validate_nodes(&self.0, payload, scope, &LoopVariables::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ndarray::{arr2, Array3};
+
+    use super::{Page, Tree};
+    use crate::{
+        data::{value::Scope, Value},
+        html::{
+            template::{ArgumentError, ArgumentErrorType, NeuronTemplate, Partial},
+            PageImages,
+        },
+        Annotations, Payload, PayloadBuilder,
+    };
+
+    fn resolve(template: &str, partials: &[(&str, &str)]) -> Result<Tree, ArgumentError> {
+        let partials: BTreeMap<String, Partial> = partials
+            .iter()
+            .map(|&(name, source)| (name.to_string(), Partial::parse(source).unwrap()))
+            .collect();
+        Tree::parse(template).unwrap().resolve_includes(&partials)
+    }
+
+    fn cycle(template: &str, partials: &[(&str, &str)]) -> String {
+        match resolve(template, partials).unwrap_err().error_type {
+            ArgumentErrorType::Other(message) => message,
+            error_type => panic!("Expected a cycle, found {error_type:?}"),
+        }
+    }
+
+    #[test]
+    fn partials_are_included_with_their_arguments() {
+        let tree = resolve(
+            "$for(i, scores)$include(board, value=boards[i], caption=\"Board\")$end",
+            &[("board", "$heatmap({{value}}, title=\"{{caption}}\")")],
+        )
+        .unwrap();
+        let names: Vec<_> = tree
+            .schema(Scope::Neuron)
+            .requirements()
+            .iter()
+            .map(|requirement| requirement.name.clone())
+            .collect();
+        assert_eq!(names, ["boards", "scores"]);
+    }
+
+    #[test]
+    fn partials_including_themselves_are_rejected() {
+        assert_eq!(
+            cycle("$include(a)", &[("a", "x $include(a)")]),
+            "The partial includes itself through a -> a."
+        );
+        assert_eq!(
+            cycle(
+                "$include(a)",
+                &[
+                    ("a", "$if(layer > 0)$include(b)$end"),
+                    ("b", "$for(i, scores)$include(a)$end"),
+                ]
+            ),
+            "The partial includes itself through a -> b -> a."
+        );
+        // Including a partial several times is no cycle.
+        assert!(resolve(
+            "$include(a) $include(b)",
+            &[("a", "$include(b) $include(b)"), ("b", "b")]
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn partial_arguments_must_match_the_parameters() {
+        let partials = [("captioned", "{{caption}}")];
+        assert!(matches!(
+            resolve("$include(captioned)", &partials).unwrap_err().error_type,
+            ArgumentErrorType::MissingPartialArgument(parameter) if parameter == "caption"
+        ));
+        assert!(matches!(
+            resolve("$include(captioned, caption=a, title=b)", &partials)
+                .unwrap_err()
+                .error_type,
+            ArgumentErrorType::UnknownPartialArgument(argument) if argument == "title"
+        ));
+        assert!(matches!(
+            resolve("$include(captiond, caption=a)", &partials).unwrap_err().error_type,
+            ArgumentErrorType::MissingPartial { name, suggestions }
+                if name == "captiond" && suggestions == ["captioned"]
+        ));
+    }
+
+    /// A payload of 2 layers with 3 neurons each, with a `Global` board and a `Layer` board whose second layer is
+    /// also stored as the `Global` value `second_layer_board`.
+    fn scoped_payload() -> Payload {
+        let mut builder = PayloadBuilder::new(2, 3);
+        builder
+            .mlp_neuron_template(
+                NeuronTemplate::parse(
+                    "$heatmap(board) $heatmap(layer_board) $heatmap(second_layer_board)",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        builder.add_value(
+            "board",
+            Value::new(arr2(&[[1.0f32, -2.], [3., 0.5]]), Scope::Global),
+        );
+        builder.add_value(
+            "layer_board",
+            Value::new(
+                Array3::from_shape_fn((2, 2, 2), |(layer, row, column)| {
+                    (layer * 4 + row * 2 + column) as f32
+                }),
+                Scope::Layer,
+            ),
+        );
+        builder.add_value(
+            "second_layer_board",
+            Value::new(arr2(&[[4.0f32, 5.], [6., 7.]]), Scope::Global),
+        );
+        builder.build().unwrap()
+    }
+
+    fn render(
+        template: &str,
+        payload: &Payload,
+        layer_index: usize,
+        neuron_index: usize,
+    ) -> String {
+        Tree::parse(template).unwrap().generate(&Page {
+            payload,
+            annotations: &Annotations::new(),
+            file: false,
+            scope: Scope::Neuron,
+            layer_index,
+            neuron_index,
+            images: &PageImages::default(),
+        })
+    }
+
+    #[test]
+    fn global_values_are_the_same_on_every_page() {
+        let payload = scoped_payload();
+        let template = "$heatmap(board, cell_text=\"number\")";
+        let first_page = render(template, &payload, 0, 0);
+        assert!(first_page.contains("<table"));
+        assert_eq!(first_page, render(template, &payload, 1, 2));
+    }
+
+    #[test]
+    fn layer_values_are_indexed_by_the_layer_of_the_page() {
+        let payload = scoped_payload();
+        // Equal bounds, so the slice of the layer value is coloured like the global value holding the same numbers.
+        let options = "vmin=0, vmax=8, cell_text=\"number\"";
+        let layer_board = format!("$heatmap(layer_board, {options})");
+        let second_layer_board = format!("$heatmap(second_layer_board, {options})");
+        assert_eq!(
+            render(&layer_board, &payload, 1, 0),
+            render(&second_layer_board, &payload, 0, 0)
+        );
+        assert_eq!(
+            render(&layer_board, &payload, 1, 0),
+            render(&layer_board, &payload, 1, 2)
+        );
+        assert_ne!(
+            render(&layer_board, &payload, 0, 0),
+            render(&layer_board, &payload, 1, 0)
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use maud::{html, Markup};
+
+use crate::data::TokenSnippet;
+
+use super::ColorScale;
+
+pub fn token_snippets(snippets: &[TokenSnippet], color_scale: &ColorScale) -> Markup {
+    html! {
+        div class="token_snippets" {
+            @for snippet in snippets {
+                div class="token_snippet" {
+                    span class="token_snippet_max" {
+                        (format!("{:.3}", snippet.focus_activation()))
+                    }
+                    @for (index, (token, &activation)) in snippet.tokens().iter().zip(snippet.activations()).enumerate() {
+                        (token_cell(token, activation, index == snippet.focus(), color_scale))
+                    }
+                }
+            }
+        }
+        (color_scale.legend())
+    }
+}
+
+fn token_cell(
+    token: &str,
+    activation: f32,
+    is_focus: bool,
+    color_scale: &ColorScale,
+) -> impl maud::Render {
+    html! {
+        span.token.token_focus[is_focus] title=(activation) style={"background-color: "(color_scale.css_color(activation))} {
+            (token)
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use maud::{html, Markup};
+
+use super::colormap::format_number;
+
+const ROW_HEIGHT: f32 = 16.;
+const BAR_HEIGHT: f32 = 12.;
+/// The width of the bars on either side of zero.
+const HALF_BAR_WIDTH: f32 = 150.;
+const NUMBER_WIDTH: f32 = 64.;
+/// The approximate width of a character of the labels, to leave room for them.
+const CHARACTER_WIDTH: f32 = 7.;
+/// The space between the largest and the smallest entries.
+const GAP_HEIGHT: f32 = 8.;
+
+/// The indices of the `k` largest and the `k` smallest finite numbers, each in descending order of their numbers.
+/// If there are fewer than `2 * k` numbers, the smallest are those which are not among the largest, so none is
+/// shown twice.
+fn largest_and_smallest(numbers: &[f32], k: usize) -> (Vec<usize>, Vec<usize>) {
+    let mut indices: Vec<usize> = (0..numbers.len())
+        .filter(|&index| numbers[index].is_finite())
+        .collect();
+    indices.sort_by(|&a, &b| numbers[b].total_cmp(&numbers[a]));
+    let smallest_start = k.max(indices.len().saturating_sub(k)).min(indices.len());
+    let smallest = indices.split_off(smallest_start);
+    indices.truncate(k);
+    (indices, smallest)
+}
+
+/// Renders the `k` largest and `k` smallest numbers with their labels as an inline SVG bar chart, with bars to the
+/// right of zero for positive and to the left for negative numbers.
+pub fn top_k(numbers: &[f32], labels: &[String], k: usize) -> Markup {
+    assert_eq!(numbers.len(), labels.len());
+    let (largest, smallest) = largest_and_smallest(numbers, k);
+    let max_abs = largest
+        .iter()
+        .chain(&smallest)
+        .map(|&index| numbers[index].abs())
+        .fold(0., f32::max);
+    let label_width = largest
+        .iter()
+        .chain(&smallest)
+        .map(|&index| labels[index].chars().count())
+        .max()
+        .unwrap_or(0) as f32
+        * CHARACTER_WIDTH
+        + 4.;
+    let zero_x = label_width + HALF_BAR_WIDTH;
+    let width = zero_x + HALF_BAR_WIDTH + NUMBER_WIDTH;
+    let gap = if smallest.is_empty() { 0. } else { GAP_HEIGHT };
+    let label_x = label_width - 4.;
+    let number_x = zero_x + HALF_BAR_WIDTH + 4.;
+    let height = (largest.len() + smallest.len()) as f32 * ROW_HEIGHT + gap;
+
+    let rows = largest
+        .iter()
+        .enumerate()
+        .map(|(row, &index)| (row as f32 * ROW_HEIGHT, index))
+        .chain(
+            smallest
+                .iter()
+                .enumerate()
+                .map(|(row, &index)| ((largest.len() + row) as f32 * ROW_HEIGHT + gap, index)),
+        );
+
+    html! {
+        svg class="top_k" width=(width) height=(height)
+            viewBox={"0 0 "(width)" "(height)} xmlns="http://www.w3.org/2000/svg" {
+            @for (y, index) in rows {
+                @let number = numbers[index];
+                @let bar_width = if max_abs > 0. { HALF_BAR_WIDTH * number.abs() / max_abs } else { 0. };
+                @let bar_x = if number < 0. { zero_x - bar_width } else { zero_x };
+                @let bar_y = y + (ROW_HEIGHT - BAR_HEIGHT) / 2.;
+                @let text_y = y + ROW_HEIGHT / 2.;
+                @let bar_class = if number < 0. { "top_k_bar top_k_negative" } else { "top_k_bar top_k_positive" };
+                text class="top_k_label" x=(label_x) y=(text_y) text-anchor="end" dominant-baseline="central" {
+                    (labels[index])
+                }
+                rect class=(bar_class) x=(bar_x) y=(bar_y) width=(bar_width) height=(BAR_HEIGHT) {
+                    title {(labels[index])": "(number)}
+                }
+                text class="top_k_number" x=(number_x) y=(text_y) dominant-baseline="central" {
+                    (format_number(number))
+                }
+            }
+            line class="top_k_axis" x1=(zero_x) y1="0" x2=(zero_x) y2=(height) {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::largest_and_smallest;
+
+    #[test]
+    fn more_than_twice_k_numbers_leave_out_the_middle() {
+        let numbers = [0.5, -2., 3., 0., 1., -1.];
+        assert_eq!(largest_and_smallest(&numbers, 2), (vec![2, 4], vec![5, 1]));
+    }
+
+    #[test]
+    fn at_most_twice_k_numbers_are_split_after_the_largest_k() {
+        let numbers: Vec<f32> = (0..15).map(|number| number as f32).collect();
+        let (largest, smallest) = largest_and_smallest(&numbers, 10);
+        assert_eq!(largest, (5..15).rev().collect::<Vec<_>>());
+        assert_eq!(smallest, (0..5).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn at_most_k_numbers_are_all_largest() {
+        let numbers = [1., -1., 2.];
+        assert_eq!(largest_and_smallest(&numbers, 3), (vec![2, 0, 1], vec![]));
+        assert_eq!(largest_and_smallest(&numbers, 5), (vec![2, 0, 1], vec![]));
+    }
+
+    #[test]
+    fn non_finite_numbers_are_left_out() {
+        let numbers = [f32::NAN, 1., f32::INFINITY, -1.];
+        assert_eq!(largest_and_smallest(&numbers, 1), (vec![1], vec![3]));
+    }
+}
@@ -1,6 +1,11 @@
 mod data;
 pub mod html;
-pub use data::{Payload, PayloadBuilder};
+pub use data::{
+    Annotation, Annotations, AnnotationsError, Clustering, ClusteringMethod, Payload,
+    PayloadBuilder, PayloadFileError, Quantization, QuantizationError, QuantizedArray,
+    RequestError, SearchIndex, SearchQuery, SearchQueryError, SimilarityMetric, SimilarityMode,
+    SubsetError, TopKAccumulator, TopKAccumulatorError, Vocabulary, PAYLOAD_FORMAT_VERSION,
+};
 
 #[cfg(feature = "python")]
 mod python;
@@ -1,10 +1,23 @@
-use ndarray::ArrayD;
-use numpy::borrow::PyReadonlyArrayDyn;
-use pyo3::{create_exception, exceptions::PyException, prelude::*};
+use std::collections::HashMap;
+
+use ndarray::{Array2, ArrayD};
+use numpy::borrow::{PyReadonlyArray2, PyReadonlyArray4, PyReadonlyArrayDyn};
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyValueError},
+    prelude::*,
+};
 
 use crate::{
-    data::{value::Scope, Payload, PayloadBuilder, Value},
-    html::template::{ArgumentError, NeuronTemplate},
+    data::{
+        value::Scope, Annotations, ClusteringMethod, Payload, PayloadBuilder, Quantization,
+        RequestError, SimilarityMetric, SimilarityMode, SubsetError, TokenSnippet, TopKAccumulator,
+        TopKAccumulatorError, Value, Vocabulary,
+    },
+    html::template::{
+        self, ArgumentError, IndexTemplate, LayerTemplate, NeuronTemplate, Partial, SchemaError,
+        ValueRequirement,
+    },
 };
 
 #[pyfunction]
@@ -37,6 +50,40 @@ impl From<ArgumentError> for PyErr {
     }
 }
 
+create_exception!(transformer_scope, TemplateParseError, PyException);
+
+impl From<template::TemplateParseError> for PyErr {
+    fn from(value: template::TemplateParseError) -> Self {
+        PyErr::new::<TemplateParseError, _>(format!("{value}"))
+    }
+}
+
+create_exception!(transformer_scope, PayloadSchemaError, PyException);
+
+impl From<SchemaError> for PyErr {
+    fn from(value: SchemaError) -> Self {
+        PyErr::new::<PayloadSchemaError, _>(format!("{value}"))
+    }
+}
+
+impl From<SubsetError> for PyErr {
+    fn from(value: SubsetError) -> Self {
+        PyValueError::new_err(format!("{value}"))
+    }
+}
+
+impl From<RequestError> for PyErr {
+    fn from(value: RequestError) -> Self {
+        PyValueError::new_err(format!("{value}"))
+    }
+}
+
+impl From<TopKAccumulatorError> for PyErr {
+    fn from(value: TopKAccumulatorError) -> Self {
+        PyValueError::new_err(format!("{value}"))
+    }
+}
+
 #[pyclass(name = "Scope")]
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum PyScope {
@@ -55,6 +102,95 @@ impl From<PyScope> for Scope {
     }
 }
 
+impl From<Scope> for PyScope {
+    fn from(value: Scope) -> Self {
+        match value {
+            Scope::Global => PyScope::Global,
+            Scope::Layer => PyScope::Layer,
+            Scope::Neuron => PyScope::Neuron,
+        }
+    }
+}
+
+#[pyclass(name = "Quantization")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PyQuantization {
+    U8,
+    U16,
+}
+
+impl From<PyQuantization> for Quantization {
+    fn from(value: PyQuantization) -> Self {
+        match value {
+            PyQuantization::U8 => Quantization::U8,
+            PyQuantization::U16 => Quantization::U16,
+        }
+    }
+}
+
+#[pyclass(name = "SimilarityMetric")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PySimilarityMetric {
+    Cosine,
+    Correlation,
+}
+
+impl From<PySimilarityMetric> for SimilarityMetric {
+    fn from(value: PySimilarityMetric) -> Self {
+        match value {
+            PySimilarityMetric::Cosine => SimilarityMetric::Cosine,
+            PySimilarityMetric::Correlation => SimilarityMetric::Correlation,
+        }
+    }
+}
+
+#[pyclass(name = "ClusteringMethod")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PyClusteringMethod {
+    KMeans,
+    Agglomerative,
+}
+
+#[pyclass(name = "ValueRequirement", frozen)]
+struct PyValueRequirement {
+    #[pyo3(get)]
+    name: String,
+    /// Names of the allowed data types.
+    #[pyo3(get)]
+    data_types: Vec<String>,
+    #[pyo3(get)]
+    scopes: Vec<PyScope>,
+    #[pyo3(get)]
+    axes: Option<usize>,
+    #[pyo3(get)]
+    conditional: bool,
+    description: String,
+}
+
+impl From<&ValueRequirement> for PyValueRequirement {
+    fn from(value: &ValueRequirement) -> Self {
+        PyValueRequirement {
+            name: value.name.clone(),
+            data_types: value.data_types.iter().map(ToString::to_string).collect(),
+            scopes: value.scopes.iter().map(|&scope| scope.into()).collect(),
+            axes: value.axes,
+            conditional: value.conditional,
+            description: value.to_string(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyValueRequirement {
+    fn __repr__(&self) -> String {
+        self.description.clone()
+    }
+}
+
+/// Token snippets passed from Python, with a list of snippets for every layer and neuron. Every snippet consists of
+/// its tokens, their activations and the index of the token it was selected for.
+type PyTokenSnippets = Vec<Vec<Vec<(Vec<String>, Vec<f32>, usize)>>>;
+
 #[pyclass(name = "PayloadBuilder")]
 struct PyPayloadBuilder {
     payload_builder: Option<PayloadBuilder>,
@@ -66,6 +202,13 @@ impl PyPayloadBuilder {
             .as_mut()
             .expect("Payload already built!")
     }
+
+    /// Adds a value after checking it against the schema, so a mismatch raises an exception instead of panicking.
+    fn add_value(&mut self, key: &str, value: Value) -> Result<(), SchemaError> {
+        self.get().check_value(key, &value)?;
+        self.get().add_value(key, value);
+        Ok(())
+    }
 }
 
 #[pymethods]
@@ -76,9 +219,35 @@ impl PyPayloadBuilder {
         PyPayloadBuilder { payload_builder }
     }
 
-    pub fn mlp_neuron_template(&mut self, neuron_template: &str) {
-        let neuron_template = NeuronTemplate::parse(neuron_template);
-        self.get().mlp_neuron_template(neuron_template);
+    pub fn mlp_neuron_template(&mut self, neuron_template: &str) -> PyResult<()> {
+        let neuron_template = NeuronTemplate::parse(neuron_template)?;
+        Ok(self.get().mlp_neuron_template(neuron_template)?)
+    }
+
+    pub fn index_template(&mut self, index_template: &str) -> PyResult<()> {
+        let index_template = IndexTemplate::parse(index_template)?;
+        Ok(self.get().index_template(index_template)?)
+    }
+
+    pub fn layer_template(&mut self, layer_template: &str) -> PyResult<()> {
+        let layer_template = LayerTemplate::parse(layer_template)?;
+        Ok(self.get().layer_template(layer_template)?)
+    }
+
+    pub fn add_partial(&mut self, name: String, partial: &str) -> PyResult<()> {
+        let partial = Partial::parse(partial)?;
+        Ok(self.get().add_partial(name, partial)?)
+    }
+
+    /// The values shown by the templates set so far.
+    pub fn schema(&mut self) -> Result<Vec<PyValueRequirement>, ArgumentError> {
+        let schema = self.get().schema()?;
+        Ok(schema.requirements().into_iter().map(Into::into).collect())
+    }
+
+    #[pyo3(signature = (tokens, token_bytes=None))]
+    pub fn vocabulary(&mut self, tokens: Vec<String>, token_bytes: Option<Vec<Vec<u8>>>) {
+        self.get().vocabulary(Vocabulary::new(tokens, token_bytes));
     }
 
     pub fn add_str_value(
@@ -87,28 +256,138 @@ impl PyPayloadBuilder {
         value: PyReadonlyArrayDyn<PyObject>,
         scope: PyScope,
         py: Python<'_>,
-    ) {
+    ) -> Result<(), SchemaError> {
         let value_array: ArrayD<String> = value.as_array().map(|obj| obj.extract(py).unwrap());
         let value = Value::new(value_array, scope.into());
-        self.get().add_value(key, value);
+        self.add_value(key, value)
     }
 
-    pub fn add_u32_value(&mut self, key: &str, value: PyReadonlyArrayDyn<u32>, scope: PyScope) {
+    pub fn add_u32_value(
+        &mut self,
+        key: &str,
+        value: PyReadonlyArrayDyn<u32>,
+        scope: PyScope,
+    ) -> Result<(), SchemaError> {
         let value_array = value.as_array().to_owned();
         let value = Value::new(value_array, scope.into());
-        self.get().add_value(key, value);
+        self.add_value(key, value)
     }
 
-    pub fn add_f32_value(&mut self, key: &str, value: PyReadonlyArrayDyn<f32>, scope: PyScope) {
+    #[pyo3(signature = (key, value, scope, quantization=None))]
+    pub fn add_f32_value(
+        &mut self,
+        key: &str,
+        value: PyReadonlyArrayDyn<f32>,
+        scope: PyScope,
+        quantization: Option<PyQuantization>,
+    ) -> Result<(), SchemaError> {
         let value_array = value.as_array().to_owned();
-        let value = Value::new(value_array, scope.into());
-        self.get().add_value(key, value);
+        let value = match quantization {
+            Some(quantization) => Value::quantized(value_array, scope.into(), quantization.into()),
+            None => Value::new(value_array, scope.into()),
+        };
+        self.add_value(key, value)
+    }
+
+    pub fn add_token_snippets_value(
+        &mut self,
+        key: &str,
+        snippets: PyTokenSnippets,
+    ) -> PyResult<()> {
+        let num_layers = self.get().num_layers();
+        let num_mlp_neurons = self.get().num_mlp_neurons();
+        if snippets.len() != num_layers
+            || snippets.iter().any(|layer| layer.len() != num_mlp_neurons)
+        {
+            return Err(PyValueError::new_err(format!(
+                "Token snippets must be given for every neuron, as {num_layers} lists of {num_mlp_neurons} neurons."
+            )));
+        }
+        let snippets = snippets
+            .into_iter()
+            .flatten()
+            .map(|neuron| {
+                neuron
+                    .into_iter()
+                    .map(|(tokens, activations, focus)| {
+                        if tokens.len() != activations.len() {
+                            return Err(PyValueError::new_err(format!(
+                                "A token snippet has {} tokens and {} activations, but needs exactly one activation per token.",
+                                tokens.len(),
+                                activations.len()
+                            )));
+                        }
+                        if focus >= tokens.len() {
+                            return Err(PyValueError::new_err(format!(
+                                "A token snippet has focus {focus}, but only {} tokens.",
+                                tokens.len()
+                            )));
+                        }
+                        Ok(TokenSnippet::new(tokens, activations, focus))
+                    })
+                    .collect()
+            })
+            .collect::<PyResult<Vec<Vec<TokenSnippet>>>>()?;
+        let snippets = Array2::from_shape_vec((num_layers, num_mlp_neurons), snippets).unwrap();
+        Ok(self.add_value(key, Value::new(snippets, Scope::Neuron))?)
     }
 
     pub fn set_rank_values(&mut self, key: &str) {
         self.get().set_rank_values(key);
     }
 
+    #[pyo3(signature = (neighbours_key, scores_key, source_key, k, metric=PySimilarityMetric::Cosine, chunk_size=None))]
+    pub fn add_nearest_neighbours(
+        &mut self,
+        neighbours_key: &str,
+        scores_key: &str,
+        source_key: &str,
+        k: usize,
+        metric: PySimilarityMetric,
+        chunk_size: Option<usize>,
+    ) -> Result<(), RequestError> {
+        let mode = match chunk_size {
+            Some(chunk_size) => SimilarityMode::Chunked { chunk_size },
+            None => SimilarityMode::BruteForce,
+        };
+        self.get().add_nearest_neighbours(
+            neighbours_key,
+            scores_key,
+            source_key,
+            k,
+            metric.into(),
+            mode,
+        )
+    }
+
+    pub fn add_connections(
+        &mut self,
+        key: &str,
+        w_in_key: &str,
+        w_out_key: &str,
+        k: usize,
+    ) -> Result<(), RequestError> {
+        self.get().add_connections(key, w_in_key, w_out_key, k)
+    }
+
+    #[pyo3(signature = (key, source_key, num_clusters, method=PyClusteringMethod::KMeans, metric=PySimilarityMetric::Cosine, max_iterations=100))]
+    pub fn cluster_neurons(
+        &mut self,
+        key: &str,
+        source_key: &str,
+        num_clusters: usize,
+        method: PyClusteringMethod,
+        metric: PySimilarityMetric,
+        max_iterations: usize,
+    ) -> Result<(), RequestError> {
+        let method = match method {
+            PyClusteringMethod::KMeans => ClusteringMethod::KMeans { max_iterations },
+            PyClusteringMethod::Agglomerative => ClusteringMethod::Agglomerative,
+        };
+        self.get()
+            .cluster_neurons(key, source_key, num_clusters, method, metric.into())
+    }
+
     pub fn build(&mut self) -> Result<PyPayload, ArgumentError> {
         let payload = self
             .payload_builder
@@ -126,12 +405,101 @@ struct PyPayload {
 
 #[pymethods]
 impl PyPayload {
-    pub fn to_file(&self, path: &str) {
-        self.payload.to_file(path);
+    pub fn to_file(&self, path: &str) -> PyResult<()> {
+        self.payload
+            .to_file(path)
+            .map_err(|error| PyValueError::new_err(format!("{error}")))
+    }
+
+    #[pyo3(signature = (dir_path, annotations_path=None))]
+    pub fn generate_site_files(
+        &self,
+        dir_path: &str,
+        annotations_path: Option<&str>,
+    ) -> PyResult<()> {
+        let annotations = match annotations_path {
+            Some(annotations_path) => Annotations::from_file(annotations_path)
+                .map_err(|error| PyValueError::new_err(format!("{error}")))?,
+            None => Annotations::new(),
+        };
+        crate::html::generate_site_in_dir(dir_path, &self.payload, &annotations);
+        Ok(())
+    }
+
+    /// Maps the name of every quantized value to its maximum absolute error and RMS error.
+    pub fn quantization_errors(&self) -> HashMap<String, (f32, f32)> {
+        self.payload
+            .quantization_errors()
+            .into_iter()
+            .map(|(key, error)| (key.to_string(), (error.max_abs_error, error.rms_error)))
+            .collect()
+    }
+
+    pub fn subset(
+        &self,
+        layers: Option<Vec<usize>>,
+        neurons: Option<Vec<usize>>,
+    ) -> Result<PyPayload, SubsetError> {
+        let payload = self.payload.subset(layers.as_deref(), neurons.as_deref())?;
+        Ok(PyPayload { payload })
+    }
+}
+
+#[pyclass(name = "TopKAccumulator")]
+struct PyTopKAccumulator {
+    accumulator: TopKAccumulator,
+}
+
+#[pymethods]
+impl PyTopKAccumulator {
+    #[new]
+    #[pyo3(signature = (num_layers, num_neurons, k, num_quantiles=0, examples_per_quantile=0, context_before=10, context_after=5))]
+    pub fn new(
+        num_layers: usize,
+        num_neurons: usize,
+        k: usize,
+        num_quantiles: usize,
+        examples_per_quantile: usize,
+        context_before: usize,
+        context_after: usize,
+    ) -> Self {
+        let accumulator = TopKAccumulator::new(
+            num_layers,
+            num_neurons,
+            k,
+            num_quantiles,
+            examples_per_quantile,
+            context_before,
+            context_after,
+        );
+        PyTopKAccumulator { accumulator }
+    }
+
+    pub fn add_batch(
+        &mut self,
+        activations: PyReadonlyArray4<f32>,
+        tokens: PyReadonlyArray2<u32>,
+        py: Python<'_>,
+    ) -> Result<(), TopKAccumulatorError> {
+        let activations = activations.as_array();
+        let tokens = tokens.as_array();
+        py.allow_threads(|| self.accumulator.add_batch(activations, tokens))
     }
 
-    pub fn generate_site_files(&self, dir_path: &str) {
-        crate::html::generate_site_in_dir(dir_path, &self.payload);
+    #[pyo3(signature = (payload_builder, top_k_key, vocabulary=None, quantiles_key=None))]
+    pub fn add_to_payload_builder(
+        &self,
+        payload_builder: &mut PyPayloadBuilder,
+        top_k_key: &str,
+        vocabulary: Option<Vec<String>>,
+        quantiles_key: Option<String>,
+    ) -> Result<(), TopKAccumulatorError> {
+        self.accumulator.add_to_payload_builder(
+            payload_builder.get(),
+            top_k_key,
+            vocabulary.as_deref(),
+            quantiles_key,
+        )
     }
 }
 
@@ -142,7 +510,14 @@ fn transformer_scope(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<PyPayloadBuilder>()?;
     m.add_class::<PyPayload>()?;
     m.add_class::<PyScope>()?;
+    m.add_class::<PyQuantization>()?;
+    m.add_class::<PySimilarityMetric>()?;
+    m.add_class::<PyClusteringMethod>()?;
+    m.add_class::<PyTopKAccumulator>()?;
     m.add("PayloadBuildError", py.get_type::<PayloadBuildError>())?;
+    m.add_class::<PyValueRequirement>()?;
+    m.add("TemplateParseError", py.get_type::<TemplateParseError>())?;
+    m.add("PayloadSchemaError", py.get_type::<PayloadSchemaError>())?;
 
     Ok(())
 }